@@ -3,8 +3,9 @@ use std::ops::IndexMut;
 
 use super::piece::Piece;
 use super::piece::PieceColor;
+use super::piece::PieceType;
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct BitBoards {
     pub pieces: [u64; 12],
     pub occupancy: [u64; 2],
@@ -36,6 +37,14 @@ impl BitBoards {
         self.occupancy[0] | self.occupancy[1]
     }
 
+    // Anything besides pawns and the king, without it zugzwang is common
+    #[inline]
+    pub fn has_non_pawn_material(&self, color: PieceColor) -> bool {
+        let pawns_and_king =
+            self[Piece::new(PieceType::Pawn, color)] | self[Piece::new(PieceType::King, color)];
+        self.col_occupancy(color) & !pawns_and_king != 0
+    }
+
     pub fn to_mailbox(&self) -> [Piece; 64] {
         let mut arr = [Piece::default(); 64];
 
@@ -4,7 +4,9 @@ mod hash;
 pub mod r#move;
 pub mod movegen;
 pub mod piece;
+mod san;
 mod scores;
+mod see;
 
 use std::{
     fmt::Write,
@@ -19,14 +21,17 @@ use std::fmt;
 
 use self::{
     hash::{CASTLE_KEYS, DOUBLE_PUSH_KEYS, PIECE_KEYS, SIDE_KEY},
-    movegen::bitmasks::{bishop_attacks, rook_attacks, KING_ATTACKS, KNIGHT_ATTACKS},
+    movegen::{
+        bitmasks::{bishop_attacks, rook_attacks, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS},
+        castling_path,
+    },
     r#move::{Move, MoveType},
     scores::{POSITIONAL_SCORES, SCORES},
 };
 
 bitflags! {
 
-    #[derive(Default,Debug,Clone)]
+    #[derive(Default,Debug,Clone,Copy,PartialEq,Eq)]
     pub struct CastleFlags : u8{
         const WHITE_KINGSIDE_CASTLING = 0x1;
         const WHITE_QUEENSIDE_CASTLING = 0x2;
@@ -35,20 +40,48 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone)]
+impl CastleFlags {
+    pub fn side(color: PieceColor, kingside: bool) -> Self {
+        Self::from_bits_retain(1 << (color as u8 * 2 + !kingside as u8))
+    }
+
+    // Index of a single right into `Board::castling_rooks`
+    pub fn index(self) -> usize {
+        self.bits().trailing_zeros() as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Board {
     pub bit_boards: BitBoards,
     pub pieces: [Piece; 64],
 
     pub current_color: PieceColor,
     pub castle_flags: CastleFlags,
+    // The square of the rook each right castles with, in the order of the flags. Only the a and h
+    // files in standard chess, any file in Chess960
+    pub castling_rooks: [usize; 4],
     pub last_double: Option<u32>,
 
+    // Plies since the last capture or pawn move
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+
     pub hash: u64,
 
     eval: i32,
 }
 
+// What `make_move` can't recompute when the move is taken back
+#[derive(Debug, Clone, Copy)]
+pub struct UndoInfo {
+    castle_flags: CastleFlags,
+    last_double: Option<u32>,
+    halfmove_clock: u32,
+    hash: u64,
+    eval: i32,
+}
+
 #[derive(Debug)]
 pub enum FenError {
     NoSuchPiece(NoSuchPieceError),
@@ -56,6 +89,7 @@ pub enum FenError {
     NoSuchCastle(char),
     EnPessentNotInRange(u32),
     InvalidEnPressant(String),
+    InvalidMoveCounter(String),
     NotEnoughInfo(),
 }
 
@@ -94,6 +128,9 @@ impl Board {
 pub enum ParseMoveError {
     StringTooSmall,
     InvalidPromotionPiece,
+    InvalidSquare(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -207,16 +244,38 @@ impl Board {
             eval *= -1
         };
 
+        // KQkq stand for the outermost rook on that side of the king, Shredder-FEN and X-FEN
+        // name the file of the rook instead
         let mut castle_flags = CastleFlags::empty();
+        let mut castling_rooks = [7, 0, 63, 56];
         for c in words.next().ok_or(FenError::NotEnoughInfo())?.chars() {
-            castle_flags |= match c {
-                'K' => CastleFlags::WHITE_KINGSIDE_CASTLING,
-                'k' => CastleFlags::BLACK_KINGSIDE_CASTLING,
-                'Q' => CastleFlags::WHITE_QUEENSIDE_CASTLING,
-                'q' => CastleFlags::BLACK_QUEENSIDE_CASTLING,
-                '-' => break,
-                _ => return Err(FenError::NoSuchCastle(c)),
+            if c == '-' {
+                break;
+            }
+
+            let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+            let back_rank = 0xffu64 << (56 * color as usize);
+            let king = bit_boards[Piece::new(PieceType::King, color)] & back_rank;
+            let rooks = bit_boards[Piece::new(PieceType::Rook, color)] & back_rank;
+            if king == 0 {
+                return Err(FenError::NoSuchCastle(c));
             }
+
+            let king_square = king.trailing_zeros() as usize;
+            let rook = match c.to_ascii_lowercase() {
+                'k' => 63usize.checked_sub((rooks & !(king | (king - 1))).leading_zeros() as usize),
+                'q' => {
+                    Some((rooks & (king - 1)).trailing_zeros() as usize).filter(|&rook| rook < 64)
+                }
+                file @ 'a'..='h' => Some((file as u8 - b'a') as usize + 56 * color as usize)
+                    .filter(|&rook| rooks & (1u64 << rook) != 0),
+                _ => None,
+            }
+            .ok_or(FenError::NoSuchCastle(c))?;
+
+            let flag = CastleFlags::side(color, rook > king_square);
+            castle_flags |= flag;
+            castling_rooks[flag.index()] = rook;
         }
 
         let last_double = {
@@ -228,24 +287,133 @@ impl Board {
                 }
 
                 let bytes = word.as_bytes();
-                let square = (bytes[0] - b'a' + 8 * (bytes[1] - b'1')) as u32;
-                match square {
-                    n @ 0..=48 => {
-                        hash ^= DOUBLE_PUSH_KEYS[n as usize];
-                        Some(n)
-                    }
-                    n => return Err(FenError::EnPessentNotInRange(n)),
+                if !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+                    return Err(FenError::InvalidEnPressant(word.to_owned()));
                 }
+
+                // The FEN stores the square behind the pawn, we store the pawn itself
+                let square = (bytes[0] - b'a' + 8 * (bytes[1] - b'1')) as u32;
+                let pawn_square = match (square / 8, current_color) {
+                    (5, PieceColor::White) => square - 8,
+                    (2, PieceColor::Black) => square + 8,
+                    _ => return Err(FenError::EnPessentNotInRange(square)),
+                };
+
+                hash ^= DOUBLE_PUSH_KEYS[pawn_square as usize];
+                Some(pawn_square)
             } else {
                 None
             }
         };
 
+        // Move counters are optional, EPD and some GUIs leave them out
+        let halfmove_clock = match words.next() {
+            Some(word) => {
+                word.parse().map_err(|_| FenError::InvalidMoveCounter(word.to_owned()))?
+            }
+            None => 0,
+        };
+
+        let fullmove_number = match words.next() {
+            Some(word) => {
+                word.parse().map_err(|_| FenError::InvalidMoveCounter(word.to_owned()))?
+            }
+            None => 1,
+        };
+
         hash ^= CASTLE_KEYS[castle_flags.bits() as usize];
 
         hash ^= *SIDE_KEY * !current_color as u64;
 
-        Ok(Self { bit_boards, current_color, castle_flags, last_double, hash, eval, pieces })
+        Ok(Self {
+            bit_boards,
+            current_color,
+            castle_flags,
+            castling_rooks,
+            last_double,
+            halfmove_clock,
+            fullmove_number,
+            hash,
+            eval,
+            pieces,
+        })
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let piece = self.pieces[rank * 8 + file];
+                if piece == Piece::Empty {
+                    empty += 1;
+                    continue;
+                }
+
+                if empty != 0 {
+                    fen.push(char::from(b'0' + empty));
+                    empty = 0;
+                }
+                fen.push(char::from(piece));
+            }
+
+            if empty != 0 {
+                fen.push(char::from(b'0' + empty));
+            }
+            if rank != 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.current_color == PieceColor::White { " w " } else { " b " });
+
+        // X-FEN, the file is only written when another rook stands further out on that side
+        if self.castle_flags.is_empty() {
+            fen.push('-');
+        } else {
+            for (flag, c) in [
+                (CastleFlags::WHITE_KINGSIDE_CASTLING, 'K'),
+                (CastleFlags::WHITE_QUEENSIDE_CASTLING, 'Q'),
+                (CastleFlags::BLACK_KINGSIDE_CASTLING, 'k'),
+                (CastleFlags::BLACK_QUEENSIDE_CASTLING, 'q'),
+            ] {
+                if !self.castle_flags.contains(flag) {
+                    continue;
+                }
+
+                let rook = self.castling_rooks[flag.index()];
+                let color =
+                    if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+                let rooks = self.bit_boards[Piece::new(PieceType::Rook, color)]
+                    & (0xffu64 << (rook / 8 * 8));
+                let outer = if c.to_ascii_lowercase() == 'k' {
+                    rooks & !(2u64 << rook).wrapping_sub(1)
+                } else {
+                    rooks & ((1u64 << rook) - 1)
+                };
+
+                if outer == 0 {
+                    fen.push(c);
+                } else {
+                    let file = (b'a' + (rook % 8) as u8) as char;
+                    fen.push(if c.is_ascii_uppercase() { file.to_ascii_uppercase() } else { file });
+                }
+            }
+        }
+
+        match self.last_double {
+            Some(square) => {
+                let target =
+                    if self.current_color == PieceColor::White { square + 8 } else { square - 8 };
+                _ = write!(fen, " {}{}", char::from(b'a' + (target % 8) as u8), target / 8 + 1);
+            }
+            None => fen.push_str(" -"),
+        }
+
+        _ = write!(fen, " {} {}", self.halfmove_clock, self.fullmove_number);
+
+        fen
     }
 
     pub fn piece_at(&self, square: usize) -> Piece {
@@ -324,6 +492,135 @@ impl Board {
         false
     }
 
+    // Pieces of both colors attacking `square`, sliders are blocked by `occupancy`
+    pub fn attackers_to(&self, square: usize, occupancy: u64) -> u64 {
+        let bishop_queens = self.bit_boards[Piece::WhiteBishop]
+            | self.bit_boards[Piece::BlackBishop]
+            | self.bit_boards[Piece::WhiteQueen]
+            | self.bit_boards[Piece::BlackQueen];
+        let rook_queens = self.bit_boards[Piece::WhiteRook]
+            | self.bit_boards[Piece::BlackRook]
+            | self.bit_boards[Piece::WhiteQueen]
+            | self.bit_boards[Piece::BlackQueen];
+
+        (PAWN_ATTACKS[PieceColor::Black as usize][square] & self.bit_boards[Piece::WhitePawn])
+            | (PAWN_ATTACKS[PieceColor::White as usize][square] & self.bit_boards[Piece::BlackPawn])
+            | (KNIGHT_ATTACKS[square]
+                & (self.bit_boards[Piece::WhiteKnight] | self.bit_boards[Piece::BlackKnight]))
+            | (KING_ATTACKS[square]
+                & (self.bit_boards[Piece::WhiteKing] | self.bit_boards[Piece::BlackKing]))
+            | (bishop_attacks(square, occupancy) & bishop_queens)
+            | (rook_attacks(square, occupancy) & rook_queens)
+    }
+
+    // Whether `move` is one `generate_moves` would produce here, checks and pins are ignored.
+    // Moves read back from the transposition table are only rebuilt from their squares, so a
+    // key collision can hand over a move from another position
+    pub fn is_pseudo_legal(&self, r#move: Move) -> bool {
+        let (from, to, move_type, piece, captured) = r#move.unpack();
+        let color = self.current_color;
+        let moved = self.piece_at(from);
+        let target = self.piece_at(to);
+
+        if from == to || moved == Piece::Empty || moved.get_color() != color {
+            return false;
+        }
+
+        // Promotions carry the new piece instead of the pawn
+        if move_type != MoveType::Promote && piece != moved {
+            return false;
+        }
+
+        // Castling is the king taking its own rook
+        if r#move.is_castle() {
+            let flag = CastleFlags::side(color, move_type == MoveType::KingCastle);
+
+            return moved.get_type() == PieceType::King
+                && captured == PieceType::Empty
+                && self.castle_flags.intersects(flag)
+                && to == self.castling_rooks[flag.index()]
+                && castling_path(r#move) & self.bit_boards.occupancy() == 0;
+        }
+
+        if target != Piece::Empty && target.get_color() == color {
+            return false;
+        }
+
+        // The captured piece has to be the one on the target square, pawn captures are generated
+        // as quiet moves with a captured piece
+        let captured_ok = match move_type {
+            MoveType::EnPassantCapture => target == Piece::Empty && captured == PieceType::Pawn,
+            MoveType::Capture => target != Piece::Empty && captured == target.get_type(),
+            MoveType::QuietMove | MoveType::Promote => captured == target.get_type(),
+            _ => target == Piece::Empty && captured == PieceType::Empty,
+        };
+        if !captured_ok {
+            return false;
+        }
+
+        let to_bit = 1u64 << to;
+        let occupancy = self.bit_boards.occupancy();
+
+        if moved.get_type() == PieceType::Pawn {
+            let is_white = color == PieceColor::White;
+            let forward = if is_white { 8 } else { -8 };
+            let single = from as i32 + forward;
+            let on_last_rank = to / 8 == if is_white { 7 } else { 0 };
+            let attacks = PAWN_ATTACKS[color as usize][from] & to_bit != 0;
+
+            return match move_type {
+                MoveType::QuietMove => {
+                    !on_last_rank
+                        && if captured == PieceType::Empty { to as i32 == single } else { attacks }
+                }
+                MoveType::Promote => {
+                    on_last_rank
+                        && matches!(
+                            piece.get_type(),
+                            PieceType::Knight
+                                | PieceType::Bishop
+                                | PieceType::Rook
+                                | PieceType::Queen
+                        )
+                        && piece.get_color() == color
+                        && if captured == PieceType::Empty { to as i32 == single } else { attacks }
+                }
+                MoveType::DoublePush => {
+                    from / 8 == if is_white { 1 } else { 6 }
+                        && to as i32 == single + forward
+                        && !self.is_occupied(single as usize)
+                }
+                MoveType::EnPassantCapture => {
+                    attacks
+                        && self
+                            .last_double
+                            .is_some_and(|last_double| to as i32 == last_double as i32 + forward)
+                }
+                _ => false,
+            };
+        }
+
+        match move_type {
+            MoveType::QuietMove | MoveType::Capture
+                if move_type == MoveType::Capture || target == Piece::Empty =>
+            {
+                let attacks = match moved.get_type() {
+                    PieceType::Knight => KNIGHT_ATTACKS[from],
+                    PieceType::Bishop => bishop_attacks(from, occupancy),
+                    PieceType::Rook => rook_attacks(from, occupancy),
+                    PieceType::Queen => {
+                        bishop_attacks(from, occupancy) | rook_attacks(from, occupancy)
+                    }
+                    PieceType::King => KING_ATTACKS[from],
+                    _ => 0,
+                };
+
+                attacks & to_bit != 0
+            }
+            _ => false,
+        }
+    }
+
     pub fn is_king_attacked(&self, color: PieceColor) -> bool {
         let kings = self.bit_boards[Piece::new(PieceType::King, color)];
         if kings != 0 {
@@ -333,156 +630,158 @@ impl Board {
         }
     }
 
-    pub fn make_null_move(&mut self) {
-        if let Some(last_double) = self.last_double {
-            self.hash ^= DOUBLE_PUSH_KEYS[last_double as usize];
+    fn undo_info(&self) -> UndoInfo {
+        UndoInfo {
+            castle_flags: self.castle_flags,
+            last_double: self.last_double,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+            eval: self.eval,
         }
-        self.last_double = None;
-        self.eval *= -1;
-        self.hash ^= *SIDE_KEY;
-        self.current_color = !self.current_color;
     }
 
-    pub fn make_move(&mut self, r#move: Move, mut delta: impl MoveDeltaRecorder) {
-        let (from, to, move_type, piece, capture) = r#move.unpack();
-
-        if capture != PieceType::Empty {
-            self.bit_boards.clear_piece(to, Piece::new(capture, !self.current_color));
+    fn restore(&mut self, undo: &UndoInfo) {
+        self.castle_flags = undo.castle_flags;
+        self.last_double = undo.last_double;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+        self.eval = undo.eval;
 
-            self.hash ^= PIECE_KEYS[Piece::new(capture, !self.current_color) as usize][to];
+        self.current_color = !self.current_color;
+        if self.current_color == PieceColor::Black {
+            self.fullmove_number -= 1;
+        }
+    }
 
-            self.eval += SCORES[capture as usize]
-                + POSITIONAL_SCORES[capture as usize][to ^ (56 * self.current_color as usize)];
+    pub fn make_null_move(&mut self) -> UndoInfo {
+        let undo = self.undo_info();
 
-            if move_type == MoveType::EnPassantCapture {
-                delta.record(PieceDelta {
-                    to: 64,
-                    from: (to as i32 + if piece == Piece::WhitePawn { -8 } else { 8 }) as u32,
-                    piece: Piece::new(capture, !self.current_color),
-                })
-            } else {
-                delta.record(PieceDelta {
-                    to: 64,
-                    from: to as u32,
-                    piece: Piece::new(capture, !self.current_color),
-                });
-            }
+        if let Some(last_double) = self.last_double {
+            self.hash ^= DOUBLE_PUSH_KEYS[last_double as usize];
+        }
+        self.last_double = None;
+        self.halfmove_clock += 1;
+        if self.current_color == PieceColor::Black {
+            self.fullmove_number += 1;
         }
+        self.eval *= -1;
+        self.hash ^= *SIDE_KEY;
+        self.current_color = !self.current_color;
 
-        self.bit_boards.set_piece(to, piece);
+        undo
+    }
 
-        self.pieces[to] = piece;
+    pub fn unmake_null_move(&mut self, undo: &UndoInfo) {
+        self.restore(undo);
+    }
 
-        self.hash ^= PIECE_KEYS[piece as usize][to];
+    pub fn make_move(&mut self, r#move: Move, mut delta: impl MoveDeltaRecorder) -> UndoInfo {
+        let undo = self.undo_info();
+        let (from, to, move_type, piece, capture) = r#move.unpack();
 
-        if move_type == MoveType::Promote {
-            self.bit_boards.clear_piece(from, Piece::new(PieceType::Pawn, self.current_color));
+        if r#move.is_castle() {
+            let (king_to, rook_to) = r#move.castling_squares();
+            let (king_to, rook_to) = (king_to as usize, rook_to as usize);
+            let rook = Piece::new(PieceType::Rook, self.current_color);
 
-            self.hash ^= PIECE_KEYS[Piece::new(PieceType::Pawn, self.current_color) as usize][from];
+            // In Chess960 the king and the rook can land on each other's squares, so both are
+            // lifted before either is put down
+            self.bit_boards.clear_piece(from, piece);
+            self.bit_boards.clear_piece(to, rook);
+            self.pieces[from] = Piece::Empty;
+            self.pieces[to] = Piece::Empty;
+            self.bit_boards.set_piece(king_to, piece);
+            self.bit_boards.set_piece(rook_to, rook);
+            self.pieces[king_to] = piece;
+            self.pieces[rook_to] = rook;
 
-            self.eval -= SCORES[PieceType::Pawn as usize]
-                + POSITIONAL_SCORES[PieceType::Pawn as usize]
-                    [from ^ (56 * !self.current_color as usize)];
+            self.hash ^= PIECE_KEYS[piece as usize][from] ^ PIECE_KEYS[piece as usize][king_to];
+            self.hash ^= PIECE_KEYS[rook as usize][to] ^ PIECE_KEYS[rook as usize][rook_to];
 
-            self.eval += SCORES[piece.get_type() as usize]
-                + POSITIONAL_SCORES[piece.get_type() as usize]
-                    [to ^ (56 * !self.current_color as usize)];
+            let flip = 56 * !self.current_color as usize;
+            self.eval += POSITIONAL_SCORES[PieceType::King as usize][king_to ^ flip]
+                - POSITIONAL_SCORES[PieceType::King as usize][from ^ flip]
+                + POSITIONAL_SCORES[PieceType::Rook as usize][rook_to ^ flip]
+                - POSITIONAL_SCORES[PieceType::Rook as usize][to ^ flip];
 
-            delta.record(PieceDelta {
-                to: 64,
-                from: from as u32,
-                piece: Piece::new(PieceType::Pawn, self.current_color),
-            });
-            delta.record(PieceDelta { to: to as u32, from: 64, piece });
+            delta.record(PieceDelta { to: king_to as u32, from: from as u32, piece });
+            delta.record(PieceDelta { to: rook_to as u32, from: to as u32, piece: rook });
         } else {
-            self.hash ^= PIECE_KEYS[piece as usize][from];
-
-            self.bit_boards.clear_piece(from, piece);
-
-            self.eval += POSITIONAL_SCORES[piece.get_type() as usize]
-                [to ^ (56 * !self.current_color as usize)]
-                - POSITIONAL_SCORES[piece.get_type() as usize]
-                    [from ^ (56 * !self.current_color as usize)];
+            if capture != PieceType::Empty {
+                self.bit_boards.clear_piece(to, Piece::new(capture, !self.current_color));
+
+                self.hash ^= PIECE_KEYS[Piece::new(capture, !self.current_color) as usize][to];
+
+                self.eval += SCORES[capture as usize]
+                    + POSITIONAL_SCORES[capture as usize][to ^ (56 * self.current_color as usize)];
+
+                if move_type == MoveType::EnPassantCapture {
+                    delta.record(PieceDelta {
+                        to: 64,
+                        from: (to as i32 + if piece == Piece::WhitePawn { -8 } else { 8 }) as u32,
+                        piece: Piece::new(capture, !self.current_color),
+                    })
+                } else {
+                    delta.record(PieceDelta {
+                        to: 64,
+                        from: to as u32,
+                        piece: Piece::new(capture, !self.current_color),
+                    });
+                }
+            }
 
-            delta.record(PieceDelta { to: to as u32, from: from as u32, piece });
-        }
+            self.bit_boards.set_piece(to, piece);
 
-        self.pieces[from] = Piece::Empty;
+            self.pieces[to] = piece;
 
-        self.hash ^= CASTLE_KEYS[self.castle_flags.bits() as usize];
-        if move_type == MoveType::KingCastle || move_type == MoveType::QueenCastle {
-            let rook_from = match to {
-                // C1 => A1,
-                2 => 0,
-                // G1 => H1,
-                6 => 7,
-                //C8 => A8,
-                58 => 56,
-                //G8 => H8,
-                62 => 63,
-                _ => panic!("Castle move with invalid to square"),
-            };
+            self.hash ^= PIECE_KEYS[piece as usize][to];
 
-            let rook_to = match to {
-                // C1 => D1,
-                2 => 3,
-                // G1 => F1,
-                6 => 5,
-                //C8 => D8,
-                58 => 59,
-                //G8 => F8,
-                62 => 61,
-                _ => panic!("Castle move with invalid to square"),
-            };
+            if move_type == MoveType::Promote {
+                self.bit_boards.clear_piece(from, Piece::new(PieceType::Pawn, self.current_color));
 
-            let rook = Piece::new(PieceType::Rook, self.current_color);
+                self.hash ^=
+                    PIECE_KEYS[Piece::new(PieceType::Pawn, self.current_color) as usize][from];
 
-            self.bit_boards.set_piece(rook_to, rook);
-            self.bit_boards.clear_piece(rook_from, rook);
+                self.eval -= SCORES[PieceType::Pawn as usize]
+                    + POSITIONAL_SCORES[PieceType::Pawn as usize]
+                        [from ^ (56 * !self.current_color as usize)];
 
-            self.pieces[rook_to] = rook;
-            self.pieces[rook_from] = Piece::Empty;
+                self.eval += SCORES[piece.get_type() as usize]
+                    + POSITIONAL_SCORES[piece.get_type() as usize]
+                        [to ^ (56 * !self.current_color as usize)];
 
-            self.hash ^= PIECE_KEYS[rook as usize][rook_from];
-            self.hash ^= PIECE_KEYS[rook as usize][rook_to];
+                delta.record(PieceDelta {
+                    to: 64,
+                    from: from as u32,
+                    piece: Piece::new(PieceType::Pawn, self.current_color),
+                });
+                delta.record(PieceDelta { to: to as u32, from: 64, piece });
+            } else {
+                self.hash ^= PIECE_KEYS[piece as usize][from];
 
-            self.eval += POSITIONAL_SCORES[PieceType::Rook as usize]
-                [rook_to ^ (56 * !self.current_color as usize)]
-                - POSITIONAL_SCORES[PieceType::Rook as usize]
-                    [rook_from ^ (56 * !self.current_color as usize)];
+                self.bit_boards.clear_piece(from, piece);
 
-            delta.record(PieceDelta { to: rook_to as u32, from: rook_from as u32, piece: rook });
-        }
+                self.eval += POSITIONAL_SCORES[piece.get_type() as usize]
+                    [to ^ (56 * !self.current_color as usize)]
+                    - POSITIONAL_SCORES[piece.get_type() as usize]
+                        [from ^ (56 * !self.current_color as usize)];
 
-        if piece == Piece::WhiteRook {
-            if from == 0 {
-                self.castle_flags &= !CastleFlags::WHITE_QUEENSIDE_CASTLING;
-            } else if from == 7 {
-                self.castle_flags &= !CastleFlags::WHITE_KINGSIDE_CASTLING;
-            }
-        } else if piece == Piece::BlackRook {
-            if from == 56 {
-                self.castle_flags &= !CastleFlags::BLACK_QUEENSIDE_CASTLING;
-            } else if from == 63 {
-                self.castle_flags &= !CastleFlags::BLACK_KINGSIDE_CASTLING;
+                delta.record(PieceDelta { to: to as u32, from: from as u32, piece });
             }
+
+            self.pieces[from] = Piece::Empty;
         }
 
-        if piece == Piece::WhiteKing {
-            self.castle_flags &=
-                !(CastleFlags::WHITE_KINGSIDE_CASTLING | CastleFlags::WHITE_QUEENSIDE_CASTLING);
-        } else if piece == Piece::BlackKing {
-            self.castle_flags &=
-                !(CastleFlags::BLACK_KINGSIDE_CASTLING | CastleFlags::BLACK_QUEENSIDE_CASTLING);
+        // Moving the king loses both rights, moving or losing a rook the one it castles with
+        self.hash ^= CASTLE_KEYS[self.castle_flags.bits() as usize];
+        if piece.get_type() == PieceType::King {
+            self.castle_flags &= !(CastleFlags::side(self.current_color, true)
+                | CastleFlags::side(self.current_color, false));
         }
 
-        if capture == PieceType::Rook {
-            match to {
-                0 => self.castle_flags &= !CastleFlags::WHITE_QUEENSIDE_CASTLING,
-                7 => self.castle_flags &= !CastleFlags::WHITE_KINGSIDE_CASTLING,
-                56 => self.castle_flags &= !CastleFlags::BLACK_QUEENSIDE_CASTLING,
-                63 => self.castle_flags &= !CastleFlags::BLACK_KINGSIDE_CASTLING,
-                _ => {}
+        for (index, rook) in self.castling_rooks.into_iter().enumerate() {
+            if from == rook || to == rook {
+                self.castle_flags &= !CastleFlags::from_bits_retain(1 << index);
             }
         }
 
@@ -517,14 +816,110 @@ impl Board {
             None
         };
 
+        if capture != PieceType::Empty
+            || piece.get_type() == PieceType::Pawn
+            || move_type == MoveType::Promote
+        {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        if self.current_color == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+
         self.eval *= -1;
         self.hash ^= *SIDE_KEY;
         self.current_color = !self.current_color;
+
+        undo
+    }
+
+    // Takes back `move`, which has to be the last move made with `undo` being what it returned
+    pub fn unmake_move(&mut self, r#move: Move, undo: &UndoInfo) {
+        let (from, to, move_type, piece, capture) = r#move.unpack();
+        let color = !self.current_color;
+
+        if r#move.is_castle() {
+            let (king_to, rook_to) = r#move.castling_squares();
+            let (king_to, rook_to) = (king_to as usize, rook_to as usize);
+            let rook = Piece::new(PieceType::Rook, color);
+
+            self.bit_boards.clear_piece(king_to, piece);
+            self.bit_boards.clear_piece(rook_to, rook);
+            self.pieces[king_to] = Piece::Empty;
+            self.pieces[rook_to] = Piece::Empty;
+            self.bit_boards.set_piece(from, piece);
+            self.bit_boards.set_piece(to, rook);
+            self.pieces[from] = piece;
+            self.pieces[to] = rook;
+
+            self.restore(undo);
+            return;
+        }
+
+        let moved =
+            if move_type == MoveType::Promote { Piece::new(PieceType::Pawn, color) } else { piece };
+
+        self.bit_boards.clear_piece(to, piece);
+        self.pieces[to] = Piece::Empty;
+        self.bit_boards.set_piece(from, moved);
+        self.pieces[from] = moved;
+
+        if capture != PieceType::Empty {
+            let square = if move_type == MoveType::EnPassantCapture {
+                if color == PieceColor::White {
+                    to - 8
+                } else {
+                    to + 8
+                }
+            } else {
+                to
+            };
+
+            let captured = Piece::new(capture, !color);
+            self.bit_boards.set_piece(square, captured);
+            self.pieces[square] = captured;
+        }
+
+        self.restore(undo);
     }
 
     pub fn eval(&self) -> i32 {
         self.eval
     }
+
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+
+    // Positions where neither side can deliver mate by any sequence of legal moves
+    pub fn is_insufficient_material(&self) -> bool {
+        let bit_boards = &self.bit_boards;
+
+        let heavy_or_pawns = bit_boards[Piece::WhitePawn]
+            | bit_boards[Piece::BlackPawn]
+            | bit_boards[Piece::WhiteRook]
+            | bit_boards[Piece::BlackRook]
+            | bit_boards[Piece::WhiteQueen]
+            | bit_boards[Piece::BlackQueen];
+
+        if heavy_or_pawns != 0 {
+            return false;
+        }
+
+        let knights = bit_boards[Piece::WhiteKnight] | bit_boards[Piece::BlackKnight];
+        let bishops = bit_boards[Piece::WhiteBishop] | bit_boards[Piece::BlackBishop];
+
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+
+        // Any number of bishops that all live on the same square color
+        const DARK_SQUARES: u64 = 0xAA55AA55AA55AA55;
+        knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0)
+    }
 }
 
 #[cfg(test)]
@@ -532,14 +927,15 @@ mod tests {
 
     use std::time::Instant;
 
+    use rand::Rng;
     use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
     use crate::board::{
         hash::{CASTLE_KEYS, DOUBLE_PUSH_KEYS, PIECE_KEYS, SIDE_KEY},
-        movegen::generate_moves,
-        r#move::MoveType,
+        movegen::{generate_captures, generate_legal_moves, generate_moves, perft, perft_unmake},
+        r#move::{Move, MoveType},
         scores::{POSITIONAL_SCORES, SCORES},
-        PieceColor, PiecesDelta,
+        CastleFlags, NoDelta, Piece, PieceColor, PieceType, PiecesDelta,
     };
 
     use super::Board;
@@ -620,7 +1016,7 @@ mod tests {
     }
 
     pub fn par_perft(board: &Board, depth: u32) -> u64 {
-        let moves = generate_moves(board);
+        let moves = generate_legal_moves(board);
 
         let nodes = moves
             .par_iter()
@@ -629,32 +1025,8 @@ mod tests {
 
                 let mut delta = PiecesDelta::new();
                 new_board.make_move(r#move, &mut delta);
-                if new_board.is_king_attacked(board.current_color) {
-                    return 0;
-                }
-
-                if r#move.move_type() == MoveType::KingCastle {
-                    let castle_target =
-                        if board.current_color == PieceColor::White { 5 } else { 61 };
-                    if new_board.is_square_attacked(castle_target, new_board.current_color) {
-                        return 0;
-                    }
-
-                    if board.is_king_attacked(board.current_color) {
-                        return 0;
-                    }
-                } else if r#move.move_type() == MoveType::QueenCastle {
-                    let castle_target =
-                        if board.current_color == PieceColor::White { 3 } else { 59 };
-                    if new_board.is_square_attacked(castle_target, new_board.current_color) {
-                        return 0;
-                    }
-                    if board.is_king_attacked(board.current_color) {
-                        return 0;
-                    }
-                }
 
-                let result = perft_helper(new_board, depth - 1);
+                let result = perft(&new_board, depth - 1);
                 println!("{} {}", r#move, result);
                 result
             })
@@ -664,45 +1036,305 @@ mod tests {
         nodes
     }
 
-    fn perft_helper(board: Board, depth: u32) -> u64 {
-        if depth <= 0 {
-            return 1;
+    #[test]
+    fn legal_moves_match_pseudo_legal_filter() {
+        let boards = [
+            Board::default(),
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap(),
+            Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(),
+            Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1")
+                .unwrap(),
+            Board::from_fen("8/8/8/KPp4r/8/8/8/7k w - c6 0 2").unwrap(),
+            Board::from_fen("4k3/8/8/8/1b6/8/3P4/4K3 w - - 0 1").unwrap(),
+        ];
+
+        for board in boards {
+            let legal = generate_legal_moves(&board);
+
+            for r#move in generate_moves(&board) {
+                let mut new_board = board.clone();
+                new_board.make_move(r#move, &mut PiecesDelta::new());
+
+                let mut is_legal = !new_board.is_king_attacked(board.current_color);
+                if r#move.move_type() == MoveType::KingCastle
+                    || r#move.move_type() == MoveType::QueenCastle
+                {
+                    let passed = (r#move.from() + r#move.castling_squares().0) as usize / 2;
+                    is_legal &= !board.is_king_attacked(board.current_color)
+                        && !board.is_square_attacked(passed, !board.current_color);
+                }
+
+                assert_eq!(legal.contains(&r#move), is_legal, "{} in {}", r#move, board);
+            }
         }
+    }
 
-        let moves = generate_moves(&board);
+    fn make_unmake(board: &mut Board, depth: u32) {
+        let before = board.clone();
 
-        let mut nodes = 0;
-        for r#move in moves {
-            let mut new_board = board.clone();
+        let undo = board.make_null_move();
+        board.unmake_null_move(&undo);
+        assert_eq!(*board, before);
+
+        if depth == 0 {
+            return;
+        }
 
-            let mut delta = PiecesDelta::new();
-            new_board.make_move(r#move, &mut delta);
-            if new_board.is_king_attacked(board.current_color) {
-                continue;
+        for r#move in generate_legal_moves(board) {
+            let mut copied = board.clone();
+            copied.make_move(r#move, NoDelta);
+
+            let undo = board.make_move(r#move, NoDelta);
+            assert_eq!(*board, copied, "{} in {}", r#move, before);
+
+            make_unmake(board, depth - 1);
+
+            board.unmake_move(r#move, &undo);
+            assert_eq!(*board, before, "{} in {}", r#move, before);
+        }
+    }
+
+    #[test]
+    fn unmake_move() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+            "8/1n4N1/2k5/8/8/5K2/1N4n1/8 b - - 0 1",
+            "8/1k6/8/5N2/8/4n3/8/2K5 b - - 0 1",
+            "8/8/3K4/3Nn3/3nN3/4k3/8/8 b - - 0 1",
+            "B6b/8/8/8/2K5/4k3/8/b6B w - - 0 1",
+            "r3k2r/8/8/8/8/8/8/2R1K2R b Kkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K1R1 b Qkq - 0 1",
+            "R6r/8/8/2K5/5k2/8/8/r6R w - - 0 1",
+            "8/2k1p3/3pP3/3P2K1/8/8/8/8 b - - 0 1",
+            "8/8/8/8/8/4k3/4P3/4K3 w - - 0 1",
+            "8/3k4/3p4/8/3P4/3K4/8/8 b - - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "8/8/8/KPp4r/8/8/8/7k w - c6 0 2",
+        ];
+
+        for fen in fens {
+            let mut board = Board::from_fen(fen).unwrap();
+            make_unmake(&mut board, 3);
+
+            let mut board = Board::from_fen(fen).unwrap();
+            assert_eq!(perft_unmake(&mut board, 4), perft(&board, 4), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn pseudo_legal_moves() {
+        let boards = [
+            Board::default(),
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap(),
+            Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1")
+                .unwrap(),
+            Board::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap(),
+            Board::from_fen("8/8/8/KPp4r/8/8/8/7k w - c6 0 2").unwrap(),
+            Board::from_fen("r3k2r/8/8/8/8/8/8/R3K1R1 b Qkq - 0 1").unwrap(),
+            Board::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3")
+                .unwrap(),
+        ];
+        let other_moves: Vec<Move> = boards.iter().flat_map(generate_moves).collect();
+        let mut rng = rand::thread_rng();
+
+        for board in &boards {
+            let moves = generate_moves(board);
+
+            for &r#move in moves.iter() {
+                assert!(
+                    board.is_pseudo_legal(r#move),
+                    "{:?} {:?} in {}",
+                    r#move,
+                    r#move.move_type(),
+                    board
+                );
             }
 
-            if r#move.move_type() == MoveType::KingCastle {
-                let castle_target = if board.current_color == PieceColor::White { 5 } else { 61 };
-                if new_board.is_square_attacked(castle_target, new_board.current_color) {
-                    continue;
-                }
-                if board.is_king_attacked(board.current_color) {
-                    continue;
-                }
-            } else if r#move.move_type() == MoveType::QueenCastle {
-                let castle_target = if board.current_color == PieceColor::White { 3 } else { 59 };
-                if new_board.is_square_attacked(castle_target, new_board.current_color) {
-                    continue;
-                }
-                if board.is_king_attacked(board.current_color) {
-                    continue;
+            // Completely random moves are nearly always rejected by the first checks, generated
+            // moves with one field changed and moves of the other positions come much closer
+            for _ in 0..200000 {
+                let (from, to, move_type, piece, captured) =
+                    moves[rng.gen_range(0..moves.len())].unpack();
+                let (from, to) = (from as u32, to as u32);
+                let square = rng.gen_range(0..64);
+                let random_type = MoveType::from(rng.gen_range(0..=6));
+                let random_piece = Piece::from(rng.gen_range(0..=12));
+                let random_captured = PieceType::from(rng.gen_range(0..=6));
+
+                let r#move = match rng.gen_range(0..7) {
+                    0 => Move::new(
+                        square,
+                        rng.gen_range(0..64),
+                        random_type,
+                        random_piece,
+                        random_captured,
+                    ),
+                    1 => Move::new(square, to, move_type, piece, captured),
+                    2 => Move::new(from, square, move_type, piece, captured),
+                    3 => Move::new(from, to, random_type, piece, captured),
+                    4 => Move::new(from, to, move_type, random_piece, captured),
+                    5 => Move::new(from, to, move_type, piece, random_captured),
+                    _ => other_moves[rng.gen_range(0..other_moves.len())],
+                };
+
+                assert_eq!(
+                    board.is_pseudo_legal(r#move),
+                    moves.contains(&r#move),
+                    "{:?} {:?} {:?} {:?} in {}",
+                    r#move,
+                    r#move.move_type(),
+                    r#move.piece(),
+                    r#move.captured(),
+                    board
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn chess960_perft() {
+        let fen_tests = [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", 5, 8_146_062),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", 5, 16_253_601),
+            ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", 5, 6_417_013),
+            ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", 4, 382_958),
+            ("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", 4, 1_171_749),
+            ("qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9", 4, 824_055),
+            ("q1bnrkr1/ppppp2p/2n2p2/4b1p1/2NP4/8/PPP1PPPP/QNB1RRKB w ge - 1 9", 4, 732_757),
+            ("qbn1brkr/ppp1p1p1/2n4p/3p1p2/P7/6PP/QPPPPP2/1BNNBRKR w HFhf - 0 9", 4, 465_806),
+            ("qnnbbrkr/1p2ppp1/2pp3p/p7/1P5P/2NP4/P1P1PPP1/Q1NBBRKR w HFhf - 0 9", 4, 384_260),
+            ("1rqbkrbn/1ppppp1p/1n6/p1N3p1/8/2P4P/PP1PPPP1/1RQBKRBN w FBfb - 0 9", 4, 287_739),
+            ("rbbqn1kr/pp2p1pp/6n1/2pp1p2/2P4P/P7/BP1PPPP1/R1BQNNKR w HAha - 0 9", 4, 890_435),
+            ("rqbbknr1/1ppp2pp/p5n1/4pp2/P7/1PP5/1Q1PPPPP/R1BBKNRN w GAga - 0 9", 4, 408_207),
+            ("rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1", 5, 4_973_573),
+            ("bbrknnrq/pppppppp/8/8/8/8/PPPPPPPP/BBRKNNRQ w GCgc - 0 1", 5, 6_019_548),
+        ];
+
+        for (fen, depth, nodes) in fen_tests {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(perft(&board, depth), nodes, "{}", fen);
+            assert_eq!(Board::from_fen(&board.to_fen()).unwrap(), board, "{}", fen);
+
+            let moves = generate_moves(&board);
+            assert!(
+                generate_captures(&board).iter().all(|r#move| moves.contains(r#move)),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn chess960_castling_rights() {
+        // K and Q are the outermost rooks, a file picks an inner one
+        for (fen, rooks, castling) in [
+            ("rk2r3/8/8/8/8/8/8/RK2R1R1 w KQkq - 0 1", [6, 0, 60, 56], "KQkq"),
+            ("rk2r3/8/8/8/8/8/8/RK2R1R1 w EAea - 0 1", [4, 0, 60, 56], "EQkq"),
+            ("rk2r3/8/8/8/8/8/8/RK2R1R1 w Ge - 0 1", [6, 0, 60, 56], "Kk"),
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            for (flag, rook) in CastleFlags::all().iter().zip(rooks) {
+                if board.castle_flags.contains(flag) {
+                    assert_eq!(board.castling_rooks[flag.index()], rook, "{}", fen);
                 }
             }
+            assert_eq!(board.to_fen().split(' ').nth(2), Some(castling));
+        }
+
+        assert!(Board::from_fen("rk6/8/8/8/8/8/8/RK6 w K - 0 1").is_err());
+        assert!(Board::from_fen("rk6/8/8/8/8/8/8/RK6 w C - 0 1").is_err());
+
+        // The king stays on g1 and the rook jumps over it from h1 to f1
+        let mut board = Board::from_fen("6k1/8/8/8/8/8/8/4R1KR w K - 0 1").unwrap();
+        let castle = generate_legal_moves(&board)
+            .into_iter()
+            .find(|r#move| r#move.move_type() == MoveType::KingCastle)
+            .unwrap();
+        assert_eq!((castle.from(), castle.to()), (6, 7));
+        assert_eq!(castle.to_string(), "g1g1");
+        board.make_move(castle, NoDelta);
+        assert_eq!(board.to_fen(), "6k1/8/8/8/8/8/8/4RRK1 b - - 1 1");
+    }
 
-            let result = perft_helper(new_board, depth - 1);
-            nodes += result;
+    #[test]
+    fn fen_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+            "8/1n4N1/2k5/8/8/5K2/1N4n1/8 b - - 0 1",
+            "8/1k6/8/5N2/8/4n3/8/2K5 b - - 0 1",
+            "8/8/3K4/3Nn3/3nN3/4k3/8/8 b - - 0 1",
+            "B6b/8/8/8/2K5/4k3/8/b6B w - - 0 1",
+            "r3k2r/8/8/8/8/8/8/2R1K2R b Kkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K1R1 b Qkq - 0 1",
+            "R6r/8/8/2K5/5k2/8/8/r6R w - - 0 1",
+            "8/2k1p3/3pP3/3P2K1/8/8/8/8 b - - 0 1",
+            "8/8/8/8/8/4k3/4P3/4K3 w - - 0 1",
+            "8/3k4/3p4/8/3P4/3K4/8/8 b - - 0 1",
+        ];
+
+        for fen in fens.iter().chain(crate::BENCHES.iter()) {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), *fen);
         }
+    }
 
-        nodes
+    #[test]
+    fn move_counters() {
+        let mut board = Board::default();
+
+        for (r#move, fen) in [
+            (
+                Move::new(12, 28, MoveType::DoublePush, Piece::WhitePawn, PieceType::Empty),
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            ),
+            (
+                Move::new(62, 45, MoveType::QuietMove, Piece::BlackKnight, PieceType::Empty),
+                "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2",
+            ),
+            (
+                Move::new(5, 33, MoveType::QuietMove, Piece::WhiteBishop, PieceType::Empty),
+                "rnbqkb1r/pppppppp/5n2/1B6/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 2 2",
+            ),
+            (
+                Move::new(45, 28, MoveType::Capture, Piece::BlackKnight, PieceType::Pawn),
+                "rnbqkb1r/pppppppp/8/1B6/4n3/8/PPPP1PPP/RNBQK1NR w KQkq - 0 3",
+            ),
+        ] {
+            board.make_move(r#move, NoDelta);
+            assert_eq!(board.to_fen(), fen);
+        }
+
+        board.make_null_move();
+        assert_eq!(board.to_fen(), "rnbqkb1r/pppppppp/8/1B6/4n3/8/PPPP1PPP/RNBQK1NR b KQkq - 1 3");
+    }
+
+    #[test]
+    fn insufficient_material() {
+        for (fen, drawn) in [
+            ("8/8/4k3/8/8/3K4/8/8 w - - 0 1", true),
+            ("8/8/4k3/8/8/3KB3/8/8 w - - 0 1", true),
+            ("8/8/4k3/8/8/3KN3/8/8 b - - 0 1", true),
+            ("8/8/3bk3/8/8/3KB3/8/8 w - - 0 1", true),
+            ("8/8/2b1k3/8/8/3KB3/8/8 w - - 0 1", false),
+            ("8/8/4k3/8/8/3KNN2/8/8 w - - 0 1", false),
+            ("8/8/2n1k3/8/8/3KB3/8/8 w - - 0 1", false),
+            ("8/8/4k3/8/8/3KP3/8/8 w - - 0 1", false),
+            ("8/8/4k3/8/8/3KR3/8/8 w - - 0 1", false),
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().is_insufficient_material(), drawn, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn non_pawn_material() {
+        let board = Board::from_fen("8/4kp2/8/8/8/3KN3/3P4/8 w - - 0 1").unwrap();
+        assert!(board.bit_boards.has_non_pawn_material(PieceColor::White));
+        assert!(!board.bit_boards.has_non_pawn_material(PieceColor::Black));
+        assert!(Board::default().bit_boards.has_non_pawn_material(PieceColor::Black));
     }
 }
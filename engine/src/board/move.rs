@@ -5,17 +5,13 @@ use super::{Piece, PieceType};
 #[derive(Default, Clone, Copy, Eq, PartialEq)]
 pub struct Move(pub u32);
 
+// Castling is written as the king's move, like in standard chess
 impl fmt::Display for Move {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let to = if self.is_castle() { self.castling_squares().0 } else { self.to() };
         let from_file: char = (b'a' + (self.from() % 8) as u8).into();
-        let to_file: char = (b'a' + (self.to() % 8) as u8).into();
-        f.write_fmt(format_args!(
-            "{}{}{}{}",
-            from_file,
-            self.from() / 8 + 1,
-            to_file,
-            self.to() / 8 + 1
-        ))?;
+        let to_file: char = (b'a' + (to % 8) as u8).into();
+        f.write_fmt(format_args!("{}{}{}{}", from_file, self.from() / 8 + 1, to_file, to / 8 + 1))?;
 
         if self.move_type() == MoveType::Promote {
             f.write_str(&self.piece().to_string().to_lowercase())?;
@@ -27,15 +23,10 @@ impl fmt::Display for Move {
 
 impl fmt::Debug for Move {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let to = if self.is_castle() { self.castling_squares().0 } else { self.to() };
         let from_file: char = (b'a' + (self.from() % 8) as u8).into();
-        let to_file: char = (b'a' + (self.to() % 8) as u8).into();
-        f.write_fmt(format_args!(
-            "{}{}{}{}",
-            from_file,
-            self.from() / 8 + 1,
-            to_file,
-            self.to() / 8 + 1
-        ))?;
+        let to_file: char = (b'a' + (to % 8) as u8).into();
+        f.write_fmt(format_args!("{}{}{}{}", from_file, self.from() / 8 + 1, to_file, to / 8 + 1))?;
 
         if self.move_type() == MoveType::Promote {
             f.write_str(&self.piece().to_string().to_lowercase())?;
@@ -116,6 +107,23 @@ impl Move {
         PieceType::from(((self.0 >> 20) & 0b111) as u8)
     }
 
+    #[inline]
+    pub fn is_castle(self) -> bool {
+        matches!(self.move_type(), MoveType::KingCastle | MoveType::QueenCastle)
+    }
+
+    // Castling moves are stored as the king taking its own rook, so they also work for Chess960.
+    // Where the king and the rook end up, on the g and f or the c and d files
+    #[inline]
+    pub fn castling_squares(self) -> (u32, u32) {
+        let rank = self.to() / 8 * 8;
+        if self.move_type() == MoveType::KingCastle {
+            (rank + 6, rank + 5)
+        } else {
+            (rank + 2, rank + 3)
+        }
+    }
+
     #[inline]
     pub fn set_from(&mut self, to: u32) {
         self.0 &= !0b111111;
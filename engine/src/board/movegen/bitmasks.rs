@@ -7,6 +7,10 @@ pub static KNIGHT_ATTACKS: [u64; 64] = generate_knight_attacks();
 #[dynamic]
 pub static KING_ATTACKS: [u64; 64] = generate_king_attacks();
 
+// Indexed by the color of the attacking pawn
+#[dynamic]
+pub static PAWN_ATTACKS: [[u64; 64]; 2] = generate_pawn_attacks();
+
 #[dynamic]
 pub static BISHOP_MASKS: [u64; 64] = generate_bishop_masks();
 #[dynamic]
@@ -205,6 +209,27 @@ fn generate_king_attacks() -> [u64; 64] {
     })
 }
 
+fn generate_pawn_attacks() -> [[u64; 64]; 2] {
+    from_fn(|color| {
+        let direction = if color == 0 { 1 } else { -1 };
+        from_fn(|square| {
+            let mut bitmask = 0;
+            let rank = (square / 8) as i32;
+            let file = (square % 8) as i32;
+            for file_offset in [-1, 1] {
+                let target_rank = rank + direction;
+                let target_file = file + file_offset;
+
+                if (0..8).contains(&target_rank) && (0..8).contains(&target_file) {
+                    let target_square = target_rank * 8 + target_file;
+                    bitmask |= 1u64 << target_square;
+                }
+            }
+            bitmask
+        })
+    })
+}
+
 fn directional_attack(square: usize, xdir: i32, ydir: i32, occupancy: u64) -> u64 {
     let mut attack_mask = 0u64;
 
@@ -412,6 +437,20 @@ pub fn rook_attacks(square: usize, occupancy: u64) -> u64 {
     ROOK_ATTACKS[square][magic_key(ROOK_MAGICS[square], occupancy & mask, mask.count_ones())]
 }
 
+// Squares strictly between two squares on a shared rank, file or diagonal, 0 if not aligned
+pub fn between(from: usize, to: usize) -> u64 {
+    let from_bit = 1u64 << from;
+    let to_bit = 1u64 << to;
+
+    if rook_attacks(from, 0) & to_bit != 0 {
+        rook_attacks(from, to_bit) & rook_attacks(to, from_bit)
+    } else if bishop_attacks(from, 0) & to_bit != 0 {
+        bishop_attacks(from, to_bit) & bishop_attacks(to, from_bit)
+    } else {
+        0
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::{
@@ -6,13 +6,13 @@ use std::{
 use super::CastleFlags;
 
 use self::bitmasks::{
-    magic_key, BISHOP_ATTACKS, BISHOP_MAGICS, BISHOP_MASKS, KING_ATTACKS, KNIGHT_ATTACKS,
-    ROOK_ATTACKS, ROOK_MAGICS, ROOK_MASKS,
+    between, bishop_attacks, magic_key, rook_attacks, BISHOP_ATTACKS, BISHOP_MAGICS, BISHOP_MASKS,
+    KING_ATTACKS, KNIGHT_ATTACKS, ROOK_ATTACKS, ROOK_MAGICS, ROOK_MASKS,
 };
 
 use super::{
     r#move::{Move, MoveType},
-    Board, Piece, PieceColor, PieceType,
+    Board, NoDelta, Piece, PieceColor, PieceType,
 };
 
 pub mod bitmasks;
@@ -74,6 +74,140 @@ impl Moves {
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
+
+    pub fn retain(&mut self, mut f: impl FnMut(Move) -> bool) {
+        let mut len = 0;
+        for i in 0..self.len {
+            if f(self.moves[i]) {
+                self.moves[len] = self.moves[i];
+                len += 1;
+            }
+        }
+        self.len = len;
+    }
+}
+
+// Squares that have to be empty for castling, apart from where the king and the rook stand
+pub fn castling_path(r#move: Move) -> u64 {
+    let (from, to) = (r#move.from() as usize, r#move.to() as usize);
+    let (king_to, rook_to) = r#move.castling_squares();
+    let (king_to, rook_to) = (king_to as usize, rook_to as usize);
+
+    let path = between(from, king_to) | between(to, rook_to) | 1 << king_to | 1 << rook_to;
+    path & !(1u64 << from) & !(1u64 << to)
+}
+
+// Check and pin information for the side to move, used to filter pseudo-legal moves
+// without making them
+pub struct LegalityMasks {
+    king_square: usize,
+    checkers: u64,
+    // Squares a non-king move has to land on to resolve a check, all squares if not in check
+    check_mask: u64,
+    pinned: u64,
+    // (pinned piece, squares it may move to)
+    pin_rays: [(u64, u64); 8],
+    pin_count: usize,
+}
+
+impl LegalityMasks {
+    pub fn new(board: &Board) -> Self {
+        let color = board.current_color;
+        let own = board.bit_boards.col_occupancy(color);
+        let enemy = board.bit_boards.col_occupancy(!color);
+        let occupancy = own | enemy;
+
+        let king_square =
+            board.bit_boards[Piece::new(PieceType::King, color)].trailing_zeros() as usize;
+
+        let checkers = board.attackers_to(king_square, occupancy) & enemy;
+
+        let check_mask = match checkers.count_ones() {
+            0 => !0,
+            1 => checkers | between(king_square, checkers.trailing_zeros() as usize),
+            _ => 0,
+        };
+
+        let enemy_queens = board.bit_boards[Piece::new(PieceType::Queen, !color)];
+        let rook_queens = board.bit_boards[Piece::new(PieceType::Rook, !color)] | enemy_queens;
+        let bishop_queens = board.bit_boards[Piece::new(PieceType::Bishop, !color)] | enemy_queens;
+
+        let mut snipers = (rook_attacks(king_square, enemy) & rook_queens)
+            | (bishop_attacks(king_square, enemy) & bishop_queens);
+
+        let mut pinned = 0;
+        let mut pin_rays = [(0, 0); 8];
+        let mut pin_count = 0;
+        while snipers != 0 {
+            let sniper = snipers.trailing_zeros() as usize;
+            let ray = between(king_square, sniper);
+            let blockers = ray & occupancy;
+
+            if blockers.count_ones() == 1 && blockers & own != 0 {
+                pinned |= blockers;
+                pin_rays[pin_count] = (blockers, ray | (1u64 << sniper));
+                pin_count += 1;
+            }
+
+            snipers &= snipers - 1;
+        }
+
+        Self { king_square, checkers, check_mask, pinned, pin_rays, pin_count }
+    }
+
+    pub fn in_check(&self) -> bool {
+        self.checkers != 0
+    }
+
+    pub fn is_legal(&self, board: &Board, r#move: Move) -> bool {
+        let (from, to, move_type, piece, _) = r#move.unpack();
+        let color = board.current_color;
+        let enemy = board.bit_boards.col_occupancy(!color);
+
+        if piece.get_type() == PieceType::King {
+            let occupancy = board.bit_boards.occupancy() & !(1u64 << from);
+
+            if r#move.is_castle() {
+                if self.in_check() {
+                    return false;
+                }
+
+                // Without the castling rook, which can shield the king's path in Chess960
+                let occupancy = occupancy & !(1u64 << to);
+                let king_to = r#move.castling_squares().0 as usize;
+                return (from.min(king_to)..=from.max(king_to))
+                    .all(|square| board.attackers_to(square, occupancy) & enemy == 0);
+            }
+
+            return board.attackers_to(to, occupancy) & enemy == 0;
+        }
+
+        let from_bit = 1u64 << from;
+        let to_bit = 1u64 << to;
+
+        if move_type == MoveType::EnPassantCapture {
+            // The captured pawn and the capturing pawn leave the same rank at once, so just
+            // look at the king with the resulting occupancy
+            let captured_bit = if color == PieceColor::White { to_bit >> 8 } else { to_bit << 8 };
+            let occupancy = (board.bit_boards.occupancy() & !from_bit & !captured_bit) | to_bit;
+
+            return board.attackers_to(self.king_square, occupancy) & enemy & !captured_bit == 0;
+        }
+
+        if to_bit & self.check_mask == 0 {
+            return false;
+        }
+
+        if from_bit & self.pinned != 0 {
+            for &(pinned, ray) in &self.pin_rays[0..self.pin_count] {
+                if pinned == from_bit {
+                    return ray & to_bit != 0;
+                }
+            }
+        }
+
+        true
+    }
 }
 
 pub fn generate_moves(board: &Board) -> Moves {
@@ -97,65 +231,17 @@ pub fn generate_moves(board: &Board) -> Moves {
     }
 
     {
-        let is_white = board.current_color == PieceColor::White;
-        const WHITE_KINGSIDE_EMPTY: u64 = 0x0000000000000060u64; // Squares f1 and g1 are empty
-        const BLACK_KINGSIDE_EMPTY: u64 = 0x6000000000000000u64; // Squares f8 and g8 are empty
-        const WHITE_QUEENSIDE_EMPTY: u64 = 0x000000000000000Eu64; // Squares b1, c1, and d1 are empty
-        const BLACK_QUEENSIDE_EMPTY: u64 = 0x0E00000000000000u64; // Squares b8, c8, and d8 are empty
-
-        let square =
-            board.bit_boards[Piece::new(PieceType::King, board.current_color)].trailing_zeros();
+        let king = Piece::new(PieceType::King, board.current_color);
+        let square = board.bit_boards[king].trailing_zeros();
 
         if square < 64 {
-            let can_castle_kingside = board.castle_flags.intersects(if is_white {
-                CastleFlags::WHITE_KINGSIDE_CASTLING
-            } else {
-                CastleFlags::BLACK_KINGSIDE_CASTLING
-            });
-
-            if can_castle_kingside {
-                let kingside_empty =
-                    if is_white { WHITE_KINGSIDE_EMPTY } else { BLACK_KINGSIDE_EMPTY };
-
-                if (kingside_empty & board.bit_boards.occupancy()) == 0 {
-                    let target = if is_white { 6 } else { 62 };
-                    moves.push(Move::new(
-                        square,
-                        target,
-                        MoveType::KingCastle,
-                        Piece::new(PieceType::King, board.current_color),
-                        PieceType::Empty,
-                    ));
-                }
-            }
-
-            let can_castle_queenside = board.castle_flags.intersects(if is_white {
-                CastleFlags::WHITE_QUEENSIDE_CASTLING
-            } else {
-                CastleFlags::BLACK_QUEENSIDE_CASTLING
-            });
-
-            if can_castle_queenside {
-                let queenside_empty =
-                    if is_white { WHITE_QUEENSIDE_EMPTY } else { BLACK_QUEENSIDE_EMPTY };
-
-                if (queenside_empty & board.bit_boards.occupancy()) == 0 {
-                    let target = if is_white { 2 } else { 58 };
-                    moves.push(Move::new(
-                        square,
-                        target,
-                        MoveType::QueenCastle,
-                        Piece::new(PieceType::King, board.current_color),
-                        PieceType::Empty,
-                    ));
-                }
-            }
+            generate_castles(board, &mut moves, square);
 
             generate_piece_moves(
                 board,
                 &mut moves,
                 square as usize,
-                Piece::new(PieceType::King, board.current_color),
+                king,
                 KING_ATTACKS[square as usize]
                     & !board.bit_boards.col_occupancy(board.current_color),
             );
@@ -308,65 +394,17 @@ pub fn generate_captures(board: &Board) -> Moves {
     generate_pawn_captures(board, &mut moves);
 
     {
-        let is_white = board.current_color == PieceColor::White;
-        const WHITE_KINGSIDE_EMPTY: u64 = 0x0000000000000060u64; // Squares f1 and g1 are empty
-        const BLACK_KINGSIDE_EMPTY: u64 = 0x6000000000000000u64; // Squares f8 and g8 are empty
-        const WHITE_QUEENSIDE_EMPTY: u64 = 0x000000000000000Eu64; // Squares b1, c1, and d1 are empty
-        const BLACK_QUEENSIDE_EMPTY: u64 = 0x0E00000000000000u64; // Squares b8, c8, and d8 are empty
-
-        let square =
-            board.bit_boards[Piece::new(PieceType::King, board.current_color)].trailing_zeros();
+        let king = Piece::new(PieceType::King, board.current_color);
+        let square = board.bit_boards[king].trailing_zeros();
 
         if square < 64 {
-            let can_castle_kingside = board.castle_flags.intersects(if is_white {
-                CastleFlags::WHITE_KINGSIDE_CASTLING
-            } else {
-                CastleFlags::BLACK_KINGSIDE_CASTLING
-            });
-
-            if can_castle_kingside {
-                let kingside_empty =
-                    if is_white { WHITE_KINGSIDE_EMPTY } else { BLACK_KINGSIDE_EMPTY };
-
-                if (kingside_empty & board.bit_boards.occupancy()) == 0 {
-                    let target = if is_white { 6 } else { 62 };
-                    moves.push(Move::new(
-                        square,
-                        target,
-                        MoveType::KingCastle,
-                        Piece::new(PieceType::King, board.current_color),
-                        PieceType::Empty,
-                    ));
-                }
-            }
-
-            let can_castle_queenside = board.castle_flags.intersects(if is_white {
-                CastleFlags::WHITE_QUEENSIDE_CASTLING
-            } else {
-                CastleFlags::BLACK_QUEENSIDE_CASTLING
-            });
-
-            if can_castle_queenside {
-                let queenside_empty =
-                    if is_white { WHITE_QUEENSIDE_EMPTY } else { BLACK_QUEENSIDE_EMPTY };
-
-                if (queenside_empty & board.bit_boards.occupancy()) == 0 {
-                    let target = if is_white { 2 } else { 58 };
-                    moves.push(Move::new(
-                        square,
-                        target,
-                        MoveType::QueenCastle,
-                        Piece::new(PieceType::King, board.current_color),
-                        PieceType::Empty,
-                    ));
-                }
-            }
+            generate_castles(board, &mut moves, square);
 
             generate_piece_moves(
                 board,
                 &mut moves,
                 square as usize,
-                Piece::new(PieceType::King, board.current_color),
+                king,
                 KING_ATTACKS[square as usize]
                     & !board.bit_boards.col_occupancy(board.current_color)
                     & board.bit_boards.col_occupancy(!board.current_color),
@@ -377,6 +415,76 @@ pub fn generate_captures(board: &Board) -> Moves {
     moves
 }
 
+// The king takes its own rook to castle, so the path between them has to be empty
+fn generate_castles(board: &Board, moves: &mut Moves, square: u32) {
+    let king = Piece::new(PieceType::King, board.current_color);
+
+    for (kingside, move_type) in [(true, MoveType::KingCastle), (false, MoveType::QueenCastle)] {
+        let flag = CastleFlags::side(board.current_color, kingside);
+        if !board.castle_flags.intersects(flag) {
+            continue;
+        }
+
+        let rook = board.castling_rooks[flag.index()] as u32;
+        let r#move = Move::new(square, rook, move_type, king, PieceType::Empty);
+        if castling_path(r#move) & board.bit_boards.occupancy() == 0 {
+            moves.push(r#move);
+        }
+    }
+}
+
+pub fn generate_legal_moves(board: &Board) -> Moves {
+    let mut moves = generate_moves(board);
+    let masks = LegalityMasks::new(board);
+
+    moves.retain(|r#move| masks.is_legal(board, r#move));
+    moves
+}
+
+pub fn generate_legal_captures(board: &Board) -> Moves {
+    let mut moves = generate_captures(board);
+    let masks = LegalityMasks::new(board);
+
+    moves.retain(|r#move| masks.is_legal(board, r#move));
+    moves
+}
+
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    let moves = generate_legal_moves(board);
+
+    if depth <= 1 {
+        return if depth == 0 { 1 } else { moves.len() as u64 };
+    }
+
+    let mut nodes = 0;
+    for r#move in moves {
+        let mut new_board = board.clone();
+        new_board.make_move(r#move, NoDelta);
+
+        nodes += perft(&new_board, depth - 1);
+    }
+
+    nodes
+}
+
+// Same count as `perft`, but every move is taken back instead of copying the board
+pub fn perft_unmake(board: &mut Board, depth: u32) -> u64 {
+    let moves = generate_legal_moves(board);
+
+    if depth <= 1 {
+        return if depth == 0 { 1 } else { moves.len() as u64 };
+    }
+
+    let mut nodes = 0;
+    for r#move in moves {
+        let undo = board.make_move(r#move, NoDelta);
+        nodes += perft_unmake(board, depth - 1);
+        board.unmake_move(r#move, &undo);
+    }
+
+    nodes
+}
+
 #[inline]
 fn generate_sliding_moves(
     board: &Board,
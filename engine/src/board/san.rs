@@ -0,0 +1,229 @@
+use super::{
+    movegen::generate_legal_moves,
+    piece::{Piece, PieceColor, PieceType},
+    r#move::{Move, MoveType},
+    Board, NoDelta, ParseMoveError,
+};
+
+fn piece_letter(piece_type: PieceType) -> char {
+    char::from(Piece::new(piece_type, PieceColor::White))
+}
+
+fn push_square(san: &mut String, square: u32) {
+    san.push((b'a' + (square % 8) as u8) as char);
+    san.push((b'1' + (square / 8) as u8) as char);
+}
+
+impl Move {
+    pub fn to_san(self, board: &Board) -> String {
+        let mut san = String::new();
+        let (from, to, move_type, _, captured) = self.unpack();
+        let moving = board.piece_at(from).get_type();
+
+        if move_type == MoveType::KingCastle {
+            san.push_str("O-O");
+        } else if move_type == MoveType::QueenCastle {
+            san.push_str("O-O-O");
+        } else if moving == PieceType::Pawn {
+            if captured != PieceType::Empty {
+                san.push((b'a' + (from % 8) as u8) as char);
+                san.push('x');
+            }
+            push_square(&mut san, to as u32);
+
+            if move_type == MoveType::Promote {
+                san.push('=');
+                san.push(piece_letter(self.piece().get_type()));
+            }
+        } else {
+            san.push(piece_letter(moving));
+
+            // Other pieces of the same type that can reach the same square
+            let others: Vec<Move> = generate_legal_moves(board)
+                .into_iter()
+                .filter(|other| {
+                    other.to() as usize == to
+                        && other.from() as usize != from
+                        && board.piece_at(other.from() as usize).get_type() == moving
+                })
+                .collect();
+
+            if !others.is_empty() {
+                let same_file = others.iter().any(|other| other.from() as usize % 8 == from % 8);
+                let same_rank = others.iter().any(|other| other.from() as usize / 8 == from / 8);
+
+                if !same_file {
+                    san.push((b'a' + (from % 8) as u8) as char);
+                } else if !same_rank {
+                    san.push((b'1' + (from / 8) as u8) as char);
+                } else {
+                    push_square(&mut san, from as u32);
+                }
+            }
+
+            if captured != PieceType::Empty {
+                san.push('x');
+            }
+            push_square(&mut san, to as u32);
+        }
+
+        let mut new_board = board.clone();
+        new_board.make_move(self, NoDelta);
+
+        if new_board.is_king_attacked(new_board.current_color) {
+            san.push(if generate_legal_moves(&new_board).is_empty() { '#' } else { '+' });
+        }
+
+        san
+    }
+}
+
+impl Board {
+    pub fn parse_san(&self, san: &str) -> Result<Move, ParseMoveError> {
+        let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let text = text.strip_suffix("e.p.").unwrap_or(text).trim_end();
+
+        let legal = generate_legal_moves(self);
+
+        let castle = match text {
+            "O-O" | "0-0" => Some(MoveType::KingCastle),
+            "O-O-O" | "0-0-0" => Some(MoveType::QueenCastle),
+            _ => None,
+        };
+        if let Some(castle) = castle {
+            return legal
+                .into_iter()
+                .find(|r#move| r#move.move_type() == castle)
+                .ok_or_else(|| ParseMoveError::IllegalMove(san.to_owned()));
+        }
+
+        let mut chars: Vec<char> = text.chars().collect();
+
+        let moving = match chars.first() {
+            Some(&c) if "NBRQK".contains(c) => {
+                chars.remove(0);
+                c.to_ascii_lowercase().to_string().parse::<PieceType>().unwrap()
+            }
+            Some(_) => PieceType::Pawn,
+            None => return Err(ParseMoveError::StringTooSmall),
+        };
+
+        // Accept both "e8=Q" and "e8Q"
+        let promotion = match chars.last() {
+            Some(&c) if "NBRQnbrq".contains(c) && moving == PieceType::Pawn => {
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+                Some(c.to_ascii_lowercase().to_string().parse::<PieceType>().unwrap())
+            }
+            _ => None,
+        };
+
+        if chars.len() < 2 {
+            return Err(ParseMoveError::StringTooSmall);
+        }
+
+        let (file, rank) = (chars[chars.len() - 2], chars[chars.len() - 1]);
+        if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+            return Err(ParseMoveError::InvalidSquare(san.to_owned()));
+        }
+        let to = (file as u32 - 'a' as u32) + 8 * (rank as u32 - '1' as u32);
+
+        let mut from_file = None;
+        let mut from_rank = None;
+        for &c in &chars[0..chars.len() - 2] {
+            match c {
+                'a'..='h' => from_file = Some(c as u32 - 'a' as u32),
+                '1'..='8' => from_rank = Some(c as u32 - '1' as u32),
+                'x' | ':' | '-' => {}
+                _ => return Err(ParseMoveError::InvalidSquare(san.to_owned())),
+            }
+        }
+
+        let mut candidates = legal.into_iter().filter(|r#move| {
+            r#move.to() == to
+                && self.piece_at(r#move.from() as usize).get_type() == moving
+                && r#move.move_type() != MoveType::KingCastle
+                && r#move.move_type() != MoveType::QueenCastle
+                && from_file.is_none_or(|file| r#move.from() % 8 == file)
+                && from_rank.is_none_or(|rank| r#move.from() / 8 == rank)
+                && match promotion {
+                    Some(piece_type) => {
+                        r#move.move_type() == MoveType::Promote
+                            && r#move.piece().get_type() == piece_type
+                    }
+                    None => r#move.move_type() != MoveType::Promote,
+                }
+        });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(r#move), None) => Ok(r#move),
+            (Some(_), Some(_)) => Err(ParseMoveError::AmbiguousMove(san.to_owned())),
+            (None, _) => Err(ParseMoveError::IllegalMove(san.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{movegen::generate_legal_moves, Board, ParseMoveError};
+
+    #[test]
+    fn san_round_trip() {
+        let boards = [
+            Board::default(),
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap(),
+            Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1")
+                .unwrap(),
+            Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(),
+            Board::from_fen("R6r/8/8/2K5/5k2/8/8/r6R w - - 0 1").unwrap(),
+            Board::from_fen("8/1n4N1/2k5/8/8/5K2/1N4n1/8 b - - 0 1").unwrap(),
+        ];
+
+        for board in boards {
+            for r#move in generate_legal_moves(&board) {
+                let san = r#move.to_san(&board);
+                assert_eq!(board.parse_san(&san).unwrap(), r#move, "{} in {}", san, board);
+            }
+        }
+    }
+
+    #[test]
+    fn san_formatting() {
+        let board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let san = |text: &str| board.parse_san(text).unwrap().to_san(&board);
+
+        assert_eq!(san("O-O"), "O-O");
+        assert_eq!(san("0-0-0"), "O-O-O");
+        assert_eq!(san("Nxf7"), "Nxf7");
+        assert_eq!(san("Bxa6"), "Bxa6");
+        assert_eq!(san("dxe6"), "dxe6");
+        assert_eq!(san("Qxf6"), "Qxf6");
+
+        let board = Board::from_fen("4k3/8/8/R7/8/8/4K3/R6R w - - 0 1").unwrap();
+        assert!(matches!(board.parse_san("Rd1"), Err(ParseMoveError::AmbiguousMove(_))));
+        assert!(matches!(board.parse_san("Ra3"), Err(ParseMoveError::AmbiguousMove(_))));
+        assert_eq!(board.parse_san("Rad1").unwrap().to_san(&board), "Rad1");
+        assert_eq!(board.parse_san("R1a3").unwrap().to_san(&board), "R1a3");
+        assert_eq!(board.parse_san("Ra5a3").unwrap().to_san(&board), "R5a3");
+        assert!(matches!(board.parse_san("Rb3"), Err(ParseMoveError::IllegalMove(_))));
+
+        let board = Board::from_fen("1r2k3/2P5/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.parse_san("cxb8=Q+").unwrap().to_san(&board), "cxb8=Q+");
+        assert_eq!(board.parse_san("c8N").unwrap().to_san(&board), "c8=N");
+
+        let board = Board::from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        assert_eq!(board.parse_san("Rh8").unwrap().to_san(&board), "Rh8#");
+
+        let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        assert_eq!(board.parse_san("exd6e.p.").unwrap().to_san(&board), "exd6");
+
+        let board = Board::from_fen("rn2k3/8/5n2/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(board.parse_san("Nbd7").unwrap().to_san(&board), "Nbd7");
+        assert_eq!(board.parse_san("Nfd7").unwrap().to_san(&board), "Nfd7");
+    }
+}
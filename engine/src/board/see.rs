@@ -0,0 +1,272 @@
+use super::{
+    movegen::bitmasks::{bishop_attacks, rook_attacks},
+    piece::{Piece, PieceColor, PieceType},
+    r#move::{Move, MoveType},
+    Board,
+};
+
+// Indexed by `PieceType`, nothing is won by moving to an empty square
+pub const SEE_SCORES: [i32; 7] = [
+    100,   //Pawn
+    300,   //Knight
+    350,   //Bishop
+    500,   //Rook
+    1000,  //Queen
+    10000, //King
+    0,     //Empty
+];
+
+const ATTACKER_ORDER: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+impl Board {
+    // The cheapest piece of `color` among `attackers`, with its square
+    fn least_valuable_attacker(
+        &self,
+        attackers: u64,
+        color: PieceColor,
+    ) -> Option<(PieceType, u64)> {
+        ATTACKER_ORDER.iter().find_map(|&piece_type| {
+            let pieces = attackers & self.bit_boards[Piece::new(piece_type, color)];
+            (pieces != 0).then(|| (piece_type, pieces & pieces.wrapping_neg()))
+        })
+    }
+
+    // Sliders behind a piece that just left the exchange square join in
+    fn xray_attackers(&self, square: usize, occupancy: u64, piece_type: PieceType) -> u64 {
+        let queens = self.bit_boards[Piece::WhiteQueen] | self.bit_boards[Piece::BlackQueen];
+        let mut attackers = 0;
+
+        if matches!(piece_type, PieceType::Pawn | PieceType::Bishop | PieceType::Queen) {
+            attackers |= bishop_attacks(square, occupancy)
+                & (self.bit_boards[Piece::WhiteBishop]
+                    | self.bit_boards[Piece::BlackBishop]
+                    | queens);
+        }
+        if matches!(piece_type, PieceType::Rook | PieceType::Queen) {
+            attackers |= rook_attacks(square, occupancy)
+                & (self.bit_boards[Piece::WhiteRook] | self.bit_boards[Piece::BlackRook] | queens);
+        }
+
+        attackers & occupancy
+    }
+
+    // What the move gains and the piece left standing on the target square. Castling never wins
+    // or loses material
+    fn see_start(&self, r#move: Move) -> (i32, PieceType, u64) {
+        let (from, _, move_type, piece, captured) = r#move.unpack();
+        let mut occupancy = self.bit_boards.occupancy() & !(1u64 << from);
+        let mut gain = SEE_SCORES[captured as usize];
+
+        match move_type {
+            MoveType::EnPassantCapture => occupancy &= !(1u64 << self.last_double.unwrap()),
+            MoveType::Promote => {
+                gain += SEE_SCORES[piece.get_type() as usize] - SEE_SCORES[PieceType::Pawn as usize]
+            }
+            _ => {}
+        }
+
+        (gain, piece.get_type(), occupancy)
+    }
+
+    // The material the side to move ends up with after both sides keep recapturing on the target
+    // square with their cheapest piece, each free to stop once that is better. Pins are ignored
+    pub fn see(&self, r#move: Move) -> i32 {
+        if r#move.is_castle() {
+            return 0;
+        }
+
+        let to = r#move.to() as usize;
+        let (first_gain, mut on_square, mut occupancy) = self.see_start(r#move);
+        let mut attackers = self.attackers_to(to, occupancy) & occupancy;
+
+        let mut gains = [0; 32];
+        gains[0] = first_gain;
+        let mut depth = 0;
+        let mut color = self.current_color;
+
+        loop {
+            color = !color;
+            let Some((piece_type, square)) = self.least_valuable_attacker(attackers, color) else {
+                break;
+            };
+
+            // The king can only take when nothing recaptures
+            if piece_type == PieceType::King
+                && attackers & !square & self.bit_boards.col_occupancy(!color) != 0
+            {
+                break;
+            }
+
+            depth += 1;
+            gains[depth] = SEE_SCORES[on_square as usize] - gains[depth - 1];
+            on_square = piece_type;
+
+            occupancy &= !square;
+            attackers = (attackers & occupancy) | self.xray_attackers(to, occupancy, piece_type);
+        }
+
+        while depth > 0 {
+            gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+            depth -= 1;
+        }
+
+        gains[0]
+    }
+
+    // Whether `see` is at least `threshold`, stopping as soon as the side to move is sure of it
+    pub fn see_ge(&self, r#move: Move, threshold: i32) -> bool {
+        if r#move.is_castle() {
+            return threshold <= 0;
+        }
+
+        let to = r#move.to() as usize;
+        let (first_gain, on_square, mut occupancy) = self.see_start(r#move);
+
+        // Even if the moved piece is not taken back
+        let mut swap = first_gain - threshold;
+        if swap < 0 {
+            return false;
+        }
+
+        // Even if it is
+        swap = SEE_SCORES[on_square as usize] - swap;
+        if swap <= 0 {
+            return true;
+        }
+
+        let mut attackers = self.attackers_to(to, occupancy) & occupancy;
+        let mut color = self.current_color;
+        let mut result = true;
+
+        loop {
+            color = !color;
+            let Some((piece_type, square)) = self.least_valuable_attacker(attackers, color) else {
+                break;
+            };
+
+            // The king can only take when nothing recaptures
+            if piece_type == PieceType::King {
+                return if attackers & !square & self.bit_boards.col_occupancy(!color) != 0 {
+                    result
+                } else {
+                    !result
+                };
+            }
+
+            result = !result;
+            swap = SEE_SCORES[piece_type as usize] - swap;
+            if swap < result as i32 {
+                break;
+            }
+
+            occupancy &= !square;
+            attackers = (attackers & occupancy) | self.xray_attackers(to, occupancy, piece_type);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{movegen::generate_legal_moves, Board, PieceType};
+
+    use super::SEE_SCORES;
+
+    const P: i32 = SEE_SCORES[PieceType::Pawn as usize];
+    const N: i32 = SEE_SCORES[PieceType::Knight as usize];
+    const B: i32 = SEE_SCORES[PieceType::Bishop as usize];
+    const R: i32 = SEE_SCORES[PieceType::Rook as usize];
+    const Q: i32 = SEE_SCORES[PieceType::Queen as usize];
+
+    #[test]
+    fn see() {
+        for (fen, uci, value) in [
+            ("6k1/1pp4p/p1pb4/6q1/3P1pRr/2P4P/PP1Br1P1/5RKN w - - 0 1", "f1f4", P - R + B),
+            ("5rk1/1pp2q1p/p1pb4/8/3P1NP1/2P5/1P1BQ1P1/5RK1 b - - 0 1", "d6f4", N - B),
+            ("4R3/2r3p1/5bk1/1p1r3p/p2PR1P1/P1BK1P2/1P6/8 b - - 0 1", "h5g4", 0),
+            ("4R3/2r3p1/5bk1/1p1r1p1p/p2PR1P1/P1BK1P2/1P6/8 b - - 0 1", "h5g4", 0),
+            ("4r1k1/5pp1/nbp4p/1p2p2q/1P2P1b1/1BP2N1P/1B2QPPK/3R4 b - - 0 1", "g4f3", N - B),
+            ("2r1r1k1/pp1bppbp/3p1np1/q3P3/2P2P2/1P2B3/P1N1B1PP/2RQ1RK1 b - - 0 1", "d6e5", P),
+            ("7r/5qpk/p1Qp1b1p/3r3n/BB3p2/5p2/P1P2P2/4RK1R w - - 0 1", "e1e8", 0),
+            ("6rr/6pk/p1Qp1b1p/2n5/1B3p2/5p2/P1P2P2/4RK1R w - - 0 1", "e1e8", -R),
+            ("7r/5qpk/2Qp1b1p/1N1r3n/BB3p2/5p2/P1P2P2/4RK1R w - - 0 1", "e1e8", -R),
+            ("6RR/4bP2/8/8/5r2/3K4/5p2/4k3 w - - 0 1", "f7f8q", B - P),
+            ("6RR/4bP2/8/8/5r2/3K4/5p2/4k3 w - - 0 1", "f7f8n", N - P),
+            ("7R/5P2/8/8/6r1/3K4/5p2/4k3 w - - 0 1", "f7f8q", Q - P),
+            ("7R/5P2/8/8/6r1/3K4/5p2/4k3 w - - 0 1", "f7f8b", B - P),
+            ("7R/4bP2/8/8/1q6/3K4/5p2/4k3 w - - 0 1", "f7f8r", -P),
+            ("8/4kp2/2npp3/1Nn5/1p2PQP1/7q/1PP1B3/4KR1r b - - 0 1", "h1f1", 0),
+            ("8/4kp2/2npp3/1Nn5/1p2P1P1/7q/1PP1B3/4KR1r b - - 0 1", "h1f1", 0),
+            ("2r2r1k/6bp/p7/2q2p1Q/3PpP2/1B6/P5PP/2RR3K b - - 0 1", "c5c1", 2 * R - Q),
+            ("r2qk1nr/pp2ppbp/2b3p1/2p1p3/8/2N2N2/PPPP1PPP/R1BQR1K1 w kq - 0 1", "f3e5", P),
+            ("6r1/4kq2/b2p1p2/p1pPb3/p1P2B1Q/2P4P/2B1R1P1/6K1 w - - 0 1", "f4e5", 0),
+            ("3q2nk/pb1r1p2/np6/3P2Pp/2p1P3/2R4B/PQ3P1P/3R2K1 w - h6 0 1", "g5h6", 0),
+            ("3q2nk/pb1r1p2/np6/3P2Pp/2p1P3/2R1B2B/PQ3P1P/3R2K1 w - h6 0 1", "g5h6", P),
+            ("2r4r/1P4pk/p2p1b1p/7n/BB3p2/2R2p2/P1P2P2/4RK2 w - - 0 1", "c3c8", R),
+            ("2r4k/2r4p/p7/2b2p1b/4pP2/1BR5/P1R3PP/2Q4K w - - 0 1", "c3c5", B),
+            ("8/pp6/2pkp3/4bp2/2R3b1/2P5/PP4B1/1K6 w - - 0 1", "g2c6", P - B),
+            ("4q3/1p1pr1k1/1B2rp2/6p1/p3PP2/P3R1P1/1P2R1K1/4Q3 b - - 0 1", "e6e4", P - R),
+            ("4q3/1p1pr1kb/1B2rp2/6p1/p3PP2/P3R1P1/1P2R1K1/4Q3 b - - 0 1", "h7e4", P),
+            ("3r3k/3r4/2n1n3/8/3p4/2PR4/1B1Q4/3R3K w - - 0 1", "d3d4", P - R + N - P),
+            ("1k1r4/1ppn3p/p4b2/4n3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5", N - R + B),
+            ("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5", P - N),
+            ("r2q1rk1/2p1bppp/p2p1n2/1p2P3/4P1b1/1nP1BN2/PP3PPP/RN1QR1K1 b - - 0 1", "g4f3", N - B),
+            ("r1bqkb1r/2pp1ppp/p1n5/1p2p3/3Pn3/1B3N2/PPP2PPP/RNBQ1RK1 b kq - 0 1", "c6d4", 0),
+            ("r1bq1r2/pp1ppkbp/4N1pB/n3P3/8/2N5/PPP2PPP/R2QK2R w KQ - 0 1", "e6g7", B),
+            ("rnq1k2r/1b3ppp/p2bpn2/1p1p4/3N4/1BN1P3/PPP2PPP/R1BQR1K1 b kq - 0 1", "d6h2", P - B),
+            ("rn2k2r/1bq2ppp/p2bpn2/1p1p4/3N4/1BN1P3/PPP2PPP/R1BQR1K1 b kq - 0 1", "d6h2", P),
+            ("r4rk1/3nppbp/bq1p1np1/2pP4/8/2N2NPP/PP2PPB1/R1BQR1K1 b - - 0 1", "b6b2", P - Q),
+            ("r4rk1/1q1nppbp/b2p1np1/2pP4/8/2N2NPP/PP2PPB1/R1BQR1K1 b - - 0 1", "f6d5", P - N),
+            ("1r3r2/5p2/4p2p/2k1n1P1/2PN1nP1/1P3P2/8/2KR1B1R b - - 0 1", "b8b3", P - R),
+            ("1r3r2/5p2/4p2p/4n1P1/kPPN1nP1/5P2/8/2KR1B1R b - - 0 1", "b8b4", P),
+            ("2r2rk1/5pp1/pp5p/q2p4/P3n3/1Q3NP1/1P2PP1P/2RR2K1 b - - 0 1", "c8c1", 0),
+            ("5rk1/5pp1/2r4p/5b2/2R5/6Q1/R1P1qPP1/5NK1 b - - 0 1", "f5c2", P - B),
+            ("1r3r1k/p4pp1/2p1p2p/qpQP3P/2P5/3R4/PP3PP1/1K1R4 b - - 0 1", "a5a2", P - Q),
+            ("1r5k/p4pp1/2p1p2p/qpQP3P/2P2P2/1P1R4/P4rP1/1K1R4 b - - 0 1", "a5a2", P),
+            ("rnbqrbn1/pp3ppp/3p4/2p2k2/4p3/3B1K2/PPP2PPP/RNB1Q1NR w - - 0 1", "d3e4", P),
+            ("2r1k2r/pb4pp/5p1b/2KB3n/4N3/2NP1PB1/PPP1P1PP/R2Q3R w k - 0 1", "d5c6", -B),
+            ("2r1k2r/pb4pp/5p1b/2KB3n/1N2N3/3P1PB1/PPP1P1PP/R2Q3R w k - 0 1", "d5c6", 0),
+            ("3N4/2K5/2n5/1k6/8/8/8/8 b - - 0 1", "c6d8", 0),
+            ("r2n3r/2P1P3/4N3/1k6/8/8/8/4K3 w - - 0 1", "e6d8", N),
+            ("8/8/8/1k6/6b1/4N3/2p3K1/3n4 w - - 0 1", "e3d1", 0),
+            ("r1bqk1nr/pppp1ppp/2n5/1B2p3/1b2P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 1", "e1g1", 0),
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let r#move = generate_legal_moves(&board)
+                .iter()
+                .copied()
+                .find(|r#move| r#move.to_string() == uci)
+                .unwrap_or_else(|| panic!("{} in {}", uci, fen));
+
+            assert_eq!(board.see(r#move), value, "{} in {}", uci, fen);
+            assert!(board.see_ge(r#move, value), "{} in {}", uci, fen);
+            assert!(!board.see_ge(r#move, value + 1), "{} in {}", uci, fen);
+        }
+    }
+
+    #[test]
+    fn see_ge_matches_see() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "2r1r1k1/pp1bppbp/3p1np1/q3P3/2P2P2/1P2B3/P1N1B1PP/2RQ1RK1 b - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            for r#move in generate_legal_moves(&board) {
+                let see = board.see(r#move);
+                for threshold in [-1000, -500, -200, -100, -1, 0, 1, 100, 200, 500, 1000] {
+                    assert_eq!(board.see_ge(r#move, threshold), see >= threshold, "{}", r#move);
+                }
+            }
+        }
+    }
+}
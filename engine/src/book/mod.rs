@@ -0,0 +1,274 @@
+mod keys;
+
+use std::{fs::File, io::BufReader, io::Read, path::Path};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use rand::Rng;
+
+use crate::board::{
+    movegen::generate_legal_moves,
+    piece::{Piece, PieceColor, PieceType},
+    r#move::{Move, MoveType},
+    Board, CastleFlags,
+};
+
+use self::keys::POLYGLOT_RANDOM;
+
+const CASTLE_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+// The key Polyglot books are indexed with, unrelated to `Board::hash`
+pub fn polyglot_key(board: &Board) -> u64 {
+    let mut key = 0;
+
+    for (square, piece) in board.pieces.iter().enumerate() {
+        if *piece != Piece::Empty {
+            let kind =
+                piece.get_type() as usize * 2 + (piece.get_color() == PieceColor::White) as usize;
+            key ^= POLYGLOT_RANDOM[64 * kind + square];
+        }
+    }
+
+    let castles = [
+        CastleFlags::WHITE_KINGSIDE_CASTLING,
+        CastleFlags::WHITE_QUEENSIDE_CASTLING,
+        CastleFlags::BLACK_KINGSIDE_CASTLING,
+        CastleFlags::BLACK_QUEENSIDE_CASTLING,
+    ];
+    for (i, castle) in castles.into_iter().enumerate() {
+        if board.castle_flags.contains(castle) {
+            key ^= POLYGLOT_RANDOM[CASTLE_OFFSET + i];
+        }
+    }
+
+    // The en passant file only counts if a pawn of the side to move stands next to the pushed pawn
+    if let Some(double) = board.last_double {
+        let file = double as usize % 8;
+        let pawn = Piece::new(PieceType::Pawn, board.current_color);
+        if (file > 0 && board.pieces[double as usize - 1] == pawn)
+            || (file < 7 && board.pieces[double as usize + 1] == pawn)
+        {
+            key ^= POLYGLOT_RANDOM[EN_PASSANT_OFFSET + file];
+        }
+    }
+
+    if board.current_color == PieceColor::White {
+        key ^= POLYGLOT_RANDOM[TURN_OFFSET];
+    }
+
+    key
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub r#move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct Book {
+    // Sorted by key, like in the file
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::load(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn load(r: &mut impl Read) -> std::io::Result<Self> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+
+        let mut entries = Vec::with_capacity(bytes.len() / 16);
+        for mut record in bytes.chunks_exact(16) {
+            entries.push(BookEntry {
+                key: record.read_u64::<BigEndian>()?,
+                r#move: record.read_u16::<BigEndian>()?,
+                weight: record.read_u16::<BigEndian>()?,
+                learn: record.read_u32::<BigEndian>()?,
+            });
+        }
+
+        // Books should already be sorted, but the lookup relies on it
+        entries.sort_by_key(|entry| entry.key);
+
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Every legal book move for the position with its weight, entries that don't decode to a
+    // legal move are skipped
+    pub fn moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        let key = polyglot_key(board);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let legal_moves = generate_legal_moves(board);
+
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| {
+                let r#move = decode_move(entry.r#move, &legal_moves)?;
+                Some((r#move, entry.weight))
+            })
+            .collect()
+    }
+
+    pub fn best_move(&self, board: &Board) -> Option<Move> {
+        let moves = self.moves(board);
+        let best = moves.iter().map(|(_, weight)| *weight).max()?;
+        moves.into_iter().find(|(_, weight)| *weight == best).map(|(r#move, _)| r#move)
+    }
+
+    // Picks a move with a probability proportional to its weight
+    pub fn weighted_move(&self, board: &Board, rng: &mut impl Rng) -> Option<Move> {
+        let moves = self.moves(board);
+        let total: u32 = moves.iter().map(|(_, weight)| *weight as u32).sum();
+        if total == 0 {
+            return moves.first().map(|(r#move, _)| *r#move);
+        }
+
+        let mut pick = rng.gen_range(0..total);
+        for (r#move, weight) in moves {
+            if pick < weight as u32 {
+                return Some(r#move);
+            }
+            pick -= weight as u32;
+        }
+
+        None
+    }
+}
+
+// Polyglot packs to in bits 0-5, from in bits 6-11 and the promotion in bits 12-14, castling is
+// written as the king capturing its own rook, just like our moves
+fn decode_move(encoded: u16, legal_moves: &[Move]) -> Option<Move> {
+    let from = ((encoded >> 6) & 63) as u32;
+    let to = (encoded & 63) as u32;
+    let promotion = match (encoded >> 12) & 7 {
+        0 => None,
+        1 => Some(PieceType::Knight),
+        2 => Some(PieceType::Bishop),
+        3 => Some(PieceType::Rook),
+        4 => Some(PieceType::Queen),
+        _ => return None,
+    };
+
+    legal_moves.iter().copied().find(|r#move| {
+        r#move.from() == from
+            && r#move.to() == to
+            && (r#move.move_type() == MoveType::Promote) == promotion.is_some()
+            && promotion.is_none_or(|piece| r#move.piece().get_type() == piece)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::NoDelta;
+    use byteorder::WriteBytesExt;
+    use std::io::Cursor;
+
+    #[test]
+    fn reference_keys() {
+        // The example positions from the Polyglot format description
+        let positions = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0x463b96181691fc9c),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", 0x823c9b50fd114196),
+            ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", 0x0756b94461c50fb0),
+            ("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2", 0x662fafb965db29d4),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 0x22a48b5a8e47ff78),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3", 0x652a607ca3f242c1),
+            ("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4", 0x00fdd303c946bdd9),
+            ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3", 0x3c8123ea7b067637),
+            ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4", 0x5c3f9b829b279560),
+        ];
+
+        for (fen, key) in positions {
+            assert_eq!(polyglot_key(&Board::from_fen(fen).unwrap()), key, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn random_table() {
+        // Pins every key, all 781 were compared with an independent copy of the Random64 table
+        let checksum = POLYGLOT_RANDOM.iter().fold(0u64, |hash, key| hash.rotate_left(5) ^ key);
+        assert_eq!(checksum, 0x55187f9f3b6d7fb4);
+
+        // The first and last key of every piece block, from black pawns to white kings
+        let blocks = [
+            (0x9D39247E33776D41, 0xEC16CA8AEA98AD76),
+            (0x5355F900C2A82DC7, 0x73A1921916591CBD),
+            (0x56436C9FE1A1AA8D, 0x6703DF9D2924E97E),
+            (0xC547F57E42A7444E, 0x48CBFF086DDF285A),
+            (0x7F9B6AF1EBF78BAF, 0x098954D51FFF6580),
+            (0x23B70EDB1955C4BF, 0x7F9D1A2E1EBE1327),
+            (0xDA3A361B1C5157B1, 0x8FE88B57305E2AB6),
+            (0xA09E8C8C35AB96DE, 0x12A8F216AF9418C2),
+            (0x001F837CC7350524, 0x1A4E4822EB4D7A59),
+            (0x6FFE73E81B637FB3, 0xB5B4071DBFC73A66),
+            (0x230E343DFBA08D33, 0xFF577222C14F0A3A),
+            (0x55B6344CF97AAFAE, 0xD20D8C88C8FFE65F),
+        ];
+        for (kind, (first, last)) in blocks.into_iter().enumerate() {
+            assert_eq!(POLYGLOT_RANDOM[64 * kind], first, "{}", kind);
+            assert_eq!(POLYGLOT_RANDOM[64 * kind + 63], last, "{}", kind);
+        }
+
+        assert_eq!(POLYGLOT_RANDOM[CASTLE_OFFSET], 0x31D71DCE64B2C310);
+        assert_eq!(POLYGLOT_RANDOM[EN_PASSANT_OFFSET], 0x70CC73D90BC26E24);
+        assert_eq!(POLYGLOT_RANDOM[TURN_OFFSET], 0xF8D626AAAF278509);
+    }
+
+    fn write_entry(bytes: &mut Vec<u8>, key: u64, from: u16, to: u16, weight: u16) {
+        bytes.write_u64::<BigEndian>(key).unwrap();
+        bytes.write_u16::<BigEndian>(from << 6 | to).unwrap();
+        bytes.write_u16::<BigEndian>(weight).unwrap();
+        bytes.write_u32::<BigEndian>(0).unwrap();
+    }
+
+    #[test]
+    fn book_moves() {
+        let start = Board::default();
+        let castle =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+                .unwrap();
+
+        let mut bytes = Vec::new();
+        write_entry(&mut bytes, polyglot_key(&castle), 4, 7, 3);
+        write_entry(&mut bytes, polyglot_key(&start), 12, 28, 10);
+        write_entry(&mut bytes, polyglot_key(&start), 11, 27, 5);
+        // Not legal, must be skipped
+        write_entry(&mut bytes, polyglot_key(&start), 12, 36, 100);
+
+        let book = Book::load(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(book.len(), 4);
+
+        let moves = book.moves(&start);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(book.best_move(&start).unwrap().to_san(&start), "e4");
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let r#move = book.weighted_move(&start, &mut rng).unwrap();
+            assert!(moves.iter().any(|(book_move, _)| *book_move == r#move));
+        }
+
+        let r#move = book.best_move(&castle).unwrap();
+        assert_eq!(r#move.move_type(), MoveType::KingCastle);
+
+        let mut board = start.clone();
+        board.make_move(book.best_move(&start).unwrap(), NoDelta);
+        assert!(book.moves(&board).is_empty());
+    }
+}
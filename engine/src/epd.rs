@@ -0,0 +1,172 @@
+use crate::board::{r#move::Move, Board, FenError, ParseMoveError};
+
+#[derive(Debug)]
+pub enum EpdError {
+    NotEnoughFields,
+    InvalidFen(FenError),
+    InvalidMove(String, ParseMoveError),
+    UnterminatedString,
+}
+
+impl From<FenError> for EpdError {
+    fn from(e: FenError) -> EpdError {
+        EpdError::InvalidFen(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EpdEntry {
+    pub board: Board,
+    pub best_moves: Vec<Move>,
+    pub avoid_moves: Vec<Move>,
+    pub id: Option<String>,
+    pub comment: Option<String>,
+    // Every operation in the record, including the ones above, as (opcode, operands)
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdEntry {
+    // A position counts as solved if we played one of the `bm` moves and none of the `am` moves
+    pub fn is_solved_by(&self, r#move: Move) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&r#move))
+            && !self.avoid_moves.contains(&r#move)
+    }
+}
+
+// Splits "bm Nf3 Qd1; id \"WAC.001\";" into opcodes with their operands, semicolons inside
+// quoted strings don't end an operation
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(EpdError::UnterminatedString),
+                    }
+                }
+                words.push(std::mem::take(&mut word));
+            }
+            ';' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+            }
+            _ if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            _ => word.push(c),
+        }
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+
+    Ok(operations)
+}
+
+pub fn parse_epd_line(line: &str) -> Result<EpdEntry, EpdError> {
+    let line = line.trim();
+
+    // The first four fields are the FEN without the move counters
+    let mut fields = Vec::new();
+    let mut rest = line;
+    for _ in 0..4 {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(EpdError::NotEnoughFields);
+        }
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let operations = parse_operations(rest)?;
+
+    let mut fen = fields.join(" ");
+    let operand = |opcode: &str| {
+        operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .and_then(|(_, operands)| operands.first().cloned())
+    };
+    fen.push_str(&format!(
+        " {} {}",
+        operand("hmvc").unwrap_or_else(|| "0".to_owned()),
+        operand("fmvn").unwrap_or_else(|| "1".to_owned())
+    ));
+
+    let board = Board::from_fen(&fen)?;
+
+    let moves = |opcode: &str| -> Result<Vec<Move>, EpdError> {
+        operations
+            .iter()
+            .filter(|(name, _)| name == opcode)
+            .flat_map(|(_, operands)| operands.iter())
+            .map(|san| board.parse_san(san).map_err(|e| EpdError::InvalidMove(san.clone(), e)))
+            .collect()
+    };
+
+    Ok(EpdEntry {
+        best_moves: moves("bm")?,
+        avoid_moves: moves("am")?,
+        id: operand("id"),
+        comment: operand("c0"),
+        board,
+        operations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_wac_lines() {
+        let entry = parse_epd_line(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+        )
+        .unwrap();
+
+        assert_eq!(entry.id.as_deref(), Some("WAC.001"));
+        assert_eq!(entry.best_moves.len(), 1);
+        assert_eq!(entry.best_moves[0].to_san(&entry.board), "Qg6");
+        assert!(entry.is_solved_by(entry.best_moves[0]));
+
+        let entry = parse_epd_line(
+            "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - bm Nxc6 Nf5; \
+             am Qd2; c0 \"two; best moves\"; hmvc 3; fmvn 7;",
+        )
+        .unwrap();
+
+        assert_eq!(entry.best_moves.len(), 2);
+        assert_eq!(entry.avoid_moves.len(), 1);
+        assert_eq!(entry.comment.as_deref(), Some("two; best moves"));
+        assert_eq!(entry.board.halfmove_clock, 3);
+        assert_eq!(entry.board.fullmove_number, 7);
+        assert!(!entry.is_solved_by(entry.avoid_moves[0]));
+
+        assert!(matches!(parse_epd_line("8/8/8 w -"), Err(EpdError::NotEnoughFields)));
+        assert!(matches!(
+            parse_epd_line("4k3/8/8/8/8/8/8/4K3 w - - bm Qg6;"),
+            Err(EpdError::InvalidMove(..))
+        ));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::board::piece::PieceColor;
+
+use super::{GrandBoard, PieceKind, FILES};
+
+// Indexed by `PieceKind`
+pub const SCORES: [i32; 8] = [
+    100,   //Pawn
+    300,   //Knight
+    350,   //Bishop
+    500,   //Rook
+    950,   //Queen
+    10000, //King
+    900,   //Marshal
+    800,   //Cardinal
+];
+
+// Bonus per step closer to the middle of the board, pieces that jump or slide a short way care
+// the most
+const CENTER_BONUS: [i32; 8] = [0, 8, 4, 2, 2, 0, 3, 5];
+
+// Bonus per rank a pawn has advanced from its start
+const PAWN_ADVANCE: i32 = 8;
+
+// The king should stay on its first two ranks while the opponent still has heavy pieces
+const KING_EXPOSED: i32 = 20;
+
+fn center_distance(square: usize) -> i32 {
+    let rank = (square / FILES) as i32;
+    let file = (square % FILES) as i32;
+    // Twice the distance, so the four middle squares are 1 away
+    (2 * rank - 9).abs().max((2 * file - 9).abs()) / 2
+}
+
+fn side_eval(board: &GrandBoard, color: PieceColor) -> i32 {
+    let mut score = 0;
+
+    for kind in PieceKind::ALL {
+        let mut pieces = board.pieces(color, kind);
+        while pieces != 0 {
+            let square = pieces.trailing_zeros() as usize;
+            pieces &= pieces - 1;
+
+            score +=
+                SCORES[kind as usize] + CENTER_BONUS[kind as usize] * (4 - center_distance(square));
+
+            let relative_rank = if color == PieceColor::White {
+                square / FILES
+            } else {
+                FILES - 1 - square / FILES
+            };
+            match kind {
+                PieceKind::Pawn => score += PAWN_ADVANCE * (relative_rank as i32 - 2),
+                PieceKind::King if relative_rank > 1 => {
+                    let heavy = [PieceKind::Queen, PieceKind::Marshal, PieceKind::Rook]
+                        .iter()
+                        .any(|&kind| board.pieces(!color, kind) != 0);
+                    if heavy {
+                        score -= KING_EXPOSED * (relative_rank as i32 - 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    score
+}
+
+// Handcrafted, from the side to move's point of view, the NNUE only knows 8x8 chess
+pub fn evaluate(board: &GrandBoard) -> i32 {
+    let color = board.current_color;
+    side_eval(board, color) - side_eval(board, !color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grand_eval() {
+        assert_eq!(evaluate(&GrandBoard::default()), 0);
+
+        // Mirrored positions score the same for the side to move
+        let white = "r8r/1nbqkmcbn1/pppp1ppppp/10/4p5/4P5/10/PPPP1PPPPP/1NBQKMCBN1/R8R w - - 0 3";
+        let black = "r8r/1nbqkmcbn1/pppp1ppppp/10/4p5/4P5/10/PPPP1PPPPP/1NBQKMCBN1/R8R b - - 0 3";
+        assert_eq!(
+            evaluate(&GrandBoard::from_fen(white).unwrap()),
+            evaluate(&GrandBoard::from_fen(black).unwrap())
+        );
+
+        // A marshal up is winning
+        let fen = "r8r/1nbqk1cbn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R w - - 0 1";
+        assert!(evaluate(&GrandBoard::from_fen(fen).unwrap()) > 700);
+    }
+}
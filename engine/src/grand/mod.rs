@@ -0,0 +1,543 @@
+// Grand Chess: a 10x10 board with a marshal (rook and knight) and a cardinal (bishop and knight)
+// next to the usual pieces. Pawns start on the third rank and may promote on the eighth and ninth
+// ranks, must promote on the tenth, and only to a piece their side has lost. There is no castling
+use std::fmt::{self, Write};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use static_init::dynamic;
+
+use crate::board::{piece::PieceColor, ParseMoveError};
+
+pub mod eval;
+pub mod movegen;
+pub mod search;
+
+use movegen::generate_legal_moves;
+
+pub const FILES: usize = 10;
+pub const SQUARES: usize = FILES * FILES;
+
+// Marshals are written as M and cardinals as C
+pub const START_FEN: &str =
+    "r8r/1nbqkmcbn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R w - - 0 1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PieceKind {
+    Pawn = 0,
+    Knight = 1,
+    Bishop = 2,
+    Rook = 3,
+    Queen = 4,
+    King = 5,
+    Marshal = 6,
+    Cardinal = 7,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 8] = [
+        PieceKind::Pawn,
+        PieceKind::Knight,
+        PieceKind::Bishop,
+        PieceKind::Rook,
+        PieceKind::Queen,
+        PieceKind::King,
+        PieceKind::Marshal,
+        PieceKind::Cardinal,
+    ];
+
+    // Strongest first, so the first promotion generated is the likeliest one
+    pub const PROMOTIONS: [PieceKind; 6] = [
+        PieceKind::Queen,
+        PieceKind::Marshal,
+        PieceKind::Cardinal,
+        PieceKind::Rook,
+        PieceKind::Bishop,
+        PieceKind::Knight,
+    ];
+
+    // How many of each piece a side starts with, pawns can only promote to the missing ones
+    pub fn start_count(self) -> u32 {
+        match self {
+            PieceKind::Pawn => 10,
+            PieceKind::Knight | PieceKind::Bishop | PieceKind::Rook => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        Some(match c.to_ascii_lowercase() {
+            'p' => PieceKind::Pawn,
+            'n' => PieceKind::Knight,
+            'b' => PieceKind::Bishop,
+            'r' => PieceKind::Rook,
+            'q' => PieceKind::Queen,
+            'k' => PieceKind::King,
+            'm' => PieceKind::Marshal,
+            'c' => PieceKind::Cardinal,
+            _ => return None,
+        })
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            PieceKind::Pawn => 'p',
+            PieceKind::Knight => 'n',
+            PieceKind::Bishop => 'b',
+            PieceKind::Rook => 'r',
+            PieceKind::Queen => 'q',
+            PieceKind::King => 'k',
+            PieceKind::Marshal => 'm',
+            PieceKind::Cardinal => 'c',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrandPiece {
+    pub color: PieceColor,
+    pub kind: PieceKind,
+}
+
+impl From<GrandPiece> for char {
+    fn from(piece: GrandPiece) -> Self {
+        match piece.color {
+            PieceColor::White => piece.kind.to_char().to_ascii_uppercase(),
+            PieceColor::Black => piece.kind.to_char(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GrandMove {
+    pub from: u8,
+    pub to: u8,
+    pub promotion: Option<PieceKind>,
+}
+
+impl GrandMove {
+    pub fn null() -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Display for GrandMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_square(f, self.from as usize)?;
+        write_square(f, self.to as usize)?;
+
+        if let Some(promotion) = self.promotion {
+            f.write_char(promotion.to_char())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_square(f: &mut impl Write, square: usize) -> fmt::Result {
+    f.write_fmt(format_args!("{}{}", (b'a' + (square % FILES) as u8) as char, square / FILES + 1))
+}
+
+// Ranks go up to 10, so a square is two or three characters long
+fn parse_square(square: &str) -> Option<usize> {
+    let file = square.as_bytes().first().filter(|file| (b'a'..=b'j').contains(file))?;
+    let rank: usize = square.get(1..)?.parse().ok().filter(|rank| (1..=FILES).contains(rank))?;
+    Some((rank - 1) * FILES + (file - b'a') as usize)
+}
+
+#[derive(Debug)]
+pub enum GrandFenError {
+    NoSuchPiece(char),
+    NoSuchColor(String),
+    InvalidRank(String),
+    MissingKing(PieceColor),
+    InvalidEnPassant(String),
+    InvalidMoveCounter(String),
+    NotEnoughInfo(),
+}
+
+#[dynamic]
+static PIECE_KEYS: [[u64; SQUARES]; 16] = {
+    let mut rng: StdRng = StdRng::seed_from_u64(246810);
+    let mut keys = [[0; SQUARES]; 16];
+    for row in keys.iter_mut() {
+        for val in row.iter_mut() {
+            *val = rng.next_u64();
+        }
+    }
+    keys
+};
+
+#[dynamic]
+static EN_PASSANT_KEYS: [u64; SQUARES] = {
+    let mut rng: StdRng = StdRng::seed_from_u64(135791);
+    let mut keys = [0; SQUARES];
+    for val in keys.iter_mut() {
+        *val = rng.next_u64();
+    }
+    keys
+};
+
+#[dynamic]
+static SIDE_KEY: u64 = StdRng::seed_from_u64(864202).next_u64();
+
+fn piece_key(piece: GrandPiece, square: usize) -> u64 {
+    PIECE_KEYS[piece.color as usize * 8 + piece.kind as usize][square]
+}
+
+// Bitboards use the low 100 bits of a `u128`, square `rank * 10 + file`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrandBoard {
+    pub pieces: [[u128; 8]; 2],
+    pub occupancy: [u128; 2],
+    pub mailbox: [Option<GrandPiece>; SQUARES],
+
+    pub current_color: PieceColor,
+    // The square a pawn skipped with its double step on the last move
+    pub en_passant: Option<usize>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+
+    pub hash: u64,
+}
+
+impl Default for GrandBoard {
+    fn default() -> Self {
+        GrandBoard::from_fen(START_FEN).unwrap()
+    }
+}
+
+impl fmt::Display for GrandBoard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rank in (0..FILES).rev() {
+            for file in 0..FILES {
+                let piece = self.mailbox[rank * FILES + file];
+                f.write_fmt(format_args!("{}|", piece.map_or(' ', char::from)))?;
+            }
+            f.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+}
+
+impl GrandBoard {
+    pub fn from_fen(fen: &str) -> Result<GrandBoard, GrandFenError> {
+        let mut board = GrandBoard {
+            pieces: [[0; 8]; 2],
+            occupancy: [0; 2],
+            mailbox: [None; SQUARES],
+            current_color: PieceColor::White,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+        };
+
+        let mut words = fen.split_whitespace();
+
+        let ranks: Vec<&str> =
+            words.next().ok_or(GrandFenError::NotEnoughInfo())?.split('/').collect();
+        if ranks.len() != FILES {
+            return Err(GrandFenError::InvalidRank(fen.to_owned()));
+        }
+
+        for (rank, row) in ranks.iter().rev().enumerate() {
+            let mut file = 0;
+            let mut empty = 0;
+
+            for c in row.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    empty = empty * 10 + digit as usize;
+                    continue;
+                }
+
+                file += std::mem::take(&mut empty);
+                let kind = PieceKind::from_char(c).ok_or(GrandFenError::NoSuchPiece(c))?;
+                let color =
+                    if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+
+                // Pawns can't stand on the last ranks, they would have had to promote
+                if file >= FILES || (kind == PieceKind::Pawn && (rank == 0 || rank == FILES - 1)) {
+                    return Err(GrandFenError::InvalidRank(row.to_string()));
+                }
+                board.set_piece(rank * FILES + file, GrandPiece { color, kind });
+                file += 1;
+            }
+
+            if file + empty != FILES {
+                return Err(GrandFenError::InvalidRank(row.to_string()));
+            }
+        }
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            if board.pieces[color as usize][PieceKind::King as usize].count_ones() != 1 {
+                return Err(GrandFenError::MissingKing(color));
+            }
+        }
+
+        board.current_color = match words.next().ok_or(GrandFenError::NotEnoughInfo())? {
+            "w" | "W" => PieceColor::White,
+            "b" | "B" => PieceColor::Black,
+            s => return Err(GrandFenError::NoSuchColor(s.to_string())),
+        };
+        if board.current_color == PieceColor::Black {
+            board.hash ^= *SIDE_KEY;
+        }
+
+        // No castling in Grand Chess, the field is kept for the FEN layout
+        words.next().ok_or(GrandFenError::NotEnoughInfo())?;
+
+        let word = words.next().ok_or(GrandFenError::NotEnoughInfo())?;
+        if word != "-" {
+            let square =
+                parse_square(word).ok_or(GrandFenError::InvalidEnPassant(word.to_owned()))?;
+
+            let expected_rank = if board.current_color == PieceColor::White { 6 } else { 3 };
+            if square / FILES != expected_rank {
+                return Err(GrandFenError::InvalidEnPassant(word.to_owned()));
+            }
+
+            board.en_passant = Some(square);
+            board.hash ^= EN_PASSANT_KEYS[square];
+        }
+
+        if let Some(word) = words.next() {
+            board.halfmove_clock =
+                word.parse().map_err(|_| GrandFenError::InvalidMoveCounter(word.to_owned()))?;
+        }
+
+        if let Some(word) = words.next() {
+            board.fullmove_number =
+                word.parse().map_err(|_| GrandFenError::InvalidMoveCounter(word.to_owned()))?;
+        }
+
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..FILES).rev() {
+            let mut empty = 0;
+            for file in 0..FILES {
+                match self.mailbox[rank * FILES + file] {
+                    Some(piece) => {
+                        if empty != 0 {
+                            fen += &empty.to_string();
+                            empty = 0;
+                        }
+                        fen.push(piece.into());
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty != 0 {
+                fen += &empty.to_string();
+            }
+            if rank != 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.current_color == PieceColor::White { " w - " } else { " b - " });
+
+        match self.en_passant {
+            Some(square) => write_square(&mut fen, square).unwrap(),
+            None => fen.push('-'),
+        }
+
+        fen + &format!(" {} {}", self.halfmove_clock, self.fullmove_number)
+    }
+
+    #[inline]
+    pub fn occupancy(&self) -> u128 {
+        self.occupancy[0] | self.occupancy[1]
+    }
+
+    #[inline]
+    pub fn pieces(&self, color: PieceColor, kind: PieceKind) -> u128 {
+        self.pieces[color as usize][kind as usize]
+    }
+
+    pub fn king_square(&self, color: PieceColor) -> usize {
+        self.pieces(color, PieceKind::King).trailing_zeros() as usize
+    }
+
+    pub fn in_check(&self) -> bool {
+        let color = self.current_color;
+        movegen::is_attacked(self, self.king_square(color), !color)
+    }
+
+    // Pieces the pawns of `color` can still promote to, a side can't have more than it started with
+    pub fn can_promote_to(&self, color: PieceColor, kind: PieceKind) -> bool {
+        self.pieces(color, kind).count_ones() < kind.start_count()
+    }
+
+    fn set_piece(&mut self, square: usize, piece: GrandPiece) {
+        self.pieces[piece.color as usize][piece.kind as usize] |= 1 << square;
+        self.occupancy[piece.color as usize] |= 1 << square;
+        self.mailbox[square] = Some(piece);
+        self.hash ^= piece_key(piece, square);
+    }
+
+    fn clear_piece(&mut self, square: usize) -> Option<GrandPiece> {
+        let piece = self.mailbox[square].take()?;
+        self.pieces[piece.color as usize][piece.kind as usize] &= !(1 << square);
+        self.occupancy[piece.color as usize] &= !(1 << square);
+        self.hash ^= piece_key(piece, square);
+        Some(piece)
+    }
+
+    pub fn is_capture(&self, r#move: GrandMove) -> bool {
+        self.mailbox[r#move.to as usize].is_some() || self.is_en_passant(r#move)
+    }
+
+    fn is_en_passant(&self, r#move: GrandMove) -> bool {
+        self.en_passant == Some(r#move.to as usize)
+            && self.mailbox[r#move.from as usize].is_some_and(|piece| piece.kind == PieceKind::Pawn)
+    }
+
+    pub fn make_move(&mut self, r#move: GrandMove) {
+        let (from, to) = (r#move.from as usize, r#move.to as usize);
+        let en_passant = self.is_en_passant(r#move);
+
+        let piece = self.clear_piece(from).expect("no piece to move");
+        let captured = self.clear_piece(to);
+
+        if en_passant {
+            let pawn_square =
+                if piece.color == PieceColor::White { to - FILES } else { to + FILES };
+            self.clear_piece(pawn_square);
+        }
+
+        let kind = r#move.promotion.unwrap_or(piece.kind);
+        self.set_piece(to, GrandPiece { color: piece.color, kind });
+
+        if let Some(square) = self.en_passant.take() {
+            self.hash ^= EN_PASSANT_KEYS[square];
+        }
+        if piece.kind == PieceKind::Pawn && from.abs_diff(to) == 2 * FILES {
+            let square = (from + to) / 2;
+            self.en_passant = Some(square);
+            self.hash ^= EN_PASSANT_KEYS[square];
+        }
+
+        if piece.kind == PieceKind::Pawn || captured.is_some() || en_passant {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        if self.current_color == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+        self.current_color = !self.current_color;
+        self.hash ^= *SIDE_KEY;
+    }
+
+    pub fn make_null_move(&mut self) {
+        if let Some(square) = self.en_passant.take() {
+            self.hash ^= EN_PASSANT_KEYS[square];
+        }
+        self.current_color = !self.current_color;
+        self.hash ^= *SIDE_KEY;
+    }
+
+    // Reads a move like `e3e5` or `e9e10q`, which has to be legal in this position
+    pub fn parse_move(&self, text: &str) -> Result<GrandMove, ParseMoveError> {
+        if text.len() < 4 {
+            return Err(ParseMoveError::StringTooSmall);
+        }
+
+        let promotion = match text.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+            Some(c) => Some(PieceKind::from_char(c).ok_or(ParseMoveError::InvalidPromotionPiece)?),
+            None => None,
+        };
+        let squares = if promotion.is_some() { &text[..text.len() - 1] } else { text };
+
+        // The destination starts at the second file letter
+        let split = squares
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| c.is_ascii_alphabetic())
+            .map(|(index, _)| index)
+            .ok_or(ParseMoveError::InvalidSquare(text.to_owned()))?;
+
+        let from = parse_square(&squares[..split])
+            .ok_or(ParseMoveError::InvalidSquare(squares[..split].to_owned()))?;
+        let to = parse_square(&squares[split..])
+            .ok_or(ParseMoveError::InvalidSquare(squares[split..].to_owned()))?;
+
+        let r#move = GrandMove { from: from as u8, to: to as u8, promotion };
+        if generate_legal_moves(self).contains(&r#move) {
+            Ok(r#move)
+        } else {
+            Err(ParseMoveError::IllegalMove(text.to_owned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grand_fen() {
+        for fen in [
+            START_FEN,
+            "r8r/1nbqkmcbn1/p1pppppppp/10/1p8/P9/10/1PPPPPPPPP/1NBQKMCBN1/R8R w - b7 0 3",
+            "4k5/10/10/10/10/10/10/10/1p8/4K5 b - - 12 40",
+            "r8r/1nbq1mcbn1/pppppkpppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R b - - 5 7",
+        ] {
+            let board = GrandBoard::from_fen(fen).unwrap();
+            assert_eq!(board.to_fen(), fen);
+        }
+
+        let board = GrandBoard::default();
+        assert_eq!(board.mailbox[1 * FILES + 5].unwrap().kind, PieceKind::Marshal);
+        assert_eq!(board.mailbox[1 * FILES + 6].unwrap().kind, PieceKind::Cardinal);
+        assert_eq!(board.king_square(PieceColor::Black), 8 * FILES + 4);
+        assert!(!board.can_promote_to(PieceColor::White, PieceKind::Queen));
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r8r/1nbqkmcbn1/pppppppppp/11/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R w - - 0 1",
+            "r8r/1nbq1mcbn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R w - - 0 1",
+            "r8r/1nbqkmcbn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R w - e4 0 1",
+            "r8r/1nbqkxcbn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R w - - 0 1",
+        ] {
+            assert!(GrandBoard::from_fen(fen).is_err(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn grand_moves() {
+        let mut board = GrandBoard::default();
+        for text in ["e3e5", "d8d6", "e5d6", "c9d8", "g2h4"] {
+            let r#move = board.parse_move(text).unwrap();
+            assert_eq!(r#move.to_string(), text);
+            board.make_move(r#move);
+        }
+        assert!(board.parse_move("j1j10").is_err());
+
+        // En passant and the hash of the position reached
+        let fen = "r8r/1nbqkmcbn1/pppp1ppppp/10/10/3pP5/10/PPPP1PPPPP/1NBQKMCBN1/R8R b - e4 0 5";
+        let mut board = GrandBoard::from_fen(fen).unwrap();
+        board.make_move(board.parse_move("d5e4").unwrap());
+        assert!(board.mailbox[4 * FILES + 4].is_none());
+        assert_eq!(board.hash, GrandBoard::from_fen(&board.to_fen()).unwrap().hash);
+
+        // Promotions need a lost piece, and are forced on the last rank
+        let board = GrandBoard::from_fen("4k5/P9/10/10/10/10/10/10/10/4K5 w - - 0 1").unwrap();
+        assert!(board.parse_move("a9a10").is_err());
+        assert!(board.parse_move("a9a10q").is_ok());
+        assert!(board.parse_move("a9a10m").is_ok());
+        assert!(board.parse_move("a9a10k").is_err());
+
+        let fen = "4k5/P9/10/10/10/10/10/10/1NBQ1MCBN1/R3K4R w - - 0 1";
+        let board = GrandBoard::from_fen(fen).unwrap();
+        assert!(board.parse_move("a9a10q").is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use std::array::from_fn;
+
+use static_init::dynamic;
+
+use crate::board::piece::PieceColor;
+
+use super::{GrandBoard, GrandMove, PieceKind, FILES, SQUARES};
+
+#[dynamic]
+pub static KNIGHT_ATTACKS: [u128; SQUARES] = generate_leaper_attacks(&[
+    (-1, -2),
+    (1, -2),
+    (-2, -1),
+    (2, -1),
+    (-2, 1),
+    (2, 1),
+    (-1, 2),
+    (1, 2),
+]);
+
+#[dynamic]
+pub static KING_ATTACKS: [u128; SQUARES] = generate_leaper_attacks(&[
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+]);
+
+// Indexed by the color of the attacking pawn
+#[dynamic]
+pub static PAWN_ATTACKS: [[u128; SQUARES]; 2] =
+    [generate_leaper_attacks(&[(1, -1), (1, 1)]), generate_leaper_attacks(&[(-1, -1), (-1, 1)])];
+
+// (rank, file) steps, the first four go towards higher squares
+const DIRECTIONS: [(i32, i32); 8] =
+    [(1, 0), (0, 1), (1, 1), (1, -1), (-1, 0), (0, -1), (-1, -1), (-1, 1)];
+
+// Every square in a direction up to the edge of the board
+#[dynamic]
+static RAYS: [[u128; SQUARES]; 8] = from_fn(|direction| {
+    let (rank_step, file_step) = DIRECTIONS[direction];
+    from_fn(|square| {
+        let mut ray = 0;
+        let mut rank = (square / FILES) as i32 + rank_step;
+        let mut file = (square % FILES) as i32 + file_step;
+
+        while on_board(rank, file) {
+            ray |= 1u128 << (rank * FILES as i32 + file);
+            rank += rank_step;
+            file += file_step;
+        }
+        ray
+    })
+});
+
+fn on_board(rank: i32, file: i32) -> bool {
+    (0..FILES as i32).contains(&rank) && (0..FILES as i32).contains(&file)
+}
+
+fn generate_leaper_attacks(offsets: &[(i32, i32)]) -> [u128; SQUARES] {
+    from_fn(|square| {
+        let rank = (square / FILES) as i32;
+        let file = (square % FILES) as i32;
+
+        offsets
+            .iter()
+            .filter(|(rank_offset, file_offset)| on_board(rank + rank_offset, file + file_offset))
+            .fold(0, |bitmask, (rank_offset, file_offset)| {
+                bitmask | 1u128 << ((rank + rank_offset) * FILES as i32 + file + file_offset)
+            })
+    })
+}
+
+// The ray stops at the first piece in the way, which can be captured
+fn ray_attacks(square: usize, direction: usize, occupancy: u128) -> u128 {
+    let ray = RAYS[direction][square];
+    let blockers = ray & occupancy;
+    if blockers == 0 {
+        return ray;
+    }
+
+    let blocker =
+        if direction < 4 { blockers.trailing_zeros() } else { 127 - blockers.leading_zeros() };
+    ray ^ RAYS[direction][blocker as usize]
+}
+
+pub fn rook_attacks(square: usize, occupancy: u128) -> u128 {
+    [0, 1, 4, 5]
+        .iter()
+        .fold(0, |attacks, &direction| attacks | ray_attacks(square, direction, occupancy))
+}
+
+pub fn bishop_attacks(square: usize, occupancy: u128) -> u128 {
+    [2, 3, 6, 7]
+        .iter()
+        .fold(0, |attacks, &direction| attacks | ray_attacks(square, direction, occupancy))
+}
+
+pub fn attacks(kind: PieceKind, color: PieceColor, square: usize, occupancy: u128) -> u128 {
+    match kind {
+        PieceKind::Pawn => PAWN_ATTACKS[color as usize][square],
+        PieceKind::Knight => KNIGHT_ATTACKS[square],
+        PieceKind::Bishop => bishop_attacks(square, occupancy),
+        PieceKind::Rook => rook_attacks(square, occupancy),
+        PieceKind::Queen => bishop_attacks(square, occupancy) | rook_attacks(square, occupancy),
+        PieceKind::King => KING_ATTACKS[square],
+        PieceKind::Marshal => rook_attacks(square, occupancy) | KNIGHT_ATTACKS[square],
+        PieceKind::Cardinal => bishop_attacks(square, occupancy) | KNIGHT_ATTACKS[square],
+    }
+}
+
+pub fn is_attacked(board: &GrandBoard, square: usize, by: PieceColor) -> bool {
+    let occupancy = board.occupancy();
+    let pieces =
+        |kinds: &[PieceKind]| kinds.iter().fold(0, |bb, &kind| bb | board.pieces(by, kind));
+
+    PAWN_ATTACKS[!by as usize][square] & board.pieces(by, PieceKind::Pawn) != 0
+        || KNIGHT_ATTACKS[square]
+            & pieces(&[PieceKind::Knight, PieceKind::Marshal, PieceKind::Cardinal])
+            != 0
+        || KING_ATTACKS[square] & board.pieces(by, PieceKind::King) != 0
+        || rook_attacks(square, occupancy)
+            & pieces(&[PieceKind::Rook, PieceKind::Queen, PieceKind::Marshal])
+            != 0
+        || bishop_attacks(square, occupancy)
+            & pieces(&[PieceKind::Bishop, PieceKind::Queen, PieceKind::Cardinal])
+            != 0
+}
+
+fn push_moves(moves: &mut Vec<GrandMove>, from: usize, mut targets: u128) {
+    while targets != 0 {
+        let to = targets.trailing_zeros() as u8;
+        moves.push(GrandMove { from: from as u8, to, promotion: None });
+        targets &= targets - 1;
+    }
+}
+
+// A pawn may stay a pawn on the eighth and ninth ranks but has to promote on the tenth, where it
+// can't go at all if none of its side's pieces have been captured
+fn push_pawn_moves(board: &GrandBoard, moves: &mut Vec<GrandMove>, from: usize, mut targets: u128) {
+    let color = board.current_color;
+
+    while targets != 0 {
+        let to = targets.trailing_zeros() as usize;
+        targets &= targets - 1;
+
+        let relative_rank =
+            if color == PieceColor::White { to / FILES } else { FILES - 1 - to / FILES };
+        if relative_rank < FILES - 3 {
+            moves.push(GrandMove { from: from as u8, to: to as u8, promotion: None });
+            continue;
+        }
+
+        for kind in PieceKind::PROMOTIONS {
+            if board.can_promote_to(color, kind) {
+                moves.push(GrandMove { from: from as u8, to: to as u8, promotion: Some(kind) });
+            }
+        }
+
+        if relative_rank < FILES - 1 {
+            moves.push(GrandMove { from: from as u8, to: to as u8, promotion: None });
+        }
+    }
+}
+
+// Pseudo legal moves, `captures_only` leaves out quiet moves other than promotions
+fn generate_moves(board: &GrandBoard, captures_only: bool) -> Vec<GrandMove> {
+    let mut moves = Vec::with_capacity(128);
+    let color = board.current_color;
+    let occupancy = board.occupancy();
+    let enemies = board.occupancy[!color as usize];
+    let targets = if captures_only { enemies } else { !board.occupancy[color as usize] };
+
+    for kind in PieceKind::ALL.into_iter().skip(1) {
+        let mut pieces = board.pieces(color, kind);
+        while pieces != 0 {
+            let from = pieces.trailing_zeros() as usize;
+            pieces &= pieces - 1;
+
+            push_moves(&mut moves, from, attacks(kind, color, from, occupancy) & targets);
+        }
+    }
+
+    let en_passant = board.en_passant.map_or(0, |square| 1u128 << square);
+    let last_ranks: u128 = if color == PieceColor::White {
+        ((1 << (3 * FILES)) - 1) << (SQUARES - 3 * FILES)
+    } else {
+        (1 << (3 * FILES)) - 1
+    };
+
+    let mut pawns = board.pieces(color, PieceKind::Pawn);
+    while pawns != 0 {
+        let from = pawns.trailing_zeros() as usize;
+        pawns &= pawns - 1;
+
+        let mut pawn_targets = PAWN_ATTACKS[color as usize][from] & (enemies | en_passant);
+
+        let forward = if color == PieceColor::White { from + FILES } else { from - FILES };
+        if occupancy & 1 << forward == 0 {
+            pawn_targets |= 1 << forward;
+
+            let start_rank = if color == PieceColor::White { 2 } else { FILES - 3 };
+            if from / FILES == start_rank {
+                let double =
+                    if color == PieceColor::White { forward + FILES } else { forward - FILES };
+                if occupancy & 1 << double == 0 {
+                    pawn_targets |= 1 << double;
+                }
+            }
+        }
+
+        if captures_only {
+            pawn_targets &= enemies | en_passant | last_ranks;
+        }
+
+        push_pawn_moves(board, &mut moves, from, pawn_targets);
+    }
+
+    moves
+}
+
+pub fn is_legal(board: &GrandBoard, r#move: GrandMove) -> bool {
+    let mut new_board = board.clone();
+    new_board.make_move(r#move);
+    !is_attacked(&new_board, new_board.king_square(board.current_color), new_board.current_color)
+}
+
+pub fn generate_legal_moves(board: &GrandBoard) -> Vec<GrandMove> {
+    let mut moves = generate_moves(board, false);
+    moves.retain(|r#move| is_legal(board, *r#move));
+    moves
+}
+
+// Captures and promotions, for the quiescence search
+pub fn generate_legal_captures(board: &GrandBoard) -> Vec<GrandMove> {
+    let mut moves = generate_moves(board, true);
+    moves.retain(|r#move| {
+        (board.is_capture(*r#move) || r#move.promotion.is_some()) && is_legal(board, *r#move)
+    });
+    moves
+}
+
+pub fn perft(board: &GrandBoard, depth: u32) -> u64 {
+    let moves = generate_legal_moves(board);
+
+    if depth <= 1 {
+        return if depth == 0 { 1 } else { moves.len() as u64 };
+    }
+
+    let mut nodes = 0;
+    for r#move in moves {
+        let mut new_board = board.clone();
+        new_board.make_move(r#move);
+
+        nodes += perft(&new_board, depth - 1);
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grand::START_FEN;
+
+    #[test]
+    fn grand_perft() {
+        // Checked against a separate mailbox move generator only, not yet against Fairy-Stockfish
+        // (`UCI_Variant grand`, `go perft`), which these counts still have to be confirmed with
+        let fen_tests = [
+            (START_FEN, 3, 259_514),
+            (
+                "r8r/1nbqkmcbn1/pppp1ppppp/10/4p5/3P6/10/PPP1PPPPPP/1NBQKMCBN1/R8R w - - 0 2",
+                3,
+                397_858,
+            ),
+            ("4k5/1P1P6/10/10/10/10/10/10/3p1p4/R3K4R w - - 0 1", 3, 1_095),
+            (
+                "r8r/1nbqkmcbn1/pppp1ppppp/10/10/3pP5/10/PPPP1PPPPP/1NBQKMCBN1/R8R b - e4 0 5",
+                3,
+                356_564,
+            ),
+            ("4k5/10/4r5/10/1c8/10/10/3N6/4K5/10 w - - 0 1", 4, 72_132),
+            ("10/3k6/10/2M7/10/10/6c3/10/4K5/10 w - - 0 1", 4, 9_956),
+            ("4k5/P9/10/10/10/10/10/10/1NBQ1MCBN1/R3K4R w - - 0 1", 3, 15_663),
+            ("2r1k4r/1P8/5P4/10/3pP5/10/10/1p8/3K6/10 b - e4 0 1", 4, 394_184),
+        ];
+
+        for (fen, depth, nodes) in fen_tests {
+            let board = GrandBoard::from_fen(fen).unwrap();
+            assert_eq!(perft(&board, depth), nodes, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn grand_captures() {
+        for fen in [
+            "r8r/1nbqkmcbn1/pppp1ppppp/10/4p5/3P6/10/PPP1PPPPPP/1NBQKMCBN1/R8R w - - 0 2",
+            "2r1k4r/1P8/5P4/10/3pP5/10/10/1p8/3K6/10 b - e4 0 1",
+            "2r1k4r/1P8/5P4/10/3pP5/10/10/1p8/3K6/10 w - - 0 1",
+        ] {
+            let board = GrandBoard::from_fen(fen).unwrap();
+            let moves = generate_legal_moves(&board);
+            let captures = generate_legal_captures(&board);
+
+            let expected: Vec<_> = moves
+                .iter()
+                .filter(|r#move| board.is_capture(**r#move) || r#move.promotion.is_some())
+                .collect();
+            assert_eq!(captures.len(), expected.len(), "{}", fen);
+            assert!(captures.iter().all(|r#move| expected.contains(&r#move)), "{}", fen);
+        }
+    }
+}
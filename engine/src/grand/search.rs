@@ -0,0 +1,360 @@
+use std::{
+    sync::mpsc::{Receiver, Sender},
+    time::Instant,
+};
+
+use uci::{RecivedMessage, UciInfoAttribute, UciSearchControl, UciTimeControl};
+
+use crate::{uci::search_deadline, MATE_SCORE, MATE_VALUE, MAX_PLY, MAX_SCORE, MIN_SCORE, STOPPED};
+
+use super::{
+    eval::{evaluate, SCORES},
+    movegen::{generate_legal_captures, generate_legal_moves},
+    GrandBoard, GrandMove, START_FEN,
+};
+
+// A plain alpha-beta search for Grand Chess, the chess search is built around the 8x8 board and
+// its NNUE
+pub struct GrandGame {
+    pub board: GrandBoard,
+    // Positions played before the root, back to the last irreversible move
+    game_history: Vec<u64>,
+
+    node_count: u64,
+    max_time: Option<Instant>,
+    max_nodes: Option<u64>,
+    stop: bool,
+    dont_stop: bool,
+
+    pv_length: [usize; MAX_PLY],
+    pv_table: [[GrandMove; MAX_PLY]; MAX_PLY],
+    killer_moves: [[GrandMove; MAX_PLY]; 2],
+    repetition_table: [u64; MAX_PLY],
+}
+
+impl Default for GrandGame {
+    fn default() -> Self {
+        Self {
+            board: GrandBoard::default(),
+            game_history: Vec::new(),
+            node_count: 0,
+            max_time: None,
+            max_nodes: None,
+            stop: false,
+            dont_stop: false,
+            pv_length: [0; MAX_PLY],
+            pv_table: [[GrandMove::null(); MAX_PLY]; MAX_PLY],
+            killer_moves: [[GrandMove::null(); MAX_PLY]; 2],
+            repetition_table: [0; MAX_PLY],
+        }
+    }
+}
+
+impl GrandGame {
+    pub fn set_pos(&mut self, fen: Option<&str>, moves: &[String]) {
+        self.board = match GrandBoard::from_fen(fen.unwrap_or(START_FEN)) {
+            Ok(board) => board,
+            Err(e) => {
+                eprintln!("invalid position: {:?}", e);
+                GrandBoard::default()
+            }
+        };
+        self.game_history.clear();
+
+        for text in moves {
+            let r#move = match self.board.parse_move(text) {
+                Ok(r#move) => r#move,
+                Err(e) => {
+                    eprintln!("invalid move: {:?}", e);
+                    return;
+                }
+            };
+
+            self.game_history.push(self.board.hash);
+            self.board.make_move(r#move);
+
+            // Nothing before an irreversible move can repeat
+            if self.board.halfmove_clock == 0 {
+                self.game_history.clear();
+            }
+        }
+    }
+
+    pub fn go(
+        &mut self,
+        reciver: &Receiver<()>,
+        sender: &mut Sender<RecivedMessage>,
+        time_control: Option<UciTimeControl>,
+        search_control: Option<UciSearchControl>,
+    ) {
+        let depth = search_control.as_ref().and_then(|s| s.depth.map(|d| d as i32)).unwrap_or(100);
+        self.max_nodes = search_control.as_ref().and_then(|s| s.nodes);
+        self.max_time = search_deadline(time_control, self.board.current_color);
+        self.node_count = 0;
+        self.stop = false;
+        self.repetition_table[0] = self.board.hash;
+
+        let start = Instant::now();
+        let mut best_move = None;
+
+        for d in 1..=depth.min(MAX_PLY as i32 - 1) {
+            // The first iteration always completes, so there is a move to play
+            self.dont_stop = d == 1;
+            let board = self.board.clone();
+            let score = self.neg_max(d, 0, &board, MIN_SCORE, MAX_SCORE, reciver);
+
+            if self.stop {
+                break;
+            }
+
+            let pv = &self.pv_table[0][..self.pv_length[0]];
+            best_move = pv.first().copied();
+
+            let time = start.elapsed();
+            let (cp, mate) = if score.abs() > MATE_SCORE {
+                (None, Some((score.signum() * (MATE_VALUE - score.abs() + 1) / 2) as i8))
+            } else {
+                (Some(score), None)
+            };
+            let pv = pv.iter().map(|r#move| r#move.to_string()).collect::<Vec<_>>().join(" ");
+
+            // Moves on the 10x10 board don't fit `UciMove`, so the PV is written out as text
+            _ = sender.send(RecivedMessage::Info(vec![
+                UciInfoAttribute::Depth(d as u8),
+                UciInfoAttribute::Score { cp, mate, lower_bound: None, upper_bound: None },
+                UciInfoAttribute::Nodes(self.node_count),
+                UciInfoAttribute::Nps((self.node_count as f64 / time.as_secs_f64()) as u64),
+                UciInfoAttribute::Time(time),
+                UciInfoAttribute::Any("pv".to_owned(), pv),
+            ]));
+
+            if score.abs() > MATE_SCORE {
+                break;
+            }
+        }
+
+        let best_move = best_move.map_or("0000".to_owned(), |r#move| r#move.to_string());
+        _ = sender.send(RecivedMessage::VariantBestMove(best_move));
+
+        self.pv_table.fill([GrandMove::null(); MAX_PLY]);
+        self.pv_length.fill(0);
+        self.killer_moves.fill([GrandMove::null(); MAX_PLY]);
+    }
+
+    fn should_stop(&self, reciver: &Receiver<()>) -> bool {
+        !self.dont_stop
+            && (self.max_nodes.is_some_and(|max_nodes| self.node_count >= max_nodes)
+                || (self.node_count & 4095 == 0
+                    && (self.max_time.is_some_and(|max_time| Instant::now() > max_time)
+                        || reciver.try_recv().is_ok())))
+    }
+
+    fn is_repetition(&self, board: &GrandBoard, ply: usize) -> bool {
+        let mut distance = 2;
+        while distance <= board.halfmove_clock as usize {
+            let hash = if distance <= ply {
+                self.repetition_table[ply - distance]
+            } else {
+                match self.game_history.len().checked_sub(distance - ply) {
+                    Some(index) => self.game_history[index],
+                    None => break,
+                }
+            };
+
+            if hash == board.hash {
+                return true;
+            }
+            distance += 2;
+        }
+
+        false
+    }
+
+    // Promotions and captures by their victim and attacker, then killers
+    fn score_move(&self, board: &GrandBoard, r#move: GrandMove, ply: usize) -> i32 {
+        if r#move == self.pv_table[0][ply] && ply < self.pv_length[0] {
+            return 100000;
+        }
+
+        let promotion = r#move.promotion.map_or(0, |kind| SCORES[kind as usize]);
+        if let Some(victim) = board.mailbox[r#move.to as usize] {
+            let attacker = board.mailbox[r#move.from as usize].unwrap();
+            return 10000 + promotion + 10 * SCORES[victim.kind as usize] / 100
+                - SCORES[attacker.kind as usize] / 100;
+        }
+        if promotion != 0 || board.is_capture(r#move) {
+            return 10000 + promotion;
+        }
+
+        if r#move == self.killer_moves[0][ply] {
+            5000
+        } else if r#move == self.killer_moves[1][ply] {
+            4000
+        } else {
+            0
+        }
+    }
+
+    fn order_moves(&self, board: &GrandBoard, moves: &mut [GrandMove], ply: usize) {
+        moves.sort_by_cached_key(|r#move| -self.score_move(board, *r#move, ply));
+    }
+
+    fn neg_max(
+        &mut self,
+        mut depth: i32,
+        ply: usize,
+        board: &GrandBoard,
+        mut alpha: i32,
+        beta: i32,
+        reciver: &Receiver<()>,
+    ) -> i32 {
+        self.pv_length[ply] = ply;
+
+        if ply != 0 && (board.halfmove_clock >= 100 || self.is_repetition(board, ply)) {
+            return 0;
+        }
+
+        let in_check = board.in_check();
+        depth += in_check as i32;
+
+        if depth <= 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(ply, board, alpha, beta);
+        }
+
+        if self.should_stop(reciver) {
+            self.stop = true;
+            return STOPPED;
+        }
+        self.node_count += 1;
+
+        let mut moves = generate_legal_moves(board);
+        if moves.is_empty() {
+            // Stalemate is a draw
+            return if in_check { -MATE_VALUE + ply as i32 } else { 0 };
+        }
+        self.order_moves(board, &mut moves, ply);
+
+        for r#move in moves {
+            let mut new_board = board.clone();
+            new_board.make_move(r#move);
+            self.repetition_table[ply + 1] = new_board.hash;
+
+            let score = -self.neg_max(depth - 1, ply + 1, &new_board, -beta, -alpha, reciver);
+            if self.stop {
+                return STOPPED;
+            }
+
+            if score > alpha {
+                alpha = score;
+
+                self.pv_table[ply][ply] = r#move;
+                for next_ply in (ply + 1)..self.pv_length[ply + 1] {
+                    self.pv_table[ply][next_ply] = self.pv_table[ply + 1][next_ply];
+                }
+                self.pv_length[ply] = self.pv_length[ply + 1];
+
+                if score >= beta {
+                    if !board.is_capture(r#move) && self.killer_moves[0][ply] != r#move {
+                        self.killer_moves[1][ply] = self.killer_moves[0][ply];
+                        self.killer_moves[0][ply] = r#move;
+                    }
+                    return beta;
+                }
+            }
+        }
+
+        alpha
+    }
+
+    fn quiescence(&mut self, ply: usize, board: &GrandBoard, mut alpha: i32, beta: i32) -> i32 {
+        self.node_count += 1;
+
+        let stand_pat = evaluate(board);
+        if stand_pat >= beta {
+            return beta;
+        }
+        if ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves = generate_legal_captures(board);
+        self.order_moves(board, &mut moves, ply);
+
+        for r#move in moves {
+            let mut new_board = board.clone();
+            new_board.make_move(r#move);
+
+            let score = -self.quiescence(ply + 1, &new_board, -beta, -alpha);
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    fn search(fen: &str, moves: &[&str], depth: i32) -> (String, Vec<RecivedMessage>) {
+        let mut game = GrandGame::default();
+        let moves: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+        game.set_pos(Some(fen), &moves);
+
+        let (_stop_sender, stop_reciver) = channel();
+        let (mut sender, reciver) = channel();
+        let search_control = UciSearchControl {
+            search_moves: Vec::new(),
+            mate: None,
+            depth: Some(depth as u8),
+            nodes: None,
+        };
+        game.go(&stop_reciver, &mut sender, None, Some(search_control));
+
+        let messages: Vec<_> = reciver.try_iter().collect();
+        let best_move = messages.iter().find_map(|message| match message {
+            RecivedMessage::VariantBestMove(best_move) => Some(best_move.clone()),
+            _ => None,
+        });
+        (best_move.unwrap(), messages)
+    }
+
+    #[test]
+    fn grand_search() {
+        let (best_move, _) = search(START_FEN, &["e3e4", "e8e7"], 3);
+        let mut board = GrandBoard::default();
+        board.make_move(board.parse_move("e3e4").unwrap());
+        board.make_move(board.parse_move("e8e7").unwrap());
+        assert!(board.parse_move(&best_move).is_ok());
+
+        // The checking marshal is taken
+        let fen = "r8r/1nbqk1cbn1/pppppppppp/10/10/10/5m4/PPPPPPPPPP/1NBQKMCBN1/R8R w - - 0 1";
+        let (best_move, _) = search(fen, &[], 3);
+        assert!(best_move.ends_with("f4"), "{}", best_move);
+    }
+
+    #[test]
+    fn grand_mate() {
+        // The rook and queen mate on the last rank
+        let fen = "4k5/10/4K5/10/10/10/10/10/10/R8Q w - - 0 1";
+        let (best_move, messages) = search(fen, &[], 4);
+        let board = GrandBoard::from_fen(fen).unwrap();
+        let mut new_board = board.clone();
+        new_board.make_move(board.parse_move(&best_move).unwrap());
+        assert!(new_board.in_check() && generate_legal_moves(&new_board).is_empty());
+
+        assert!(messages.iter().any(|message| matches!(
+            message,
+            RecivedMessage::Info(info) if info.iter().any(|attribute| matches!(
+                attribute,
+                UciInfoAttribute::Score { mate: Some(1), .. }
+            ))
+        )));
+    }
+}
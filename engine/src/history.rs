@@ -0,0 +1,154 @@
+use crate::board::{piece::PieceColor, r#move::Move};
+
+// Scores stay within this, every update moves an entry part of the way towards it
+const MAX_HISTORY: i32 = 16384;
+const MAX_BONUS: i32 = 1536;
+
+// Continuation histories are indexed by the piece and target square of an earlier move
+const CONTINUATION_SIZE: usize = 12 * 64;
+
+fn bonus(depth: i32) -> i32 {
+    (32 * depth * depth).min(MAX_BONUS)
+}
+
+// Big entries move less in the same direction and more in the other, so they stay bounded and
+// adapt once a move stops working
+fn apply_gravity(entry: &mut i32, bonus: i32) {
+    *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+}
+
+fn continuation_index(r#move: Move) -> Option<usize> {
+    (r#move != Move::null()).then(|| r#move.piece() as usize * 64 + r#move.to() as usize)
+}
+
+// How well quiet moves did in earlier cutoffs, on their own and as answers to the moves one and
+// two plies before them
+pub struct History {
+    // Indexed by color, from and to square
+    butterfly: [[[i32; 64]; 64]; 2],
+    // The quiet move that last refuted each piece and target square
+    counter_moves: [[Move; 64]; 12],
+    continuation: Vec<[[i32; 64]; 12]>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            butterfly: [[[0; 64]; 64]; 2],
+            counter_moves: [[Move::null(); 64]; 12],
+            continuation: vec![[[0; 64]; 12]; CONTINUATION_SIZE],
+        }
+    }
+}
+
+impl History {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    // Between searches, the old scores still help but the new position should take over quickly
+    pub fn decay(&mut self) {
+        for entry in self.butterfly.iter_mut().flatten().flatten() {
+            *entry /= 2;
+        }
+        for entry in self.continuation.iter_mut().flatten().flatten() {
+            *entry /= 2;
+        }
+    }
+
+    pub fn counter_move(&self, previous: Move) -> Move {
+        if previous == Move::null() {
+            return Move::null();
+        }
+        self.counter_moves[previous.piece() as usize][previous.to() as usize]
+    }
+
+    // `previous` holds the moves one and two plies back, null if there are none
+    pub fn quiet_score(&self, color: PieceColor, r#move: Move, previous: [Move; 2]) -> i32 {
+        let (from, to, _, piece, _) = r#move.unpack();
+        let mut score = self.butterfly[color as usize][from][to];
+
+        for index in previous.into_iter().filter_map(continuation_index) {
+            score += self.continuation[index][piece as usize][to];
+        }
+
+        score
+    }
+
+    // `best` caused a cutoff, the quiet moves searched before it did not
+    pub fn update_quiets(
+        &mut self,
+        color: PieceColor,
+        best: Move,
+        tried: &[Move],
+        previous: [Move; 2],
+        depth: i32,
+    ) {
+        let bonus = bonus(depth);
+
+        self.update_quiet(color, best, previous, bonus);
+        for &r#move in tried {
+            self.update_quiet(color, r#move, previous, -bonus);
+        }
+
+        if previous[0] != Move::null() {
+            self.counter_moves[previous[0].piece() as usize][previous[0].to() as usize] = best;
+        }
+    }
+
+    fn update_quiet(&mut self, color: PieceColor, r#move: Move, previous: [Move; 2], bonus: i32) {
+        let (from, to, _, piece, _) = r#move.unpack();
+        apply_gravity(&mut self.butterfly[color as usize][from][to], bonus);
+
+        for index in previous.into_iter().filter_map(continuation_index) {
+            apply_gravity(&mut self.continuation[index][piece as usize][to], bonus);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{
+        piece::{Piece, PieceType},
+        r#move::MoveType,
+    };
+
+    use super::*;
+
+    fn quiet(from: u32, to: u32, piece: Piece) -> Move {
+        Move::new(from, to, MoveType::QuietMove, piece, PieceType::Empty)
+    }
+
+    #[test]
+    fn history_updates() {
+        let mut history = History::default();
+        let previous = [quiet(52, 36, Piece::BlackPawn), quiet(12, 28, Piece::WhitePawn)];
+        let best = quiet(6, 21, Piece::WhiteKnight);
+        let tried = [quiet(1, 18, Piece::WhiteKnight), quiet(5, 26, Piece::WhiteBishop)];
+
+        history.update_quiets(PieceColor::White, best, &tried, previous, 4);
+        let score = history.quiet_score(PieceColor::White, best, previous);
+        assert_eq!(score, 3 * bonus(4));
+        assert_eq!(history.quiet_score(PieceColor::White, tried[0], previous), -score);
+        assert_eq!(history.counter_move(previous[0]), best);
+
+        // Only the butterfly entry carries over to another line or to the other color
+        let other = [quiet(51, 35, Piece::BlackPawn), Move::null()];
+        assert_eq!(history.quiet_score(PieceColor::White, best, other), bonus(4));
+        assert_eq!(history.quiet_score(PieceColor::Black, best, previous), 2 * bonus(4));
+        assert_eq!(history.counter_move(other[0]), Move::null());
+
+        // Repeated bonuses level off below the maximum
+        for _ in 0..1000 {
+            history.update_quiets(PieceColor::White, best, &[], previous, 20);
+        }
+        let score = history.quiet_score(PieceColor::White, best, [Move::null(); 2]);
+        assert!(score > MAX_HISTORY * 9 / 10 && score <= MAX_HISTORY, "{}", score);
+
+        history.decay();
+        assert_eq!(history.quiet_score(PieceColor::White, best, [Move::null(); 2]), score / 2);
+
+        history.clear();
+        assert_eq!(history.quiet_score(PieceColor::White, best, previous), 0);
+    }
+}
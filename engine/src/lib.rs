@@ -1,23 +1,49 @@
 #![feature(test, min_generic_const_args)]
 #![allow(incomplete_features, clippy::identity_op, clippy::needless_range_loop)]
 pub mod board;
+pub mod book;
+pub mod epd;
+pub mod grand;
+mod history;
+mod movepick;
 pub mod nnue;
+mod params;
+pub mod pgn;
+mod root;
+pub mod syzygy;
 mod transposition;
 pub mod uci;
 
 static NET: &[u8] = include_bytes!(env!("EVALFILE"));
 
-use std::{sync::mpsc::Receiver, time::Instant};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use ::uci::{RecivedMessage, UciInfoAttribute};
 
 use board::{
-    movegen::{generate_captures, generate_moves},
-    piece::{PieceColor, PieceType},
-    r#move::{Move, MoveType},
+    movegen::{generate_legal_moves, Moves},
+    piece::PieceColor,
+    r#move::Move,
     Board,
 };
+use book::Book;
+use grand::search::GrandGame;
+use history::History;
+use movepick::{is_quiet, MovePicker};
 use nnue::{half_kp::HalfKP, network::TripleLayerNetwork, Nnue};
+use params::SearchParams;
+use root::RootMoves;
+use syzygy::{Tablebases, WDL_LOSS, WDL_WIN};
 
-use self::transposition::{HashFlags, THash, TTable};
+pub use self::transposition::TTStats;
+use self::transposition::{HashFlags, THash, TTable, EVAL_NONE};
 
 const MIN_SCORE: i32 = -50000;
 const MAX_SCORE: i32 = 50000;
@@ -28,50 +54,125 @@ const MATE_SCORE: i32 = 48000;
 
 const MAX_PLY: usize = 128;
 
+// Tablebase wins are scored below the mates but above any evaluation
+const TB_WIN_SCORE: i32 = MATE_SCORE - 2 * MAX_PLY as i32;
+
+// Scores beyond this are mates or tablebase results, which pruning margins don't apply to
+const DECISIVE_SCORE: i32 = TB_WIN_SCORE - MAX_PLY as i32;
+
 const STOPPED: i32 = -1000000;
 
+// Searches running longer than this report the root move they are on
+const CURRMOVE_DELAY: Duration = Duration::from_secs(3);
+
 pub struct GrandChessEngine {
     node_count: u64,
 
     max_time: Option<Instant>,
+    max_nodes: Option<u64>,
 
-    ply_offset: u32,
-
-    tt: TTable,
+    // Shared with the helper threads
+    tt: Arc<TTable>,
+    // Collect and print the transposition table counters after every search
+    hash_stats: bool,
 
     pv_length: [usize; MAX_PLY],
     pv_table: [[Move; MAX_PLY]; MAX_PLY],
 
     killer_moves: [[Move; MAX_PLY]; 2],
-    history_moves: [[u32; 64]; 12],
+    // Kept between searches, only decayed
+    history: History,
+    // The move played and the static eval at every ply of the current line
+    move_stack: [Move; MAX_PLY],
+    eval_stack: [i32; MAX_PLY],
+    // While verifying a null move cutoff, `null_color` may not pass again before this ply
+    null_min_ply: usize,
+    null_color: PieceColor,
 
     repetition_table: [u64; MAX_PLY],
+    // Positions played before the root, back to the last irreversible move
+    game_history: Vec<u64>,
 
     board: Board,
 
+    params: SearchParams,
+
     nnue: Box<Nnue<TripleLayerNetwork<512, 32, 32>, HalfKP, MAX_PLY>>,
 
     stop: bool,
 
     dont_stop: bool,
+
+    book: Option<Book>,
+    own_book: bool,
+    // Only moves up to this fullmove number are taken from the book
+    book_depth: u32,
+    book_best_move: bool,
+
+    // Moves are read and written with the king taking its own rook to castle
+    chess960: bool,
+    // Set while playing Grand Chess, which has its own board and search
+    grand: Option<Box<GrandGame>>,
+
+    tablebases: Option<Arc<Tablebases>>,
+    // Rebuilt for every search, in the order of the last iteration
+    root_moves: RootMoves,
+    // Lines searched for every depth, each without the first moves of the ones before it
+    multi_pv: usize,
+    // The root moves before this are the lines already found in this iteration
+    pv_index: usize,
+    // Depth of the current iteration, singular extensions stop at twice of it
+    root_depth: i32,
+
+    // Lazy SMP helpers, each searching the same position with its own tables and sharing only the
+    // transposition table. The main thread sets `stop_flag` once it is done
+    helpers: Vec<GrandChessEngine>,
+    stop_flag: Arc<AtomicBool>,
+    // `node_count` published for the main thread while searching as a helper
+    nodes: Arc<AtomicU64>,
 }
 
 impl GrandChessEngine {
     pub fn new(tt_bytes: usize) -> Self {
+        Self::with_tt(Arc::new(TTable::new(tt_bytes)))
+    }
+
+    fn with_tt(tt: Arc<TTable>) -> Self {
         Self {
             node_count: 0,
             max_time: None,
-            ply_offset: 0,
-            tt: TTable::new(tt_bytes),
+            max_nodes: None,
+            tt,
+            hash_stats: false,
             pv_length: [0; MAX_PLY],
             pv_table: [[Move::null(); MAX_PLY]; MAX_PLY],
             killer_moves: [[Move::null(); MAX_PLY]; 2],
-            history_moves: [[0; 64]; 12],
+            history: History::default(),
+            move_stack: [Move::null(); MAX_PLY],
+            eval_stack: [EVAL_NONE; MAX_PLY],
+            null_min_ply: 0,
+            null_color: PieceColor::White,
             repetition_table: [0; MAX_PLY],
+            game_history: Vec::new(),
             board: Board::default(),
+            params: SearchParams::default(),
             nnue: Nnue::new_boxed(&mut std::io::Cursor::new(NET)),
             stop: false,
             dont_stop: false,
+            book: None,
+            own_book: false,
+            book_depth: 20,
+            book_best_move: false,
+            chess960: false,
+            grand: None,
+            tablebases: None,
+            root_moves: RootMoves::default(),
+            multi_pv: 1,
+            pv_index: 0,
+            root_depth: 0,
+            helpers: Vec::new(),
+            stop_flag: Arc::new(AtomicBool::new(false)),
+            nodes: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -79,21 +180,22 @@ impl GrandChessEngine {
         let start_time = Instant::now();
         self.node_count = 0;
         for fen in benches {
+            let mut board = Board::from_fen(fen).unwrap();
+            self.game_history.clear();
+            self.repetition_table[0] = board.hash;
+
+            self.root_moves = RootMoves::new(&board, &[], &[]);
+
             for i in 1..depth {
-                self.neg_max(
-                    i as i32,
-                    0,
-                    &Board::from_fen(fen).unwrap(),
-                    MIN_SCORE,
-                    MAX_SCORE,
-                    None,
-                );
+                self.root_moves.new_iteration();
+                self.search_root(i as i32, &mut board, MIN_SCORE, MAX_SCORE, None, None);
+                self.root_moves.finish_iteration(1);
             }
 
             self.tt.clear();
             self.pv_table.fill([Move::null(); 128]);
             self.pv_length.fill(0);
-            self.history_moves.fill([0; 64]);
+            self.history.clear();
             self.killer_moves.fill([Move::null(); MAX_PLY]);
             self.repetition_table.fill(0);
         }
@@ -104,68 +206,158 @@ impl GrandChessEngine {
         );
     }
 
-    fn quiescence(&mut self, ply: usize, board: &Board, mut alpha: i32, beta: i32) -> i32 {
-        let best_move = Move::null();
-        let stand_pat = (self.nnue.eval(ply, board.current_color) + board.eval()) / 2;
+    fn quiescence(&mut self, ply: usize, board: &mut Board, mut alpha: i32, beta: i32) -> i32 {
+        if board.is_insufficient_material()
+            || (board.is_fifty_move_draw() && !board.is_king_attacked(board.current_color))
+        {
+            return 0;
+        }
+
+        let stand_pat = self.static_eval(ply, board);
 
         if stand_pat >= beta {
             return beta;
         }
 
+        if ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+
         alpha = alpha.max(stand_pat);
 
-        let mut moves = generate_captures(board);
+        // Captures that lose material can't raise alpha above the stand pat, so they are skipped
+        let mut picker = MovePicker::captures(board);
+        while let Some(r#move) = picker.next(board, &self.history) {
+            let undo = self.nnue.make_move(r#move, board, ply);
+            let score = -self.quiescence(ply + 1, board, -beta, -alpha);
+            board.unmake_move(r#move, &undo);
 
-        for i in 0..moves.len() {
-            let mut max_index = i;
-            for j in i..moves.len() {
-                if self.score_capture(moves[j], best_move)
-                    > self.score_capture(moves[max_index], best_move)
-                {
-                    max_index = j;
-                }
+            if score > alpha {
+                alpha = score;
             }
 
-            moves.swap(i, max_index);
+            if score >= beta {
+                return beta;
+            }
+        }
 
-            let mut new_board = board.clone();
+        alpha
+    }
 
-            self.nnue.make_move(moves[i], &mut new_board, ply);
+    // Searches the root moves from `pv_index` on in their order, each one is given the score and
+    // the line with which it raised alpha. `info` is where `currmove` is reported once the search
+    // started there is running long
+    fn search_root(
+        &mut self,
+        mut depth: i32,
+        board: &mut Board,
+        mut alpha: i32,
+        beta: i32,
+        reciver: Option<&Receiver<()>>,
+        info: Option<(&Sender<RecivedMessage>, Instant)>,
+    ) -> i32 {
+        self.pv_length[0] = 0;
+        self.root_depth = depth;
 
-            if new_board.is_king_attacked(board.current_color) {
-                break;
+        let in_check = board.is_king_attacked(board.current_color);
+        if self.root_moves.is_empty() {
+            return in_check as i32 * -(MATE_VALUE + depth);
+        }
+
+        depth += in_check as i32;
+        self.eval_stack[0] = if in_check { EVAL_NONE } else { self.static_eval(0, board) };
+
+        for index in self.pv_index..self.root_moves.len() {
+            let r#move = self.root_moves[index].r#move;
+
+            if let Some((sender, _)) = info.filter(|(_, start)| start.elapsed() >= CURRMOVE_DELAY) {
+                _ = sender.send(RecivedMessage::Info(vec![
+                    UciInfoAttribute::Depth(depth as u8),
+                    UciInfoAttribute::CurrMove(uci::to_uci_move(r#move, self.chess960)),
+                    UciInfoAttribute::CurrMoveNum(index as u16 + 1),
+                ]));
             }
 
-            if moves[i].move_type() == MoveType::KingCastle {
-                let castle_target = if board.current_color == PieceColor::White { 5 } else { 61 };
-                if new_board.is_square_attacked(castle_target, new_board.current_color) {
-                    break;
-                }
+            let nodes = self.node_count;
+            self.move_stack[0] = r#move;
+            let undo = self.nnue.make_move(r#move, board, 0);
+            self.repetition_table[1] = board.hash;
 
-                if board.is_king_attacked(board.current_color) {
-                    break;
+            // The first move of the line gets the full window, the others have to be proven worse
+            let score = if index == self.pv_index {
+                -self.neg_max(depth - 1, 1, board, -beta, -alpha, Move::null(), reciver)
+            } else {
+                // Late quiet moves are searched less deep, as at any PV node that isn't improving
+                let moves_searched = index - self.pv_index;
+                let mut reduction = 0;
+                if is_quiet(r#move)
+                    && !in_check
+                    && depth >= self.params.lmr_min_depth
+                    && moves_searched >= self.params.lmr_min_moves as usize
+                {
+                    reduction = self.params.reduction(depth, moves_searched).min(depth - 2).max(0);
                 }
-            } else if moves[i].move_type() == MoveType::QueenCastle {
-                let castle_target = if board.current_color == PieceColor::White { 3 } else { 59 };
-                if new_board.is_square_attacked(castle_target, new_board.current_color) {
-                    break;
+
+                let mut score = -self.neg_max(
+                    depth - 1 - reduction,
+                    1,
+                    board,
+                    -alpha - 1,
+                    -alpha,
+                    Move::null(),
+                    reciver,
+                );
+
+                if score > alpha && reduction > 0 {
+                    score = -self.neg_max(
+                        depth - 1,
+                        1,
+                        board,
+                        -alpha - 1,
+                        -alpha,
+                        Move::null(),
+                        reciver,
+                    );
                 }
-                if board.is_king_attacked(board.current_color) {
-                    break;
+
+                if score > alpha && score < beta {
+                    score =
+                        -self.neg_max(depth - 1, 1, board, -beta, -alpha, Move::null(), reciver);
                 }
-            }
 
-            let score = -self.quiescence(ply + 1, &new_board, -beta, -alpha);
+                score
+            };
+
+            board.unmake_move(r#move, &undo);
+            self.node_count += 1;
+            self.root_moves[index].nodes += self.node_count - nodes;
+
+            if self.stop {
+                return STOPPED;
+            }
 
             if score > alpha {
                 alpha = score;
+
+                self.pv_table[0][0] = r#move;
+                for next_ply in 1..self.pv_length[1] {
+                    self.pv_table[0][next_ply] = self.pv_table[1][next_ply];
+                }
+                self.pv_length[0] = self.pv_length[1];
+
+                let root_move = &mut self.root_moves[index];
+                root_move.score = score;
+                root_move.pv = self.pv_table[0][..self.pv_length[0]].to_vec();
             }
 
             if score >= beta {
-                return beta;
+                alpha = beta;
+                break;
             }
         }
 
+        // The best move of the line goes first for the next search
+        self.root_moves.sort_from(self.pv_index);
         alpha
     }
 
@@ -173,42 +365,78 @@ impl GrandChessEngine {
         &mut self,
         mut depth: i32,
         ply: usize,
-        board: &Board,
+        board: &mut Board,
         mut alpha: i32,
         beta: i32,
+        // Searched as if this move wasn't legal, to see how the others do without it
+        excluded: Move,
         reciver: Option<&Receiver<()>>,
     ) -> i32 {
         self.pv_length[ply] = ply;
 
-        if self.is_repetition(board.hash, ply) {
+        if ply != 0 && self.is_repetition(board, ply) {
             return 0;
         }
+
+        if ply != 0 && board.is_insufficient_material() {
+            return 0;
+        }
+
         let in_check = board.is_king_attacked(board.current_color);
 
+        // Mate on the hundredth ply still counts, so only claim the draw if we have a move
+        if ply != 0
+            && board.is_fifty_move_draw()
+            && (!in_check || !generate_legal_moves(board).is_empty())
+        {
+            return 0;
+        }
+
         depth += in_check as i32;
 
-        if depth <= 0 {
+        if depth <= 0 || ply >= MAX_PLY - 1 {
             return self.quiescence(ply, board, alpha, beta);
         }
 
-        if self.node_count & 16383 == 0
-            && ((self.max_time.is_some() && Instant::now() > self.max_time.unwrap())
-                || reciver.map(|recv| recv.try_recv().is_ok()).unwrap_or(false))
+        // The node limit is checked at every node, it is only overshot by the first iteration,
+        // which always completes, and by the few nodes counted while unwinding
+        if (self.max_nodes.is_some_and(|max_nodes| self.node_count >= max_nodes)
+            || (self.node_count & 16383 == 0
+                && ((self.max_time.is_some() && Instant::now() > self.max_time.unwrap())
+                    || reciver.map(|recv| recv.try_recv().is_ok()).unwrap_or(false)
+                    || self.stop_flag.load(Ordering::Relaxed))))
             && !self.dont_stop
         {
             self.stop = true;
             return STOPPED;
         }
 
+        if self.node_count & 1023 == 0 {
+            self.nodes.store(self.node_count, Ordering::Relaxed);
+        }
+
         let is_pv = (beta - alpha) > 1;
+        // Searches without a move don't say anything about the position itself, so they leave its
+        // entry alone
+        let writes_entry = excluded == Move::null();
 
         let mut best_move = Move::null();
 
-        let entry = self.tt.probe_entry(board, board.hash, ply as u32);
+        // An entry whose move can't be played here belongs to another position with the same key
+        let entry = self.tt.probe_entry(board, board.hash, ply as u32).filter(|entry| {
+            entry.best_move == Move::null() || board.is_pseudo_legal(entry.best_move)
+        });
+
+        let static_eval = match entry.map(|entry| entry.eval) {
+            _ if in_check => EVAL_NONE,
+            Some(eval) if eval != EVAL_NONE => eval,
+            _ => self.static_eval(ply, board),
+        };
+
         if let Some(entry) = entry {
             best_move = entry.best_move;
 
-            if entry.depth >= depth && !is_pv {
+            if entry.depth >= depth && !is_pv && excluded == Move::null() {
                 match entry.flags {
                     HashFlags::Exsact => return entry.score,
                     HashFlags::Alpha => {
@@ -225,87 +453,280 @@ impl GrandChessEngine {
             }
         };
 
-        if !in_check && ply != 0 && !is_pv {
-            let mut null_board = board.clone();
-
-            null_board.make_null_move();
-            const R: i32 = 2;
+        // Better than two plies ago, when it was also our move
+        let improving = !in_check
+            && ply >= 2
+            && self.eval_stack[ply - 2] != EVAL_NONE
+            && static_eval > self.eval_stack[ply - 2];
+
+        let mut max_score = MAX_SCORE;
+
+        // Tables are only probed right after captures and pawn moves, any other position with the
+        // same material was already probed further up
+        if let Some(tablebases) = self.tablebases.as_ref().filter(|tablebases| {
+            ply != 0
+                && excluded == Move::null()
+                && board.halfmove_clock == 0
+                && board.castle_flags.is_empty()
+                && board.bit_boards.occupancy().count_ones() as usize <= tablebases.cardinality()
+        }) {
+            if let Some(wdl) = tablebases.probe_wdl(board) {
+                // A win is only a lower bound and a loss an upper bound, the search can still find
+                // a mate or a longer defence
+                match wdl {
+                    WDL_WIN => {
+                        let score = TB_WIN_SCORE - ply as i32;
+                        if score >= beta {
+                            return score;
+                        } else if is_pv {
+                            alpha = alpha.max(score);
+                        }
+                    }
+                    WDL_LOSS => {
+                        let score = -TB_WIN_SCORE + ply as i32;
+                        if score <= alpha {
+                            return score;
+                        } else if is_pv {
+                            max_score = score;
+                        }
+                    }
+                    _ => return wdl * 2,
+                }
+            }
+        }
 
-            let null_score =
-                -self.neg_max(depth - 1 - R, ply, &null_board, -beta, -beta + 1, reciver);
+        // Pruning by the static eval, only where it can be trusted
+        let prune = !in_check
+            && ply != 0
+            && !is_pv
+            && excluded == Move::null()
+            && beta.abs() < DECISIVE_SCORE;
+
+        // Far ahead, a move that loses a bit still beats beta
+        if prune
+            && depth <= self.params.rfp_max_depth
+            && static_eval - self.params.rfp_margin * (depth - improving as i32) >= beta
+        {
+            return beta;
+        }
 
-            if null_score >= beta {
-                return beta;
+        // Far behind, only captures might save the position
+        if prune
+            && depth <= self.params.razor_max_depth
+            && static_eval + self.params.razor_margin * depth < alpha
+        {
+            let score = self.quiescence(ply, board, alpha, alpha + 1);
+            if score <= alpha {
+                return alpha;
             }
         }
 
-        let mut hash_flag = HashFlags::Alpha;
+        // Passing is tried when even that seems to hold beta, never twice in a row, and not in
+        // endgames where zugzwang makes having to move a disadvantage
+        if prune
+            && static_eval >= beta
+            && self.move_stack[ply - 1] != Move::null()
+            && (ply >= self.null_min_ply || board.current_color != self.null_color)
+            && board.bit_boards.has_non_pawn_material(board.current_color)
+        {
+            let reduction = self.params.null_reduction(depth, static_eval - beta);
+
+            self.move_stack[ply] = Move::null();
+            let undo = self.nnue.make_null_move(board, ply);
+            self.repetition_table[ply + 1] = board.hash;
+
+            let null_score = -self.neg_max(
+                depth - 1 - reduction,
+                ply + 1,
+                board,
+                -beta,
+                -beta + 1,
+                Move::null(),
+                reciver,
+            );
+            board.unmake_null_move(&undo);
 
-        let mut moves = generate_moves(board);
+            if self.stop {
+                return STOPPED;
+            }
 
-        let mut moves_searched = 0;
+            if null_score >= beta {
+                if depth < self.params.null_verify_depth || self.null_min_ply != 0 {
+                    return beta;
+                }
 
-        for i in 0..moves.len() {
-            let mut max_index = i;
-            for j in i..moves.len() {
-                if self.score_move(moves[j], ply, best_move)
-                    > self.score_move(moves[max_index], ply, best_move)
-                {
-                    max_index = j;
+                // Deep down the same search without passing for this side, for most of what
+                // remains of it
+                self.null_min_ply = ply + 3 * (depth - reduction).max(0) as usize / 4;
+                self.null_color = board.current_color;
+                let score = self.neg_max(
+                    depth - reduction,
+                    ply,
+                    board,
+                    beta - 1,
+                    beta,
+                    Move::null(),
+                    reciver,
+                );
+                self.null_min_ply = 0;
+
+                if self.stop {
+                    return STOPPED;
+                }
+
+                if score >= beta {
+                    return beta;
                 }
             }
+        }
 
-            moves.swap(i, max_index);
+        let mut hash_flag = HashFlags::Alpha;
+
+        let previous = [
+            if ply > 0 { self.move_stack[ply - 1] } else { Move::null() },
+            if ply > 1 { self.move_stack[ply - 2] } else { Move::null() },
+        ];
+        let killers = [self.killer_moves[0][ply], self.killer_moves[1][ply]];
+        let mut picker = MovePicker::new(board, best_move, killers, previous);
 
-            let mut new_board = board.clone();
+        self.eval_stack[ply] = static_eval;
 
-            self.nnue.make_move(moves[i], &mut new_board, ply);
+        let mut moves_searched = 0;
+        // Quiet moves that didn't cause a cutoff, they lose history if a later one does
+        let mut quiets_tried = Moves::default();
 
-            if new_board.is_king_attacked(board.current_color) {
+        while let Some(r#move) = picker.next(board, &self.history) {
+            if r#move == excluded {
                 continue;
             }
 
-            if moves[i].move_type() == MoveType::KingCastle {
-                let castle_target = if board.current_color == PieceColor::White { 5 } else { 61 };
-                if new_board.is_square_attacked(castle_target, !board.current_color) {
+            let quiet = is_quiet(r#move);
+
+            // Once a move is searched, quiet moves that can't be expected to reach alpha are
+            // skipped, the side to move is not mated either way
+            if quiet && moves_searched > 0 && !in_check && !is_pv && alpha.abs() < DECISIVE_SCORE {
+                if depth <= self.params.futility_max_depth
+                    && static_eval + self.params.futility_base + self.params.futility_margin * depth
+                        <= alpha
+                {
                     continue;
                 }
-                if in_check {
+
+                if depth <= self.params.lmp_max_depth
+                    && quiets_tried.len() >= self.params.lmp_count(depth, improving)
+                {
                     continue;
                 }
-            } else if moves[i].move_type() == MoveType::QueenCastle {
-                let castle_target = if board.current_color == PieceColor::White { 3 } else { 59 };
-                if new_board.is_square_attacked(castle_target, !board.current_color) {
-                    continue;
+            }
+
+            let history_score = if quiet {
+                self.history.quiet_score(board.current_color, r#move, previous)
+            } else {
+                0
+            };
+
+            // A table move that is much better than every other one is searched a ply deeper
+            let mut extension = 0;
+            if let Some(entry) = entry.filter(|entry| {
+                ply != 0
+                    && excluded == Move::null()
+                    && depth >= self.params.singular_min_depth
+                    && (ply as i32) < 2 * self.root_depth
+                    && entry.best_move == r#move
+                    && entry.flags != HashFlags::Alpha
+                    && entry.depth >= depth - 3
+                    && entry.score.abs() < DECISIVE_SCORE
+            }) {
+                let singular_beta = entry.score - self.params.singular_margin * depth;
+                let score = self.neg_max(
+                    (depth - 1) / 2,
+                    ply,
+                    board,
+                    singular_beta - 1,
+                    singular_beta,
+                    r#move,
+                    reciver,
+                );
+                // The search of the same position left its own line here
+                self.pv_length[ply] = ply;
+
+                if self.stop {
+                    return STOPPED;
                 }
-                if in_check {
-                    continue;
+
+                if score < singular_beta {
+                    extension = 1;
+                } else if singular_beta >= beta {
+                    // Another move beats beta as well, so one of them will likely cut
+                    return beta;
+                } else if entry.score >= beta {
+                    // Not the only good move, and the node is expected to cut anyway
+                    extension = -1;
                 }
             }
+            let new_depth = depth - 1 + extension;
+
+            self.move_stack[ply] = r#move;
+            let undo = self.nnue.make_move(r#move, board, ply);
+            self.repetition_table[ply + 1] = board.hash;
 
+            // Only the first move gets the full window, the others just have to be proven worse
             let score = if moves_searched == 0 {
-                self.repetition_table[ply + 1] = new_board.hash;
-                -self.neg_max(depth - 1, ply + 1, &new_board, -beta, -alpha, reciver)
+                -self.neg_max(new_depth, ply + 1, board, -beta, -alpha, Move::null(), reciver)
             } else {
-                let score = if moves_searched >= 4
-                    && depth >= 3
+                // Late quiet moves are searched less deep, unless they turn out better than alpha
+                let mut reduction = 0;
+                if quiet
                     && !in_check
-                    && moves[i].move_type() < MoveType::EnPassantCapture
+                    && depth >= self.params.lmr_min_depth
+                    && moves_searched >= self.params.lmr_min_moves as usize
                 {
-                    self.repetition_table[ply + 1] = new_board.hash;
-                    -self.neg_max(depth - 2, ply + 1, &new_board, -alpha - 1, -alpha, reciver)
-                } else {
-                    alpha + 1
-                };
-
-                if score > alpha {
-                    self.repetition_table[ply + 1] = new_board.hash;
-                    -self.neg_max(depth - 1, ply + 1, &new_board, -beta, -alpha, reciver)
-                } else {
-                    score
+                    reduction = self.params.reduction(depth, moves_searched) - is_pv as i32
+                        + !improving as i32
+                        - history_score / self.params.lmr_history_divisor;
+                    // Not clamped, at depth one the upper bound is below zero
+                    reduction = reduction.min(new_depth - 1).max(0);
+                }
+
+                let mut score = -self.neg_max(
+                    new_depth - reduction,
+                    ply + 1,
+                    board,
+                    -alpha - 1,
+                    -alpha,
+                    Move::null(),
+                    reciver,
+                );
+
+                if score > alpha && reduction > 0 {
+                    score = -self.neg_max(
+                        new_depth,
+                        ply + 1,
+                        board,
+                        -alpha - 1,
+                        -alpha,
+                        Move::null(),
+                        reciver,
+                    );
                 }
+
+                if score > alpha && score < beta {
+                    score = -self.neg_max(
+                        new_depth,
+                        ply + 1,
+                        board,
+                        -beta,
+                        -alpha,
+                        Move::null(),
+                        reciver,
+                    );
+                }
+
+                score
             };
 
+            board.unmake_move(r#move, &undo);
+
             self.node_count += 1;
             moves_searched += 1;
 
@@ -315,15 +736,10 @@ impl GrandChessEngine {
 
             if score > alpha && !self.stop {
                 alpha = score;
-                best_move = moves[i];
+                best_move = r#move;
                 hash_flag = HashFlags::Exsact;
 
-                if moves[i].captured() != PieceType::Empty {
-                    self.history_moves[moves[i].piece() as usize][moves[i].to() as usize] +=
-                        (depth) as u32;
-                }
-
-                self.pv_table[ply][ply] = moves[i];
+                self.pv_table[ply][ply] = r#move;
 
                 for next_ply in (ply + 1)..self.pv_length[ply + 1] {
                     self.pv_table[ply][next_ply] = self.pv_table[ply + 1][next_ply];
@@ -333,23 +749,50 @@ impl GrandChessEngine {
             }
 
             if score >= beta {
-                self.tt.write_entry(
-                    THash::new(board.hash, depth, score, best_move, HashFlags::Beta),
-                    ply as u32,
-                );
-                if moves[i].captured() != PieceType::Empty {
-                    self.killer_moves[1][ply] = self.killer_moves[0][ply];
-                    self.killer_moves[0][ply] = moves[i];
+                if writes_entry {
+                    self.tt.write_entry(
+                        THash::new(
+                            board.hash,
+                            depth,
+                            score,
+                            static_eval,
+                            best_move,
+                            HashFlags::Beta,
+                        ),
+                        ply as u32,
+                    );
+                }
+                if is_quiet(r#move) {
+                    if self.killer_moves[0][ply] != r#move {
+                        self.killer_moves[1][ply] = self.killer_moves[0][ply];
+                        self.killer_moves[0][ply] = r#move;
+                    }
+                    self.history.update_quiets(
+                        board.current_color,
+                        r#move,
+                        &quiets_tried,
+                        previous,
+                        depth,
+                    );
                 }
                 return beta;
             }
+
+            if is_quiet(r#move) {
+                quiets_tried.push(r#move);
+            }
         }
 
         if moves_searched == 0 {
+            // The excluded move is still there to be played
+            if excluded != Move::null() {
+                return alpha;
+            }
             return in_check as i32 * -(MATE_VALUE + depth);
-        } else {
+        } else if writes_entry {
+            alpha = alpha.min(max_score);
             self.tt.write_entry(
-                THash::new(board.hash, depth, alpha, best_move, hash_flag),
+                THash::new(board.hash, depth, alpha, static_eval, best_move, hash_flag),
                 ply as u32,
             );
         }
@@ -357,40 +800,472 @@ impl GrandChessEngine {
         alpha
     }
 
-    fn is_repetition(&self, hash: u64, ply: usize) -> bool {
-        for i in 0..ply {
-            if hash == self.repetition_table[i] {
-                return true;
+    // Counters of the last search, only collected with the HashStats option
+    pub fn tt_stats(&self) -> TTStats {
+        self.tt.stats()
+    }
+
+    fn static_eval(&mut self, ply: usize, board: &Board) -> i32 {
+        (self.nnue.eval(ply, board.current_color) + board.eval()) / 2
+    }
+
+    // A repetition inside the search tree is scored as a draw right away, positions from
+    // before the root have to be on the board three times
+    fn is_repetition(&self, board: &Board, ply: usize) -> bool {
+        let mut previous = 0;
+
+        // Only positions with the same side to move since the last capture or pawn move
+        let mut distance = 2;
+        while distance <= board.halfmove_clock as usize {
+            let hash = if distance <= ply {
+                self.repetition_table[ply - distance]
+            } else {
+                match self.game_history.len().checked_sub(distance - ply) {
+                    Some(index) => self.game_history[index],
+                    None => break,
+                }
+            };
+
+            if hash == board.hash {
+                if distance < ply {
+                    return true;
+                }
+
+                previous += 1;
+                if previous == 2 {
+                    return true;
+                }
             }
+
+            distance += 2;
         }
+
         false
     }
+}
 
-    fn score_move(&self, r#move: Move, ply: usize, best_move: Move) -> u32 {
-        if r#move == best_move {
-            return 200000;
-        }
-        if r#move == self.pv_table[0][ply] {
-            return 100000;
+pub static BENCHES: [&str; 26] = [
+    "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14",
+    "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24",
+    "r3qbrk/6p1/2b2pPp/p3pP1Q/PpPpP2P/3P1B2/2PB3K/R5R1 w - - 16 42",
+    "6k1/1R3p2/6p1/2Bp3p/3P2q1/P7/1P2rQ1K/5R2 b - - 4 44",
+    "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54",
+    "7r/2p3k1/1p1p1qp1/1P1Bp3/p1P2r1P/P7/4R3/Q4RK1 w - - 0 36",
+    "r1bq1rk1/pp2b1pp/n1pp1n2/3P1p2/2P1p3/2N1P2N/PP2BPPP/R1BQ1RK1 b - - 2 10",
+    "3r3k/2r4p/1p1b3q/p4P2/P2Pp3/1B2P3/3BQ1RP/6K1 w - - 3 87",
+    "2r4r/1p4k1/1Pnp4/3Qb1pq/8/4BpPp/5P2/2RR1BK1 w - - 0 42",
+    "4q1bk/6b1/7p/p1p4p/PNPpP2P/KN4P1/3Q4/4R3 b - - 0 37",
+    "2q3r1/1r2pk2/pp3pp1/2pP3p/P1Pb1BbP/1P4Q1/R3NPP1/4R1K1 w - - 2 34",
+    "1r2r2k/1b4q1/pp5p/2pPp1p1/P3Pn2/1P1B1Q1P/2R3P1/4BR1K b - - 1 37",
+    "r3kbbr/pp1n1p1P/3ppnp1/q5N1/1P1pP3/P1N1B3/2P1QP2/R3KB1R b KQkq b3 0 17",
+    "8/6pk/2b1Rp2/3r4/1R1B2PP/P5K1/8/2r5 b - - 16 42",
+    "1r4k1/4ppb1/2n1b1qp/pB4p1/1n1BP1P1/7P/2PNQPK1/3RN3 w - - 8 29",
+    "8/p2B4/PkP5/4p1pK/4Pb1p/5P2/8/8 w - - 29 68",
+    "3r4/ppq1ppkp/4bnp1/2pN4/2P1P3/1P4P1/PQ3PBP/R4K2 b - - 2 20",
+    "5rr1/4n2k/4q2P/P1P2n2/3B1p2/4pP2/2N1P3/1RR1K2Q w - - 1 49",
+    "1r5k/2pq2p1/3p3p/p1pP4/4QP2/PP1R3P/6PK/8 w - - 1 51",
+    "q5k1/5ppp/1r3bn1/1B6/P1N2P2/BQ2P1P1/5K1P/8 b - - 2 34",
+    "r1b2k1r/5n2/p4q2/1ppn1Pp1/3pp1p1/NP2P3/P1PPBK2/1RQN2R1 w - - 0 22",
+    "r1bqk2r/pppp1ppp/5n2/4b3/4P3/P1N5/1PP2PPP/R1BQKB1R w KQkq - 0 5",
+    "r1bqr1k1/pp1p1ppp/2p5/8/3N1Q2/P2BB3/1PP2PPP/R3K2n b Q - 1 12",
+    "r1bq2k1/p4r1p/1pp2pp1/3p4/1P1B3Q/P2B1N2/2P3PP/4R1K1 b - - 2 19",
+    "r4qk1/6r1/1p4p1/2ppBbN1/1p5Q/P7/2P3PP/5RK1 w - - 2 25",
+    "r7/6k1/1p6/2pp1p2/7Q/8/p1P2K1P/8 w - - 0 32",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::uci::{Engine, RecivedMessage, UciInfoAttribute, UciMove, UciSearchControl, UciSquare};
+    use board::{r#move::MoveType, NoDelta};
+    use std::sync::mpsc::channel;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn uci_moves(moves: &str) -> Vec<UciMove> {
+        moves
+            .split_whitespace()
+            .map(|m| {
+                let m = m.as_bytes();
+                UciMove::from_to(
+                    UciSquare::from(m[0] as char, m[1] - b'0'),
+                    UciSquare::from(m[2] as char, m[3] - b'0'),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn node_limit() {
+        let mut engine = GrandChessEngine::new(1000000);
+        engine.set_pos(START_FEN, Vec::new());
+
+        let (_stop_sender, stop_reciver) = channel();
+        let (mut sender, _reciver) = channel();
+        let search_control = UciSearchControl {
+            search_moves: Vec::new(),
+            mate: None,
+            depth: None,
+            nodes: Some(1000),
+        };
+        engine.go(&stop_reciver, &mut sender, None, Some(search_control));
+
+        assert!(engine.node_count >= 1000 && engine.node_count < 1000 + MAX_PLY as u64);
+    }
+
+    #[test]
+    fn repetition_in_tree() {
+        let mut engine = GrandChessEngine::new(1000000);
+        engine.set_pos(START_FEN, Vec::new());
+
+        // Shuffle the knights from the root, at ply 5 we are back at the position from ply 1
+        let mut board = engine.board.clone();
+        for (ply, m) in uci_moves("g1f3 g8f6 f3g1 f6g8 g1f3").into_iter().enumerate() {
+            engine.repetition_table[ply] = board.hash;
+            board.make_move(crate::uci::parse_move(&board, m, false), NoDelta);
+
+            if ply < 4 {
+                assert!(!engine.is_repetition(&board, ply + 1));
+            }
         }
 
-        let (_, to, _, piece, capture) = r#move.unpack();
+        assert!(engine.is_repetition(&board, 5));
+    }
+
+    #[test]
+    fn repetition_in_game_history() {
+        let mut engine = GrandChessEngine::new(1000000);
+
+        // The start position was seen once before, that is not a draw yet
+        engine.set_pos(START_FEN, uci_moves("g1f3 g8f6 f3g1 f6g8"));
+        assert_eq!(engine.game_history.len(), 4);
+        assert!(!engine.is_repetition(&engine.board, 0));
+
+        // Seen twice before, this is the third time on the board
+        engine.set_pos(START_FEN, uci_moves("g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8"));
+        assert!(engine.is_repetition(&engine.board, 0));
+    }
+
+    #[test]
+    fn game_history_cleared_on_irreversible_move() {
+        let mut engine = GrandChessEngine::new(1000000);
+
+        engine.set_pos(START_FEN, uci_moves("g1f3 g8f6"));
+        assert_eq!(engine.game_history.len(), 2);
+
+        engine.set_pos(START_FEN, uci_moves("g1f3 g8f6 e2e4"));
+        assert!(engine.game_history.is_empty());
+
+        engine.set_pos(START_FEN, uci_moves("g1f3 d7d5 b1c3 c8g4 c3d5"));
+        assert!(engine.game_history.is_empty());
+    }
+
+    #[test]
+    fn fifty_move_draw() {
+        let mut engine = GrandChessEngine::new(1000000);
+
+        // Not mated, so the hundredth halfmove is a draw
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap();
+        assert_eq!(engine.neg_max(3, 1, &mut board, MIN_SCORE, MAX_SCORE, Move::null(), None), 0);
+
+        // Mate on the hundredth halfmove still counts
+        let mut board = Board::from_fen("7k/6Q1/5K2/8/8/8/8/8 b - - 100 80").unwrap();
+        assert!(
+            engine.neg_max(3, 1, &mut board, MIN_SCORE, MAX_SCORE, Move::null(), None)
+                <= -MATE_SCORE
+        );
+    }
+
+    #[test]
+    fn tablebase_win() {
+        let mut engine = GrandChessEngine::new(1000000);
+        engine.tablebases = Some(Arc::new(Tablebases::new(syzygy::tests::kqvk_dir())));
+
+        // Taking the rook reaches a table position lost for black
+        let mut board = Board::from_fen("8/8/8/3r4/8/2k5/8/3Q2K1 w - - 0 1").unwrap();
+        engine.repetition_table[0] = board.hash;
+        let before = board.clone();
+        engine.root_moves = RootMoves::new(&board, &[], &[]);
+        let score = engine.search_root(3, &mut board, MIN_SCORE, MAX_SCORE, None, None);
+        assert_eq!(board, before);
+        assert!(score >= TB_WIN_SCORE - MAX_PLY as i32 && score < MATE_SCORE, "{}", score);
+        assert_eq!(engine.pv_table[0][0].to_string(), "d1d5");
+    }
 
-        if capture != PieceType::Empty {
-            (6 - piece.get_type() as u32) + capture as u32 * 100 + 10000
-        } else if self.killer_moves[0][ply] == r#move {
-            9000
-        } else if self.killer_moves[1][ply] == r#move {
-            8000
-        } else {
-            self.history_moves[piece as usize][to]
+    #[test]
+    fn shallow_reductions() {
+        let mut engine = GrandChessEngine::new(1000000);
+        assert!(engine.params.set("LmrMinDepth", Some("1")));
+        assert!(engine.params.set("LmrMinMoves", Some("1")));
+
+        // Late moves are reduced even at depth one, by no more than leaves a ply
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        engine.repetition_table[1] = board.hash;
+        for depth in 1..5 {
+            engine.neg_max(depth, 1, &mut board, MIN_SCORE, MAX_SCORE, Move::null(), None);
         }
+
+        // The root too, from its first iteration
+        engine.set_pos(START_FEN, Vec::new());
+        let (_stop_sender, stop_reciver) = channel();
+        let (mut sender, reciver) = channel();
+        let search_control =
+            UciSearchControl { search_moves: Vec::new(), mate: None, depth: Some(4), nodes: None };
+        engine.go(&stop_reciver, &mut sender, None, Some(search_control));
+        assert!(reciver.try_iter().any(|message| matches!(message, RecivedMessage::BestMove(_))));
+    }
+
+    #[test]
+    fn max_ply() {
+        let mut engine = GrandChessEngine::new(1000000);
+
+        // At the last ply the search stops at the static eval instead of going past the stacks
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let ply = MAX_PLY - 1;
+        engine.nnue.refresh_board(&board, ply);
+        let eval = engine.static_eval(ply, &board);
+        let score = engine.neg_max(4, ply, &mut board, MIN_SCORE, MAX_SCORE, Move::null(), None);
+        assert_eq!(score, eval);
+        assert_eq!(engine.quiescence(ply, &mut board, MIN_SCORE, MAX_SCORE), eval);
     }
 
-    fn score_capture(&self, r#move: Move, best_move: Move) -> u32 {
-        if r#move == best_move {
-            return 100000;
+    #[test]
+    fn excluded_move() {
+        let mut engine = GrandChessEngine::new(1000000);
+
+        // Without taking the rook white is worse off, and the position's entry isn't written
+        let mut board = Board::from_fen("8/8/8/3r4/8/2k5/8/3Q2K1 w - - 0 1").unwrap();
+        let capture = generate_legal_moves(&board)
+            .iter()
+            .copied()
+            .find(|r#move| r#move.to_string() == "d1d5")
+            .unwrap();
+        engine.repetition_table[1] = board.hash;
+        let excluded = engine.neg_max(4, 1, &mut board, MIN_SCORE, MAX_SCORE, capture, None);
+        assert!(engine.tt.probe_entry(&board, board.hash, 1).is_none());
+        let score = engine.neg_max(4, 1, &mut board, MIN_SCORE, MAX_SCORE, Move::null(), None);
+        assert!(score > excluded + 200, "{} {}", score, excluded);
+        assert_eq!(engine.tt.probe_entry(&board, board.hash, 1).unwrap().best_move, capture);
+
+        // With only one legal move, excluding it is no mate
+        let mut board = Board::from_fen("k7/8/8/8/8/8/5r2/6rK w - - 0 1").unwrap();
+        let only = generate_legal_moves(&board)[0];
+        assert_eq!(engine.neg_max(2, 1, &mut board, -50, 50, only, None), -50);
+    }
+
+    #[test]
+    fn root_moves_restrict_search() {
+        let mut engine = GrandChessEngine::new(1000000);
+
+        // Only the quiet king move may be played, even though the rook can be taken
+        let mut board = Board::from_fen("8/8/8/3r4/8/2k5/8/3Q2K1 w - - 0 1").unwrap();
+        let king_move = generate_legal_moves(&board)
+            .iter()
+            .copied()
+            .find(|r#move| r#move.to_string() == "g1h1")
+            .unwrap();
+        engine.root_moves = RootMoves::new(&board, &[king_move], &[]);
+        engine.repetition_table[0] = board.hash;
+        engine.search_root(3, &mut board, MIN_SCORE, MAX_SCORE, None, None);
+        assert_eq!(engine.pv_table[0][0], king_move);
+
+        // Every root move is announced once the search has run long enough
+        let (sender, reciver) = channel();
+        engine.root_moves = RootMoves::new(&board, &[], &[]);
+        let start = Instant::now() - CURRMOVE_DELAY;
+        engine.search_root(2, &mut board, MIN_SCORE, MAX_SCORE, None, Some((&sender, start)));
+        let numbers: Vec<_> = reciver
+            .try_iter()
+            .filter_map(|message| match message {
+                RecivedMessage::Info(info) => match info[..] {
+                    [_, UciInfoAttribute::CurrMove(_), UciInfoAttribute::CurrMoveNum(number)] => {
+                        Some(number as usize)
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        assert_eq!(numbers, (1..=engine.root_moves.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn search_moves() {
+        let mut engine = GrandChessEngine::new(1000000);
+        engine.set_pos(START_FEN, Vec::new());
+
+        let (_stop_sender, stop_reciver) = channel();
+        let (mut sender, reciver) = channel();
+        let search_control = UciSearchControl {
+            search_moves: uci_moves("a2a3 h2h3"),
+            mate: None,
+            depth: Some(4),
+            nodes: None,
+        };
+        engine.go(&stop_reciver, &mut sender, None, Some(search_control));
+
+        let best_move = reciver.try_iter().find_map(|message| match message {
+            RecivedMessage::BestMove(best_move) => Some(best_move),
+            _ => None,
+        });
+        assert!(uci_moves("a2a3 h2h3").contains(&best_move.unwrap()));
+    }
+
+    #[test]
+    fn lazy_smp() {
+        let mut engine = GrandChessEngine::new(1000000);
+        engine.set_option("Threads", Some("4"));
+        assert_eq!(engine.helpers.len(), 3);
+        engine.set_pos(START_FEN, uci_moves("e2e4 e7e5"));
+
+        let (_stop_sender, stop_reciver) = channel();
+        let (mut sender, reciver) = channel();
+        let search_control =
+            UciSearchControl { search_moves: Vec::new(), mate: None, depth: Some(6), nodes: None };
+        engine.go(&stop_reciver, &mut sender, None, Some(search_control));
+
+        // The helpers are stopped with the main thread and handed back
+        assert_eq!(engine.helpers.len(), 3);
+        assert!(engine.helpers.iter().all(|helper| Arc::ptr_eq(&helper.tt, &engine.tt)));
+
+        let best_move = reciver.try_iter().find_map(|message| match message {
+            RecivedMessage::BestMove(best_move) => Some(best_move),
+            _ => None,
+        });
+        let best_move = crate::uci::parse_move(&engine.board, best_move.unwrap(), false);
+        assert!(generate_legal_moves(&engine.board).contains(&best_move));
+
+        engine.set_option("Threads", Some("1"));
+        assert!(engine.helpers.is_empty());
+    }
+
+    #[test]
+    fn multi_pv() {
+        let mut engine = GrandChessEngine::new(1000000);
+        engine.set_option("MultiPV", Some("3"));
+        engine.set_pos(START_FEN, uci_moves("e2e4 e7e5"));
+
+        let (_stop_sender, stop_reciver) = channel();
+        let (mut sender, reciver) = channel();
+        let search_control =
+            UciSearchControl { search_moves: Vec::new(), mate: None, depth: Some(5), nodes: None };
+        engine.go(&stop_reciver, &mut sender, None, Some(search_control));
+
+        let messages: Vec<_> = reciver.try_iter().collect();
+        let best_move = messages.iter().find_map(|message| match message {
+            RecivedMessage::BestMove(best_move) => Some(*best_move),
+            _ => None,
+        });
+
+        // The exact lines of the last iteration, best first and each with another move
+        let lines: Vec<_> = messages
+            .iter()
+            .filter_map(|message| match message {
+                RecivedMessage::Info(info) => Some(info),
+                _ => None,
+            })
+            .filter(|info| info.contains(&UciInfoAttribute::Depth(5)))
+            .filter_map(|info| match info[..] {
+                [_, UciInfoAttribute::MultiPv(line), UciInfoAttribute::Score {
+                    cp: Some(cp),
+                    lower_bound: None,
+                    upper_bound: None,
+                    ..
+                }, .., UciInfoAttribute::Pv(ref pv)] => Some((line, cp, pv[0])),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines.iter().map(|line| line.0).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(lines.windows(2).all(|pair| pair[0].1 >= pair[1].1 && pair[0].2 != pair[1].2));
+        assert_ne!(lines[0].2, lines[2].2);
+        assert_eq!(best_move, Some(lines[0].2));
+
+        // Never more lines than moves
+        engine.set_pos("7k/8/8/8/8/8/6q1/7K w - - 0 1", Vec::new());
+        let search_control =
+            UciSearchControl { search_moves: Vec::new(), mate: None, depth: Some(2), nodes: None };
+        engine.go(&stop_reciver, &mut sender, None, Some(search_control));
+        assert!(reciver.try_iter().all(|message| match message {
+            RecivedMessage::Info(info) => info.contains(&UciInfoAttribute::MultiPv(1)),
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn hash_info() {
+        let mut engine = GrandChessEngine::new(1000000);
+        engine.set_option("HashStats", Some("true"));
+        engine.set_pos(START_FEN, Vec::new());
+
+        let (_stop_sender, stop_reciver) = channel();
+        let (mut sender, reciver) = channel();
+        let search_control =
+            UciSearchControl { search_moves: Vec::new(), mate: None, depth: Some(5), nodes: None };
+        engine.go(&stop_reciver, &mut sender, None, Some(search_control));
+
+        let stats = engine.tt_stats();
+        assert!(stats.hits > 0 && stats.misses > 0);
+
+        let infos: Vec<_> = reciver
+            .try_iter()
+            .filter_map(|message| match message {
+                RecivedMessage::Info(info) => Some(info),
+                _ => None,
+            })
+            .collect();
+        assert!(infos[..infos.len() - 1].iter().all(|info| info
+            .iter()
+            .any(|attribute| matches!(attribute, UciInfoAttribute::HashFull(_)))));
+        assert!(matches!(infos.last().unwrap()[..], [UciInfoAttribute::String(_)]));
+    }
+
+    #[test]
+    fn chess960_uci_moves() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        for (uci_move, chess960, move_type) in [
+            ("e1g1", false, MoveType::KingCastle),
+            ("e1c1", false, MoveType::QueenCastle),
+            ("e1h1", true, MoveType::KingCastle),
+            ("e1a1", true, MoveType::QueenCastle),
+        ] {
+            let uci_move = uci_moves(uci_move).remove(0);
+            let r#move = crate::uci::parse_move(&board, uci_move, chess960);
+            assert_eq!(r#move.move_type(), move_type);
+            assert!(generate_legal_moves(&board).contains(&r#move));
+            assert_eq!(crate::uci::to_uci_move(r#move, chess960), uci_move);
         }
-        (6 - r#move.piece().get_type() as u32) + r#move.captured() as u32 * 100
+
+        // Without Chess960 the king taking its rook is not a castle
+        let r#move = crate::uci::parse_move(&board, uci_moves("e1h1").remove(0), false);
+        assert!(!r#move.is_castle());
+    }
+
+    #[test]
+    fn grand_variant() {
+        let mut engine = GrandChessEngine::new(1000000);
+        engine.set_option("UCI_Variant", Some("grand"));
+        assert!(engine.is_variant());
+        engine.set_variant_pos(None, vec!["e3e5".to_owned(), "j8j6".to_owned()]);
+
+        let (_stop_sender, stop_reciver) = channel();
+        let (mut sender, reciver) = channel();
+        let search_control =
+            UciSearchControl { search_moves: Vec::new(), mate: None, depth: Some(2), nodes: None };
+        engine.go(&stop_reciver, &mut sender, None, Some(search_control));
+
+        let best_move = reciver.try_iter().find_map(|message| match message {
+            RecivedMessage::VariantBestMove(best_move) => Some(best_move),
+            _ => None,
+        });
+        let board = &engine.grand.as_ref().unwrap().board;
+        assert!(board.parse_move(&best_move.unwrap()).is_ok());
+
+        engine.set_option("UCI_Variant", Some("chess"));
+        assert!(!engine.is_variant());
     }
 }
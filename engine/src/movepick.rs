@@ -0,0 +1,314 @@
+use crate::{
+    board::{
+        movegen::{generate_captures, generate_moves, LegalityMasks, Moves},
+        piece::PieceType,
+        r#move::Move,
+        Board,
+    },
+    history::History,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    TTMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// Moves that are only generated in the quiet stage, castling comes with the captures
+pub fn is_quiet(r#move: Move) -> bool {
+    r#move.captured() == PieceType::Empty && !r#move.is_castle()
+}
+
+// Victim first, then the cheapest attacker. Castling captures nothing
+fn mvv_lva(r#move: Move) -> i32 {
+    if r#move.captured() == PieceType::Empty {
+        return 0;
+    }
+    (6 - r#move.piece().get_type() as i32) + r#move.captured() as i32 * 100
+}
+
+// Hands out the legal moves of a position one at a time, generating each group only once the
+// ones before it are used up, so a cutoff by the transposition table move costs no generation
+pub struct MovePicker {
+    stage: Stage,
+    masks: LegalityMasks,
+    // Quiescence only wants the captures that don't lose material
+    captures_only: bool,
+
+    tt_move: Move,
+    killers: [Move; 2],
+    // The moves one and two plies back
+    previous: [Move; 2],
+    counter_move: Move,
+
+    moves: Moves,
+    scores: [i32; 256],
+    index: usize,
+    bad_captures: Moves,
+}
+
+impl MovePicker {
+    // `tt_move` has to be pseudo-legal here or null
+    pub fn new(board: &Board, tt_move: Move, killers: [Move; 2], previous: [Move; 2]) -> Self {
+        Self {
+            stage: if tt_move == Move::null() { Stage::GenerateCaptures } else { Stage::TTMove },
+            masks: LegalityMasks::new(board),
+            captures_only: false,
+            tt_move,
+            killers,
+            previous,
+            counter_move: Move::null(),
+            moves: Moves::default(),
+            scores: [0; 256],
+            index: 0,
+            bad_captures: Moves::default(),
+        }
+    }
+
+    pub fn captures(board: &Board) -> Self {
+        Self {
+            stage: Stage::GenerateCaptures,
+            captures_only: true,
+            ..Self::new(board, Move::null(), [Move::null(); 2], [Move::null(); 2])
+        }
+    }
+
+    pub fn next(&mut self, board: &Board, history: &History) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TTMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if self.masks.is_legal(board, self.tt_move) {
+                        return Some(self.tt_move);
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    self.moves = generate_captures(board);
+                    for i in 0..self.moves.len() {
+                        self.scores[i] = mvv_lva(self.moves[i]);
+                    }
+                    self.index = 0;
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => match self.pick_best() {
+                    Some(r#move) if r#move == self.tt_move => {}
+                    Some(r#move) if !board.see_ge(r#move, 0) => {
+                        if !self.captures_only {
+                            self.bad_captures.push(r#move);
+                        }
+                    }
+                    Some(r#move) => {
+                        if self.masks.is_legal(board, r#move) {
+                            return Some(r#move);
+                        }
+                    }
+                    None => {
+                        self.stage = if self.captures_only { Stage::Done } else { Stage::Killers };
+                        self.index = 0;
+                    }
+                },
+                Stage::Killers => {
+                    if self.index == self.killers.len() {
+                        self.stage = Stage::CounterMove;
+                        continue;
+                    }
+
+                    let killer = self.killers[self.index];
+                    self.index += 1;
+                    if (self.index == 1 || killer != self.killers[0])
+                        && self.is_refutation(board, killer)
+                    {
+                        return Some(killer);
+                    }
+                }
+                Stage::CounterMove => {
+                    self.stage = Stage::GenerateQuiets;
+                    self.counter_move = history.counter_move(self.previous[0]);
+                    if !self.killers.contains(&self.counter_move)
+                        && self.is_refutation(board, self.counter_move)
+                    {
+                        return Some(self.counter_move);
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.moves = generate_moves(board);
+                    self.moves.retain(is_quiet);
+                    for i in 0..self.moves.len() {
+                        self.scores[i] =
+                            history.quiet_score(board.current_color, self.moves[i], self.previous);
+                    }
+                    self.index = 0;
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.pick_best() {
+                    Some(r#move)
+                        if r#move == self.tt_move
+                            || self.killers.contains(&r#move)
+                            || r#move == self.counter_move => {}
+                    Some(r#move) => {
+                        if self.masks.is_legal(board, r#move) {
+                            return Some(r#move);
+                        }
+                    }
+                    None => {
+                        self.stage = Stage::BadCaptures;
+                        self.index = 0;
+                    }
+                },
+                Stage::BadCaptures => {
+                    if self.index == self.bad_captures.len() {
+                        self.stage = Stage::Done;
+                        continue;
+                    }
+
+                    let r#move = self.bad_captures[self.index];
+                    self.index += 1;
+                    if self.masks.is_legal(board, r#move) {
+                        return Some(r#move);
+                    }
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    // Killers and counter moves come from other positions, so they are only tried if they could
+    // have been generated here
+    fn is_refutation(&self, board: &Board, r#move: Move) -> bool {
+        r#move != Move::null()
+            && r#move != self.tt_move
+            && is_quiet(r#move)
+            && board.is_pseudo_legal(r#move)
+            && self.masks.is_legal(board, r#move)
+    }
+
+    // Swaps the best scored of the remaining moves to the front, the moves are only ever partly
+    // sorted as the search asks for them
+    fn pick_best(&mut self) -> Option<Move> {
+        if self.index == self.moves.len() {
+            return None;
+        }
+
+        let mut best = self.index;
+        for i in self.index + 1..self.moves.len() {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+
+        self.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+        self.index += 1;
+        Some(self.moves[self.index - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{
+        movegen::generate_legal_moves,
+        piece::{Piece, PieceColor},
+        r#move::MoveType,
+    };
+
+    use super::*;
+
+    fn picked(board: &Board, history: &History, mut picker: MovePicker) -> Vec<Move> {
+        std::iter::from_fn(|| picker.next(board, history)).collect()
+    }
+
+    fn find(board: &Board, uci: &str) -> Move {
+        generate_legal_moves(board)
+            .iter()
+            .copied()
+            .find(|r#move| r#move.to_string() == uci)
+            .unwrap()
+    }
+
+    #[test]
+    fn move_picker() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let legal = generate_legal_moves(&board);
+            let tt_move = legal[legal.len() / 2];
+            let killers = [legal[0], legal[legal.len() - 1]];
+
+            // Every legal move exactly once, whatever the hints
+            let mut history = History::default();
+            let previous = [
+                Move::new(52, 36, MoveType::QuietMove, Piece::BlackPawn, PieceType::Empty),
+                Move::null(),
+            ];
+            history.update_quiets(board.current_color, legal[1], &[], previous, 3);
+
+            let picker = MovePicker::new(&board, tt_move, killers, previous);
+            let mut moves = picked(&board, &history, picker);
+            assert_eq!(moves[0], tt_move, "{}", fen);
+            assert_eq!(moves.len(), legal.len(), "{}", fen);
+            moves.sort_by_key(|r#move| r#move.0);
+            moves.dedup();
+            assert_eq!(moves.len(), legal.len(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn move_picker_order() {
+        let board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let killer = find(&board, "a2a3");
+        let previous = [
+            Move::new(48, 40, MoveType::QuietMove, Piece::BlackPawn, PieceType::Empty),
+            Move::null(),
+        ];
+
+        // The counter move, then a quiet move that did well elsewhere, then one that failed
+        let mut history = History::default();
+        history.update_quiets(PieceColor::White, find(&board, "b2b3"), &[], previous, 1);
+        history.update_quiets(
+            PieceColor::White,
+            find(&board, "a1b1"),
+            &[find(&board, "a1c1")],
+            [Move::null(); 2],
+            2,
+        );
+
+        let picker = MovePicker::new(&board, Move::null(), [killer, Move::null()], previous);
+        let moves = picked(&board, &history, picker);
+
+        // Winning captures, then the killer and the counter move ahead of the other quiets by
+        // their history, and the captures that lose material last
+        let position = |uci: &str| moves.iter().position(|r#move| r#move.to_string() == uci);
+        assert!(position("g2h3") < position("a2a3"));
+        assert_eq!(position("a2a3").unwrap() + 1, position("b2b3").unwrap());
+        assert_eq!(position("b2b3").unwrap() + 1, position("a1b1").unwrap());
+        assert!(position("a1b1") < position("g2g3"));
+        assert!(position("g2g3") < position("a1c1"));
+        assert!(position("a1c1") < position("f3f6"));
+        let first_bad = moves
+            .iter()
+            .position(|r#move| !is_quiet(*r#move) && !board.see_ge(*r#move, 0))
+            .unwrap();
+        assert!(moves[first_bad..]
+            .iter()
+            .all(|r#move| !is_quiet(*r#move) && !board.see_ge(*r#move, 0)));
+
+        // Quiescence drops the losing captures
+        let captures = picked(&board, &history, MovePicker::captures(&board));
+        assert!(captures.contains(&find(&board, "g2h3")));
+        assert!(!captures.contains(&find(&board, "f3f6")));
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use crate::board::{piece::PieceColor, r#move::Move, Board, PiecesDelta};
+use crate::board::{piece::PieceColor, r#move::Move, Board, PiecesDelta, UndoInfo};
 use byteorder::{LittleEndian, ReadBytesExt};
 
 use self::feature_transformer::{Accumulator, FeatureTransformer};
@@ -156,16 +156,19 @@ impl<NET: Network, SET: FeatureSet, const STACK_SIZE: usize> Nnue<NET, SET, STAC
         self.transformer.refresh(&mut self.acc_stack[ply], &features, PieceColor::Black);
     }
 
-    pub fn make_null_move(&mut self, board: &mut Board, ply: usize) {
-        board.make_null_move();
+    pub fn make_null_move(&mut self, board: &mut Board, ply: usize) -> UndoInfo {
+        let undo = board.make_null_move();
 
         let split = self.acc_stack.split_at_mut(ply + 1);
         split.1[0].accumulators.copy_from_slice(&split.0[ply].accumulators);
+
+        undo
     }
 
-    pub fn make_move(&mut self, r#move: Move, board: &mut Board, ply: usize) {
+    // The accumulators of `ply` are kept, so taking the move back only needs the board
+    pub fn make_move(&mut self, r#move: Move, board: &mut Board, ply: usize) -> UndoInfo {
         let mut delta = PiecesDelta::new();
-        board.make_move(r#move, &mut delta);
+        let undo = board.make_move(r#move, &mut delta);
 
         let needs_refresh = SET::needs_refresh(r#move);
 
@@ -220,6 +223,8 @@ impl<NET: Network, SET: FeatureSet, const STACK_SIZE: usize> Nnue<NET, SET, STAC
                 PieceColor::Black,
             );
         }
+
+        undo
     }
 
     pub fn eval(&mut self, ply: usize, side: PieceColor) -> i32 {
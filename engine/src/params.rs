@@ -0,0 +1,224 @@
+use uci::UciOptionConfig;
+
+// Every parameter with the range a tuner may move it in, each one is also a spin option
+const RANGES: [(&str, i64, i64); 20] = [
+    ("LmrBase", 0, 300),
+    ("LmrDivisor", 100, 500),
+    ("LmrHistoryDivisor", 1024, 65536),
+    ("LmrMinDepth", 1, 10),
+    ("LmrMinMoves", 1, 10),
+    ("RfpMaxDepth", 1, 16),
+    ("RfpMargin", 20, 300),
+    ("RazorMaxDepth", 1, 8),
+    ("RazorMargin", 50, 800),
+    ("FutilityMaxDepth", 1, 16),
+    ("FutilityBase", 0, 400),
+    ("FutilityMargin", 20, 300),
+    ("LmpMaxDepth", 1, 16),
+    ("LmpBase", 1, 20),
+    ("NullBase", 1, 6),
+    ("NullDepthDivisor", 1, 8),
+    ("NullEvalDivisor", 50, 800),
+    ("NullVerifyDepth", 4, 32),
+    ("SingularMinDepth", 4, 16),
+    ("SingularMargin", 1, 8),
+];
+
+// Search parameters that can be tuned with SPSA through the UCI options
+#[derive(Clone)]
+pub struct SearchParams {
+    // Late move reductions grow with the logarithms of the depth and the move number, both in
+    // hundredths of a ply
+    pub lmr_base: i32,
+    pub lmr_divisor: i32,
+    // Each this much of quiet history reduces one ply less, or one more when it is negative
+    pub lmr_history_divisor: i32,
+    pub lmr_min_depth: i32,
+    // Moves searched at a node before later ones are reduced
+    pub lmr_min_moves: i32,
+
+    // Reverse futility pruning, a node whose static eval beats beta by this much per ply of depth
+    // is cut without searching
+    pub rfp_max_depth: i32,
+    pub rfp_margin: i32,
+    // Razoring, a node this far per ply below alpha only gets a quiescence search
+    pub razor_max_depth: i32,
+    pub razor_margin: i32,
+    // Futility pruning, quiet moves are skipped when the static eval plus this can't reach alpha
+    pub futility_max_depth: i32,
+    pub futility_base: i32,
+    pub futility_margin: i32,
+    // Late move pruning, quiet moves after the first `lmp_base + depth * depth` are skipped, half
+    // as many if the position is not improving
+    pub lmp_max_depth: i32,
+    pub lmp_base: i32,
+    // Null move pruning, reduced by the base plus a ply per this much depth and per this much the
+    // static eval beats beta, at most three of the latter
+    pub null_base: i32,
+    pub null_depth_divisor: i32,
+    pub null_eval_divisor: i32,
+    // A null move cutoff this deep is only trusted once a search without null moves confirms it
+    pub null_verify_depth: i32,
+    // Singular extensions, the table move is extended if no other move reaches its score minus
+    // this much per ply in a search of half the depth
+    pub singular_min_depth: i32,
+    pub singular_margin: i32,
+
+    // Indexed by depth and move number
+    reductions: [[i32; 64]; 64],
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        let mut params = Self {
+            lmr_base: 75,
+            lmr_divisor: 225,
+            lmr_history_divisor: 8192,
+            lmr_min_depth: 3,
+            lmr_min_moves: 3,
+            rfp_max_depth: 8,
+            rfp_margin: 80,
+            razor_max_depth: 3,
+            razor_margin: 250,
+            futility_max_depth: 6,
+            futility_base: 100,
+            futility_margin: 100,
+            lmp_max_depth: 8,
+            lmp_base: 3,
+            null_base: 3,
+            null_depth_divisor: 3,
+            null_eval_divisor: 200,
+            null_verify_depth: 12,
+            singular_min_depth: 7,
+            singular_margin: 2,
+            reductions: [[0; 64]; 64],
+        };
+        params.update_reductions();
+        params
+    }
+}
+
+impl SearchParams {
+    pub fn options() -> Vec<UciOptionConfig> {
+        let mut params = Self::default();
+        RANGES
+            .iter()
+            .map(|&(name, min, max)| UciOptionConfig::Spin {
+                name: name.to_owned(),
+                default: params.value_mut(name).map(|value| *value as i64),
+                min: Some(min),
+                max: Some(max),
+            })
+            .collect()
+    }
+
+    // False if `name` is not a search parameter
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> bool {
+        let Some(&(_, min, max)) = RANGES.iter().find(|(param, _, _)| *param == name) else {
+            return false;
+        };
+
+        match value.unwrap_or_default().parse::<i64>() {
+            Ok(value) => {
+                *self.value_mut(name).unwrap() = value.clamp(min, max) as i32;
+                self.update_reductions();
+            }
+            Err(e) => eprintln!("could not parse option due to error: {}", e),
+        }
+        true
+    }
+
+    fn value_mut(&mut self, name: &str) -> Option<&mut i32> {
+        match name {
+            "LmrBase" => Some(&mut self.lmr_base),
+            "LmrDivisor" => Some(&mut self.lmr_divisor),
+            "LmrHistoryDivisor" => Some(&mut self.lmr_history_divisor),
+            "LmrMinDepth" => Some(&mut self.lmr_min_depth),
+            "LmrMinMoves" => Some(&mut self.lmr_min_moves),
+            "RfpMaxDepth" => Some(&mut self.rfp_max_depth),
+            "RfpMargin" => Some(&mut self.rfp_margin),
+            "RazorMaxDepth" => Some(&mut self.razor_max_depth),
+            "RazorMargin" => Some(&mut self.razor_margin),
+            "FutilityMaxDepth" => Some(&mut self.futility_max_depth),
+            "FutilityBase" => Some(&mut self.futility_base),
+            "FutilityMargin" => Some(&mut self.futility_margin),
+            "LmpMaxDepth" => Some(&mut self.lmp_max_depth),
+            "LmpBase" => Some(&mut self.lmp_base),
+            "NullBase" => Some(&mut self.null_base),
+            "NullDepthDivisor" => Some(&mut self.null_depth_divisor),
+            "NullEvalDivisor" => Some(&mut self.null_eval_divisor),
+            "NullVerifyDepth" => Some(&mut self.null_verify_depth),
+            "SingularMinDepth" => Some(&mut self.singular_min_depth),
+            "SingularMargin" => Some(&mut self.singular_margin),
+            _ => None,
+        }
+    }
+
+    fn update_reductions(&mut self) {
+        for depth in 1..64 {
+            for moves in 1..64 {
+                let reduction = self.lmr_base as f64 / 100.
+                    + (depth as f64).ln() * (moves as f64).ln() / (self.lmr_divisor as f64 / 100.);
+                self.reductions[depth][moves] = reduction as i32;
+            }
+        }
+    }
+
+    // Quiet moves searched at a node before the rest are pruned
+    pub fn lmp_count(&self, depth: i32, improving: bool) -> usize {
+        ((self.lmp_base + depth * depth) / (2 - improving as i32)) as usize
+    }
+
+    // `eval_margin` is how far the static eval is above beta
+    pub fn null_reduction(&self, depth: i32, eval_margin: i32) -> i32 {
+        self.null_base
+            + depth / self.null_depth_divisor
+            + (eval_margin / self.null_eval_divisor).clamp(0, 3)
+    }
+
+    // In whole plies, before the adjustments for the node and the move
+    pub fn reduction(&self, depth: i32, moves_searched: usize) -> i32 {
+        self.reductions[(depth as usize).min(63)][moves_searched.min(63)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_params() {
+        let mut params = SearchParams::default();
+        assert_eq!(params.reduction(1, 10), 0);
+        assert_eq!(params.reduction(3, 3), 1);
+        assert!(params.reduction(20, 40) > params.reduction(10, 40));
+        assert!(params.reduction(20, 40) > params.reduction(20, 10));
+        assert_eq!(params.reduction(200, 200), params.reduction(63, 63));
+
+        let reduction = params.reduction(10, 20);
+        assert!(params.set("LmrDivisor", Some("100")));
+        assert!(params.reduction(10, 20) > reduction);
+        assert_eq!(params.lmr_divisor, 100);
+
+        // Clamped to the range
+        assert!(params.set("LmrMinDepth", Some("50")));
+        assert_eq!(params.lmr_min_depth, 10);
+
+        assert!(!params.set("Hash", Some("16")));
+
+        assert!(params.lmp_count(3, true) > params.lmp_count(3, false));
+        assert!(params.lmp_count(4, false) > params.lmp_count(3, false));
+
+        assert_eq!(params.null_reduction(3, 0), 4);
+        assert_eq!(params.null_reduction(12, 10000), 10);
+        assert!(params.null_reduction(9, 400) > params.null_reduction(9, 100));
+
+        let options = SearchParams::options();
+        assert_eq!(options.len(), RANGES.len());
+        assert!(options.iter().all(|option| matches!(
+            option,
+            UciOptionConfig::Spin { default: Some(_), min: Some(min), max: Some(max), .. }
+                if min < max
+        )));
+    }
+}
@@ -0,0 +1,736 @@
+use std::fmt::{self, Write};
+
+use crate::board::{piece::PieceColor, r#move::Move, Board, FenError, NoDelta, ParseMoveError};
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+const LINE_WIDTH: usize = 79;
+
+#[derive(Debug)]
+pub enum PgnErrorKind {
+    UnexpectedChar(char),
+    UnterminatedString,
+    UnterminatedComment,
+    UnexpectedToken(String),
+    UnclosedVariation,
+    InvalidFen(FenError),
+    InvalidMove(String, ParseMoveError),
+}
+
+#[derive(Debug)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {:?}", self.line, self.column, self.kind)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnMove {
+    pub r#move: Move,
+    pub nags: Vec<u8>,
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    // Alternatives to this move, each starting from the position before it
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(r#move: Move) -> Self {
+        Self {
+            r#move,
+            nags: Vec::new(),
+            comment_before: None,
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub start: Board,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(Board::default())
+    }
+}
+
+impl Game {
+    pub fn new(start: Board) -> Self {
+        Self { tags: Vec::new(), start, moves: Vec::new(), result: "*".to_owned() }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some(tag) => tag.1 = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    // Every position of the main line, starting with the initial one
+    pub fn boards(&self) -> Vec<Board> {
+        let mut boards = vec![self.start.clone()];
+        for pgn_move in &self.moves {
+            let mut board = boards.last().unwrap().clone();
+            board.make_move(pgn_move.r#move, NoDelta);
+            boards.push(board);
+        }
+        boards
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+
+        for (name, default) in SEVEN_TAG_ROSTER {
+            let value = if name == "Result" { Some(self.result.as_str()) } else { self.tag(name) };
+            write_tag(&mut pgn, name, value.unwrap_or(default));
+        }
+
+        // The setup tags always describe `start`, whatever is stored in `tags`
+        let fen = self.start.to_fen();
+        if fen != Board::default().to_fen() {
+            write_tag(&mut pgn, "SetUp", "1");
+            write_tag(&mut pgn, "FEN", &fen);
+        }
+
+        for (name, value) in &self.tags {
+            if SEVEN_TAG_ROSTER.iter().all(|(roster, _)| roster != name)
+                && name != "SetUp"
+                && name != "FEN"
+            {
+                write_tag(&mut pgn, name, value);
+            }
+        }
+        pgn.push('\n');
+
+        let mut tokens = movetext_tokens(&self.start, &self.moves);
+        tokens.push(self.result.clone());
+
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len != 0 && line_len + 1 + token.len() > LINE_WIDTH {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len != 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            pgn.push_str(&token);
+
+            // A rest of line comment takes the line with it
+            if token.starts_with(';') {
+                pgn.push('\n');
+                line_len = 0;
+            }
+        }
+        pgn.push_str("\n\n");
+
+        pgn
+    }
+}
+
+fn write_tag(pgn: &mut String, name: &str, value: &str) {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    _ = writeln!(pgn, "[{} \"{}\"]", name, value);
+}
+
+// One token per word so long comments can be wrapped. A `}` would end the comment early, so such
+// comments are written as rest of line comments instead, as many as keep the lines short. The
+// parser joins consecutive comments again
+fn comment_tokens(comment: &str) -> Vec<String> {
+    let words = comment.split_whitespace();
+    if comment.contains('}') {
+        let mut lines: Vec<String> = Vec::new();
+        for word in words {
+            match lines.last_mut() {
+                // One column is left for a variation's `(`
+                Some(line) if line.len() + 1 + word.len() < LINE_WIDTH => {
+                    line.push(' ');
+                    line.push_str(word);
+                }
+                _ => lines.push(format!(";{}", word)),
+            }
+        }
+        return lines;
+    }
+
+    let mut tokens: Vec<String> = words.map(str::to_owned).collect();
+    if tokens.is_empty() {
+        tokens.push(String::new());
+    }
+    tokens[0].insert(0, '{');
+    tokens.last_mut().unwrap().push('}');
+
+    tokens
+}
+
+fn movetext_tokens(start: &Board, moves: &[PgnMove]) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut board = start.clone();
+    let mut needs_number = true;
+
+    for pgn_move in moves {
+        if let Some(comment) = &pgn_move.comment_before {
+            tokens.extend(comment_tokens(comment));
+            needs_number = true;
+        }
+
+        if board.current_color == PieceColor::White {
+            tokens.push(format!("{}.", board.fullmove_number));
+        } else if needs_number {
+            tokens.push(format!("{}...", board.fullmove_number));
+        }
+        needs_number = false;
+
+        tokens.push(pgn_move.r#move.to_san(&board));
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${}", nag)));
+
+        if let Some(comment) = &pgn_move.comment {
+            tokens.extend(comment_tokens(comment));
+            needs_number = true;
+        }
+
+        for variation in &pgn_move.variations {
+            let mut variation = movetext_tokens(&board, variation);
+            if let Some(first) = variation.first_mut() {
+                first.insert(0, '(');
+            }
+            match variation.last_mut() {
+                Some(last) if !last.starts_with(';') => last.push(')'),
+                _ => variation.push(")".to_owned()),
+            }
+            tokens.extend(variation);
+            needs_number = true;
+        }
+
+        board.make_move(pgn_move.r#move, NoDelta);
+    }
+
+    tokens
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    TagOpen,
+    TagClose,
+    VariationOpen,
+    VariationClose,
+    Period,
+    Str(String),
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { chars: text.chars().peekable(), line: 1, column: 1 }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, line: usize, column: usize, kind: PgnErrorKind) -> PgnError {
+        PgnError { line, column, kind }
+    }
+
+    fn tokenize(mut self) -> Result<Vec<(Token, usize, usize)>, PgnError> {
+        let mut tokens = Vec::new();
+
+        while let Some(&c) = self.chars.peek() {
+            let (line, column) = (self.line, self.column);
+
+            let token = match c {
+                _ if c.is_whitespace() => {
+                    self.bump();
+                    continue;
+                }
+                // Escaped lines start with '%' in the first column
+                '%' if column == 1 => {
+                    while self.bump().is_some_and(|c| c != '\n') {}
+                    continue;
+                }
+                ';' => {
+                    self.bump();
+                    let mut comment = String::new();
+                    while let Some(c) = self.bump() {
+                        if c == '\n' {
+                            break;
+                        }
+                        comment.push(c);
+                    }
+                    Token::Comment(comment.trim().to_owned())
+                }
+                '{' => {
+                    self.bump();
+                    let mut comment = String::new();
+                    loop {
+                        match self.bump() {
+                            Some('}') => break,
+                            Some(c) => comment.push(c),
+                            None => {
+                                return Err(self.error(
+                                    line,
+                                    column,
+                                    PgnErrorKind::UnterminatedComment,
+                                ))
+                            }
+                        }
+                    }
+                    Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+                '"' => {
+                    self.bump();
+                    let mut string = String::new();
+                    loop {
+                        match self.bump() {
+                            Some('"') => break,
+                            Some('\\') => match self.bump() {
+                                Some(c) => string.push(c),
+                                None => {
+                                    return Err(self.error(
+                                        line,
+                                        column,
+                                        PgnErrorKind::UnterminatedString,
+                                    ))
+                                }
+                            },
+                            Some('\n') | None => {
+                                return Err(self.error(
+                                    line,
+                                    column,
+                                    PgnErrorKind::UnterminatedString,
+                                ))
+                            }
+                            Some(c) => string.push(c),
+                        }
+                    }
+                    Token::Str(string)
+                }
+                '[' | ']' | '(' | ')' | '.' => {
+                    self.bump();
+                    match c {
+                        '[' => Token::TagOpen,
+                        ']' => Token::TagClose,
+                        '(' => Token::VariationOpen,
+                        ')' => Token::VariationClose,
+                        _ => Token::Period,
+                    }
+                }
+                '*' => {
+                    self.bump();
+                    Token::Symbol("*".to_owned())
+                }
+                '$' => {
+                    self.bump();
+                    let mut nag = String::new();
+                    while let Some(&c) = self.chars.peek().filter(|c| c.is_ascii_digit()) {
+                        nag.push(c);
+                        self.bump();
+                    }
+                    Token::Nag(nag.parse().map_err(|_| {
+                        self.error(line, column, PgnErrorKind::UnexpectedToken(format!("${}", nag)))
+                    })?)
+                }
+                // Suffix annotations are shorthands for the first six NAGs
+                '!' | '?' => {
+                    let mut suffix = String::new();
+                    while let Some(&c) = self.chars.peek().filter(|&&c| c == '!' || c == '?') {
+                        suffix.push(c);
+                        self.bump();
+                    }
+                    Token::Nag(match suffix.as_str() {
+                        "!" => 1,
+                        "?" => 2,
+                        "!!" => 3,
+                        "??" => 4,
+                        "!?" => 5,
+                        "?!" => 6,
+                        _ => {
+                            return Err(self.error(
+                                line,
+                                column,
+                                PgnErrorKind::UnexpectedToken(suffix),
+                            ))
+                        }
+                    })
+                }
+                _ if c.is_ascii_alphanumeric() => {
+                    // Move numbers start with a digit and end at the period, SAN may carry
+                    // an "e.p." suffix
+                    let allow_period = c.is_ascii_alphabetic();
+                    let mut symbol = String::new();
+                    while let Some(&c) = self.chars.peek().filter(|&&c| {
+                        c.is_ascii_alphanumeric()
+                            || "_+#=:-/".contains(c)
+                            || (allow_period && c == '.')
+                    }) {
+                        symbol.push(c);
+                        self.bump();
+                    }
+                    Token::Symbol(symbol)
+                }
+                _ => return Err(self.error(line, column, PgnErrorKind::UnexpectedChar(c))),
+            };
+
+            tokens.push((token, line, column));
+        }
+
+        Ok(tokens)
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    index: usize,
+    // Position reported for errors at the end of the input
+    end: (usize, usize),
+}
+
+fn is_result(symbol: &str) -> bool {
+    matches!(symbol, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _, _)| token)
+    }
+
+    fn position(&self) -> (usize, usize) {
+        self.tokens.get(self.index).map(|&(_, line, column)| (line, column)).unwrap_or(self.end)
+    }
+
+    fn error(&self, kind: PgnErrorKind) -> PgnError {
+        let (line, column) = self.position();
+        PgnError { line, column, kind }
+    }
+
+    fn unexpected(&self) -> PgnError {
+        let token = self.peek().map(|token| format!("{:?}", token)).unwrap_or_default();
+        self.error(PgnErrorKind::UnexpectedToken(token))
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), PgnError> {
+        if self.peek() == Some(&expected) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn parse_game(&mut self) -> Result<Game, PgnError> {
+        let mut tags = Vec::new();
+
+        while self.peek() == Some(&Token::TagOpen) {
+            self.index += 1;
+
+            let name = match self.peek() {
+                Some(Token::Symbol(name)) => name.clone(),
+                _ => return Err(self.unexpected()),
+            };
+            self.index += 1;
+
+            let value = match self.peek() {
+                Some(Token::Str(value)) => value.clone(),
+                _ => return Err(self.unexpected()),
+            };
+
+            if name == "FEN" {
+                Board::from_fen(&value).map_err(|e| self.error(PgnErrorKind::InvalidFen(e)))?;
+            }
+            self.index += 1;
+
+            self.expect(Token::TagClose)?;
+            tags.push((name, value));
+        }
+
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Board::from_fen(fen).unwrap(),
+            None => Board::default(),
+        };
+
+        let mut game = Game::new(start);
+        game.moves = self.parse_line(&game.start, false)?;
+
+        if let Some(Token::Symbol(symbol)) = self.peek() {
+            if is_result(symbol) {
+                game.result = symbol.clone();
+                self.index += 1;
+            }
+        } else if let Some((_, result)) = tags.iter().find(|(name, _)| name == "Result") {
+            game.result = result.clone();
+        }
+        game.tags = tags;
+
+        Ok(game)
+    }
+
+    // Stops in front of the game result, the next tag section or the closing parenthesis
+    fn parse_line(&mut self, start: &Board, in_variation: bool) -> Result<Vec<PgnMove>, PgnError> {
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut board = start.clone();
+        let mut previous = start.clone();
+        let mut comment_before: Option<String> = None;
+
+        loop {
+            match self.peek() {
+                Some(Token::Symbol(symbol)) if is_result(symbol) => {
+                    if in_variation {
+                        return Err(self.error(PgnErrorKind::UnclosedVariation));
+                    }
+                    break;
+                }
+                Some(Token::Symbol(symbol)) if symbol.chars().all(|c| c.is_ascii_digit()) => {
+                    self.index += 1;
+                }
+                Some(Token::Symbol(symbol)) => {
+                    let r#move = board
+                        .parse_san(symbol)
+                        .map_err(|e| self.error(PgnErrorKind::InvalidMove(symbol.clone(), e)))?;
+
+                    let mut pgn_move = PgnMove::new(r#move);
+                    pgn_move.comment_before = comment_before.take();
+                    moves.push(pgn_move);
+
+                    previous = board.clone();
+                    board.make_move(r#move, NoDelta);
+                    self.index += 1;
+                }
+                Some(Token::Period) => {
+                    self.index += 1;
+                }
+                Some(&Token::Nag(nag)) => {
+                    match moves.last_mut() {
+                        Some(last) => last.nags.push(nag),
+                        None => return Err(self.unexpected()),
+                    }
+                    self.index += 1;
+                }
+                Some(Token::Comment(comment)) => {
+                    let target = match moves.last_mut() {
+                        Some(last) => &mut last.comment,
+                        None => &mut comment_before,
+                    };
+                    match target {
+                        Some(existing) => {
+                            existing.push(' ');
+                            existing.push_str(comment);
+                        }
+                        None => *target = Some(comment.clone()),
+                    }
+                    self.index += 1;
+                }
+                Some(Token::VariationOpen) => {
+                    if moves.is_empty() {
+                        return Err(self.unexpected());
+                    }
+                    self.index += 1;
+
+                    let variation = self.parse_line(&previous, true)?;
+                    self.expect(Token::VariationClose)?;
+                    moves.last_mut().unwrap().variations.push(variation);
+                }
+                Some(Token::VariationClose) if in_variation => break,
+                Some(Token::TagOpen) | None if !in_variation => break,
+                None => return Err(self.error(PgnErrorKind::UnclosedVariation)),
+                Some(_) => return Err(self.unexpected()),
+            }
+        }
+
+        Ok(moves)
+    }
+}
+
+pub fn parse_pgn(text: &str) -> Result<Vec<Game>, PgnError> {
+    let lexer = Lexer::new(text);
+    let end = text.lines().enumerate().last().map_or((1, 1), |(i, line)| (i + 1, line.len() + 1));
+
+    let mut parser = Parser { tokens: lexer.tokenize()?, index: 0, end };
+
+    let mut games = Vec::new();
+    while parser.peek().is_some() {
+        games.push(parser.parse_game()?);
+    }
+
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+
+[Event "Casual"]
+[FEN "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2"]
+
+; a line comment
+{Before the first move} 2. exd6e.p.! $14 (2. Kd2 Kd7 (2... Ke7 3. Kd3?!) 3. Kd3)
+2... Kd7 *
+"#;
+
+    #[test]
+    fn parse_games() {
+        let games = parse_pgn(GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let fischer = &games[0];
+        assert_eq!(fischer.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(fischer.result, "1/2-1/2");
+        assert_eq!(fischer.moves.len(), 85);
+        assert_eq!(
+            fischer.moves[4].comment.as_deref(),
+            Some("This opening is called the Ruy Lopez.")
+        );
+        assert_eq!(
+            fischer.boards().last().unwrap().to_fen(),
+            "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43"
+        );
+
+        let casual = &games[1];
+        assert_eq!(casual.result, "*");
+        assert_eq!(casual.moves.len(), 2);
+        assert_eq!(
+            casual.moves[0].comment_before.as_deref(),
+            Some("a line comment Before the first move")
+        );
+        assert_eq!(casual.moves[0].nags, vec![1, 14]);
+        assert_eq!(casual.moves[0].variations.len(), 1);
+
+        let variation = &casual.moves[0].variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[1].variations[0].len(), 2);
+        assert_eq!(variation[1].variations[0][1].nags, vec![6]);
+    }
+
+    #[test]
+    fn write_and_reparse() {
+        for game in parse_pgn(GAMES).unwrap() {
+            let pgn = game.to_pgn();
+            assert!(pgn.lines().all(|line| line.len() <= LINE_WIDTH));
+
+            let reparsed = parse_pgn(&pgn).unwrap();
+            assert_eq!(reparsed.len(), 1);
+            assert_eq!(reparsed[0].to_pgn(), pgn);
+            assert_eq!(
+                reparsed[0].boards().last().unwrap().to_fen(),
+                game.boards().last().unwrap().to_fen()
+            );
+        }
+
+        let pgn = Game::default().to_pgn();
+        assert!(pgn.starts_with("[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n"));
+        assert!(pgn.ends_with("[Result \"*\"]\n\n*\n\n"));
+    }
+
+    #[test]
+    fn write_custom_start_and_comments() {
+        let start = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let mut game = Game::new(start.clone());
+
+        let mut first = PgnMove::new(start.parse_san("exd6").unwrap());
+        first.comment = Some(format!("a {{nested}} comment {}", "word ".repeat(40)));
+        game.moves.push(first);
+
+        let mut board = start.clone();
+        board.make_move(game.moves[0].r#move, NoDelta);
+        game.moves.push(PgnMove::new(board.parse_san("Kd7").unwrap()));
+
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2\"]\n"));
+        assert!(pgn.lines().all(|line| line.len() <= LINE_WIDTH));
+
+        let reparsed = parse_pgn(&pgn).unwrap();
+        assert_eq!(reparsed[0].start.to_fen(), start.to_fen());
+        assert_eq!(reparsed[0].moves.len(), 2);
+        let comment = game.moves[0].comment.as_deref().unwrap().trim();
+        assert_eq!(reparsed[0].moves[0].comment.as_deref(), Some(comment));
+        assert_eq!(reparsed[0].to_pgn(), pgn);
+
+        // Also at the end of a variation, where the closing parenthesis goes on the next line
+        let mut variation = PgnMove::new(start.parse_san("Kd2").unwrap());
+        variation.comment = Some("}".to_owned());
+        game.moves[0].variations.push(vec![variation]);
+
+        let pgn = game.to_pgn();
+        let reparsed = parse_pgn(&pgn).unwrap();
+        assert_eq!(reparsed[0].moves[0].variations[0][0].comment.as_deref(), Some("}"));
+        assert_eq!(reparsed[0].to_pgn(), pgn);
+    }
+
+    #[test]
+    fn error_positions() {
+        let error = parse_pgn("[Event \"x\"]\n\n1. e4 e5 2. Ke3 *").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 13));
+        assert!(matches!(error.kind, PgnErrorKind::InvalidMove(..)));
+
+        let error = parse_pgn("1. e4 (1. d4 d5\n").unwrap_err();
+        assert!(matches!(error.kind, PgnErrorKind::UnclosedVariation));
+        assert_eq!((error.line, error.column), (1, 16));
+
+        let error = parse_pgn("[Event \"x]\n1. e4 *").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 8));
+        assert!(matches!(error.kind, PgnErrorKind::UnterminatedString));
+
+        let error = parse_pgn("1. e4 {never closed").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 7));
+
+        let error = parse_pgn("1. e4 & e5").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 7));
+        assert!(matches!(error.kind, PgnErrorKind::UnexpectedChar('&')));
+    }
+}
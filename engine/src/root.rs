@@ -0,0 +1,133 @@
+use std::{
+    cmp::Reverse,
+    ops::{Index, IndexMut},
+};
+
+use crate::board::{movegen::generate_legal_moves, r#move::Move, Board};
+
+use super::MIN_SCORE;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootMove {
+    pub r#move: Move,
+    // From the current iteration, MIN_SCORE until the move raised alpha
+    pub score: i32,
+    pub previous_score: i32,
+    // Spent below the move over all iterations, the moves that took the most are tried first
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+// The moves searched at the root. In every iteration the lines found so far come first, best
+// first, and the rest follow by the nodes they took
+#[derive(Debug, Default, Clone)]
+pub struct RootMoves(Vec<RootMove>);
+
+impl Index<usize> for RootMoves {
+    type Output = RootMove;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for RootMoves {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl RootMoves {
+    // The legal moves, only those in `search_moves` if it isn't empty, and of those only the ones
+    // in `allowed` if any of them are
+    pub fn new(board: &Board, search_moves: &[Move], allowed: &[Move]) -> Self {
+        let mut moves = generate_legal_moves(board).to_vec();
+        if !search_moves.is_empty() {
+            moves.retain(|r#move| search_moves.contains(r#move));
+        }
+        if moves.iter().any(|r#move| allowed.contains(r#move)) {
+            moves.retain(|r#move| allowed.contains(r#move));
+        }
+
+        Self(
+            moves
+                .into_iter()
+                .map(|r#move| RootMove {
+                    r#move,
+                    score: MIN_SCORE,
+                    previous_score: MIN_SCORE,
+                    nodes: 0,
+                    pv: vec![r#move],
+                })
+                .collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn new_iteration(&mut self) {
+        for root_move in &mut self.0 {
+            root_move.previous_score = root_move.score;
+            root_move.score = MIN_SCORE;
+        }
+    }
+
+    // After the search of a line, its best move goes first among the moves from `start`. The sort
+    // is stable, so moves that didn't raise alpha keep their order
+    pub fn sort_from(&mut self, start: usize) {
+        self.0[start..].sort_by_key(|root_move| Reverse(root_move.score));
+    }
+
+    // Once `lines` lines were searched, they are put in order and the other moves by the nodes
+    // they took, so the best move of this iteration is the first of the next
+    pub fn finish_iteration(&mut self, lines: usize) {
+        let lines = lines.min(self.0.len());
+        self.0[..lines].sort_by_key(|root_move| Reverse(root_move.score));
+        self.0[lines..].sort_by_key(|root_move| Reverse(root_move.nodes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_moves() {
+        let board = Board::default();
+        let legal = generate_legal_moves(&board);
+        assert_eq!(RootMoves::new(&board, &[], &[]).len(), 20);
+
+        // Search moves restrict the list, allowed moves only as long as some of them are left
+        let root_moves = RootMoves::new(&board, &legal[..3], &legal[2..5]);
+        assert_eq!(root_moves.len(), 1);
+        assert_eq!(root_moves[0].r#move, legal[2]);
+        assert_eq!(RootMoves::new(&board, &legal[..3], &legal[5..]).len(), 3);
+
+        let mut root_moves = RootMoves::new(&board, &legal[..5], &[]);
+        for (i, nodes) in [10, 50, 20, 40, 30].into_iter().enumerate() {
+            root_moves[i].nodes = nodes;
+        }
+
+        // Two lines, the second found with the first left out
+        root_moves[3].score = 15;
+        root_moves.sort_from(0);
+        assert_eq!(root_moves[0].r#move, legal[3]);
+        assert_eq!(root_moves[3].r#move, legal[2]);
+        root_moves[3].score = 25;
+        root_moves.sort_from(1);
+        assert_eq!(root_moves[1].r#move, legal[2]);
+
+        root_moves.finish_iteration(2);
+        let order: Vec<_> = (0..5).map(|i| root_moves[i].r#move).collect();
+        assert_eq!(order, [legal[2], legal[3], legal[1], legal[4], legal[0]]);
+
+        root_moves.new_iteration();
+        assert_eq!((root_moves[0].score, root_moves[0].previous_score), (MIN_SCORE, 25));
+    }
+}
@@ -0,0 +1,451 @@
+mod table;
+
+use std::{collections::HashMap, fs};
+
+use crate::board::{
+    movegen::generate_legal_moves,
+    piece::{PieceColor, PieceType},
+    r#move::Move,
+    Board, NoDelta,
+};
+
+use self::table::{material_key, ProbeState, Table, TableKind, TB_PIECES};
+
+// Results from the point of view of the side to move, cursed wins and blessed losses are only
+// drawn because of the fifty move rule
+pub const WDL_LOSS: i32 = -2;
+pub const WDL_BLESSED_LOSS: i32 = -1;
+pub const WDL_DRAW: i32 = 0;
+pub const WDL_CURSED_WIN: i32 = 1;
+pub const WDL_WIN: i32 = 2;
+
+const MAX_DTZ: i32 = 1 << 18;
+
+#[derive(Default)]
+pub struct Tablebases {
+    // The WDL and DTZ table of each material combination
+    tables: Vec<(Table, Table)>,
+    // Both material keys of a combination point at the same tables
+    index: HashMap<String, usize>,
+    // The largest number of pieces we have tables for
+    cardinality: usize,
+}
+
+fn sign(value: i32) -> i32 {
+    (value > 0) as i32 - (value < 0) as i32
+}
+
+fn is_zeroing(board: &Board, r#move: Move) -> bool {
+    r#move.captured() != PieceType::Empty
+        || board.piece_at(r#move.from() as usize).get_type() == PieceType::Pawn
+}
+
+fn is_mate(board: &Board) -> bool {
+    board.is_king_attacked(board.current_color) && generate_legal_moves(board).is_empty()
+}
+
+// The DTZ of a position where the best move zeroes the fifty move counter
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        WDL_WIN => 1,
+        WDL_CURSED_WIN => 101,
+        WDL_BLESSED_LOSS => -101,
+        WDL_LOSS => -1,
+        _ => 0,
+    }
+}
+
+impl Tablebases {
+    // `paths` is a list of directories like the SyzygyPath option, separated by ':' or ';' on
+    // Windows
+    pub fn new(paths: &str) -> Self {
+        let mut tablebases = Self::default();
+        let separator = if cfg!(windows) { ';' } else { ':' };
+
+        for dir in paths.split(separator).filter(|dir| !dir.is_empty()) {
+            let Ok(entries) = fs::read_dir(dir) else {
+                eprintln!("could not read tablebase directory {}", dir);
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_none_or(|extension| extension != "rtbw") {
+                    continue;
+                }
+
+                let Some(code) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                let Some(wdl) = Table::new(TableKind::Wdl, code, path.clone()) else {
+                    continue;
+                };
+                if wdl.piece_count > TB_PIECES || tablebases.index.contains_key(&wdl.key) {
+                    continue;
+                }
+
+                // A missing DTZ file only fails the DTZ probes
+                let dtz = Table::new(TableKind::Dtz, code, path.with_extension("rtbz")).unwrap();
+
+                tablebases.cardinality = tablebases.cardinality.max(wdl.piece_count);
+                tablebases.index.insert(wdl.key.clone(), tablebases.tables.len());
+                tablebases.index.insert(wdl.key2.clone(), tablebases.tables.len());
+                tablebases.tables.push((wdl, dtz));
+            }
+        }
+
+        tablebases
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    pub fn cardinality(&self) -> usize {
+        self.cardinality
+    }
+
+    fn probe_table(&self, board: &Board, kind: TableKind, wdl: i32, state: &mut ProbeState) -> i32 {
+        // KvK has no table
+        if board.bit_boards.occupancy().count_ones() == 2 {
+            return WDL_DRAW;
+        }
+
+        let Some(index) = self.index.get(&material_key(board, PieceColor::White)) else {
+            *state = ProbeState::Fail;
+            return 0;
+        };
+
+        let (wdl_table, dtz_table) = &self.tables[*index];
+        match kind {
+            TableKind::Wdl => wdl_table.probe(board, wdl, state),
+            TableKind::Dtz => dtz_table.probe(board, wdl, state),
+        }
+    }
+
+    // The tables don't store positions where a capture is possible, so those are resolved by
+    // searching the captures, and with `check_zeroing` the pawn moves as well
+    fn search(&self, board: &Board, state: &mut ProbeState, check_zeroing: bool) -> i32 {
+        let moves = generate_legal_moves(board);
+        let mut best = WDL_LOSS;
+        let mut move_count = 0;
+
+        for r#move in moves.iter().copied() {
+            if r#move.captured() == PieceType::Empty
+                && (!check_zeroing
+                    || board.piece_at(r#move.from() as usize).get_type() != PieceType::Pawn)
+            {
+                continue;
+            }
+
+            move_count += 1;
+
+            let mut new_board = board.clone();
+            new_board.make_move(r#move, NoDelta);
+            let score = -self.search(&new_board, state, false);
+
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+
+                if score >= WDL_WIN {
+                    *state = ProbeState::ZeroingBestMove;
+                    return score;
+                }
+            }
+        }
+
+        // If every move was searched there is no need to probe the position itself
+        let no_more_moves = move_count != 0 && move_count == moves.len();
+
+        let score = if no_more_moves {
+            best
+        } else {
+            let score = self.probe_table(board, TableKind::Wdl, 0, state);
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+            score
+        };
+
+        if best >= score {
+            *state = if best > WDL_DRAW || no_more_moves {
+                ProbeState::ZeroingBestMove
+            } else {
+                ProbeState::Ok
+            };
+            return best;
+        }
+
+        *state = ProbeState::Ok;
+        score
+    }
+
+    // Castling rights are not part of the tables, so positions with them can't be probed
+    pub fn probe_wdl(&self, board: &Board) -> Option<i32> {
+        if !board.castle_flags.is_empty() {
+            return None;
+        }
+
+        let mut state = ProbeState::Ok;
+        let wdl = self.search(board, &mut state, false);
+        (state != ProbeState::Fail).then_some(wdl)
+    }
+
+    // The distance in plies to the next capture or pawn move that keeps the result, positive
+    // when winning. Can be off by one for results that depend on the fifty move rule
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        if !board.castle_flags.is_empty() {
+            return None;
+        }
+
+        let mut state = ProbeState::Ok;
+        let dtz = self.dtz(board, &mut state);
+        (state != ProbeState::Fail).then_some(dtz)
+    }
+
+    fn dtz(&self, board: &Board, state: &mut ProbeState) -> i32 {
+        *state = ProbeState::Ok;
+        let wdl = self.search(board, state, true);
+
+        if *state == ProbeState::Fail || wdl == WDL_DRAW {
+            return 0;
+        }
+
+        if *state == ProbeState::ZeroingBestMove {
+            return dtz_before_zeroing(wdl);
+        }
+
+        let dtz = self.probe_table(board, TableKind::Dtz, wdl, state);
+        if *state == ProbeState::Fail {
+            return 0;
+        }
+
+        if *state != ProbeState::ChangeStm {
+            return (dtz + 100 * (wdl == WDL_CURSED_WIN || wdl == WDL_BLESSED_LOSS) as i32)
+                * sign(wdl);
+        }
+
+        // The table only stores the other side to move, so search one ply, zeroing moves are
+        // already covered by the search above
+        let mut min_dtz = i32::MAX;
+        for r#move in generate_legal_moves(board).iter().copied() {
+            let zeroing = is_zeroing(board, r#move);
+
+            let mut new_board = board.clone();
+            new_board.make_move(r#move, NoDelta);
+
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&new_board, state, false))
+            } else {
+                -self.dtz(&new_board, state)
+            };
+
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+
+            // A mate can be a zeroing move, but it still takes a ply
+            if dtz == 1 && is_mate(&new_board) {
+                min_dtz = 1;
+            }
+
+            if !zeroing {
+                dtz += sign(dtz);
+            }
+
+            if dtz < min_dtz && sign(dtz) == sign(wdl) {
+                min_dtz = dtz;
+            }
+        }
+
+        if min_dtz == i32::MAX {
+            -1
+        } else {
+            min_dtz
+        }
+    }
+
+    // Ranks every root move by its DTZ, the search should only consider the best ranked ones.
+    // Wins that don't run into the fifty move rule rank the same so the search can pick the
+    // fastest mate, unless the game already repeated and we need to make progress
+    pub fn rank_root_moves(&self, board: &Board, has_repeated: bool) -> Option<Vec<(Move, i32)>> {
+        let fifty_move_count = board.halfmove_clock as i32;
+        let mut ranked = Vec::new();
+
+        for r#move in generate_legal_moves(board).iter().copied() {
+            let mut new_board = board.clone();
+            new_board.make_move(r#move, NoDelta);
+
+            let mut dtz = if is_zeroing(board, r#move) {
+                dtz_before_zeroing(-self.probe_wdl(&new_board)?)
+            } else if new_board.is_fifty_move_draw() {
+                0
+            } else {
+                let dtz = -self.probe_dtz(&new_board)?;
+                dtz + sign(dtz)
+            };
+
+            if dtz == 2 && is_mate(&new_board) {
+                dtz = 1;
+            }
+
+            let rank = if dtz > 0 {
+                if dtz + fifty_move_count <= 99 && !has_repeated {
+                    MAX_DTZ
+                } else {
+                    MAX_DTZ - (dtz + fifty_move_count)
+                }
+            } else if dtz < 0 {
+                if -dtz * 2 + fifty_move_count < 100 {
+                    -MAX_DTZ
+                } else {
+                    -MAX_DTZ + (-dtz + fifty_move_count)
+                }
+            } else {
+                0
+            };
+
+            ranked.push((r#move, rank));
+        }
+
+        Some(ranked)
+    }
+
+    // The root moves with the best rank
+    pub fn root_moves(&self, board: &Board, has_repeated: bool) -> Option<Vec<Move>> {
+        let ranked = self.rank_root_moves(board, has_repeated)?;
+        let best = ranked.iter().map(|(_, rank)| *rank).max()?;
+
+        Some(
+            ranked
+                .into_iter()
+                .filter(|(_, rank)| *rank == best)
+                .map(|(r#move, _)| r#move)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::board::CastleFlags;
+    use std::{path::PathBuf, sync::OnceLock};
+
+    fn table_dir(dir: &'static OnceLock<PathBuf>, name: &str, dtz: bool) -> &'static str {
+        dir.get_or_init(|| {
+            let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("KQvK.rtbw"), table::tests::kqvk_wdl()).unwrap();
+            if dtz {
+                fs::write(dir.join("KQvK.rtbz"), table::tests::kqvk_dtz()).unwrap();
+            }
+            dir
+        })
+        .to_str()
+        .unwrap()
+    }
+
+    // A directory holding only the synthetic KQvK WDL table from `table::tests::kqvk_wdl`
+    pub fn kqvk_dir() -> &'static str {
+        static DIR: OnceLock<PathBuf> = OnceLock::new();
+        table_dir(&DIR, "syzygy_test", false)
+    }
+
+    // The same with the DTZ table from `table::tests::kqvk_dtz`
+    fn kqvk_dtz_dir() -> &'static str {
+        static DIR: OnceLock<PathBuf> = OnceLock::new();
+        table_dir(&DIR, "syzygy_dtz_test", true)
+    }
+
+    #[test]
+    fn probe_wdl() {
+        let tablebases = Tablebases::new(kqvk_dir());
+        assert_eq!(tablebases.len(), 1);
+        assert_eq!(tablebases.cardinality(), 3);
+
+        let probe = |fen| tablebases.probe_wdl(&Board::from_fen(fen).unwrap());
+        assert_eq!(probe("8/8/8/3Q4/8/2k5/8/6K1 b - - 0 1"), Some(WDL_LOSS));
+        assert_eq!(probe("8/8/8/3q4/8/2K5/8/6k1 w - - 0 1"), Some(WDL_LOSS));
+        // With white to move the synthetic table decides by the index of the position
+        assert_eq!(probe("8/8/8/3Q4/8/2k5/8/6K1 w - - 0 1"), Some(WDL_DRAW));
+        assert_eq!(probe("8/8/8/3Q4/8/2k5/8/7K w - - 0 1"), Some(WDL_WIN));
+
+        // Black takes the queen, which the table itself doesn't know about
+        assert_eq!(probe("8/8/8/8/8/2k5/2Q5/6K1 b - - 0 1"), Some(WDL_DRAW));
+
+        // Without a KRvK table, or the KQvK DTZ table
+        assert_eq!(probe("8/8/8/3R4/8/2k5/8/6K1 b - - 0 1"), None);
+        let board = Board::from_fen("8/8/8/3Q4/8/2k5/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_dtz(&board), None);
+        assert_eq!(tablebases.root_moves(&board, false), None);
+
+        // Nor with castling rights
+        let mut board = Board::from_fen("8/8/8/3Q4/8/2k5/8/7K w - - 0 1").unwrap();
+        board.castle_flags = CastleFlags::side(PieceColor::White, true);
+        assert_eq!(tablebases.probe_wdl(&board), None);
+        assert_eq!(tablebases.probe_dtz(&board), None);
+    }
+
+    #[test]
+    fn probe_dtz() {
+        let tablebases = Tablebases::new(kqvk_dtz_dir());
+        let board = |fen| Board::from_fen(fen).unwrap();
+
+        // Won positions with white to move are KQVK_DTZ moves from zeroing, black to move is a
+        // ply further away, or can't lose at all if it takes the queen
+        let won = board("8/8/8/3Q4/8/2k5/8/7K w - - 0 1");
+        assert_eq!(tablebases.probe_dtz(&won), Some(2 * table::tests::KQVK_DTZ as i32 + 1));
+        assert_eq!(tablebases.probe_dtz(&board("8/8/8/3Q4/8/2k5/8/6K1 w - - 0 1")), Some(0));
+        assert_eq!(tablebases.probe_dtz(&board("8/8/8/3Q4/8/2k5/8/6K1 b - - 0 1")), Some(-10));
+        assert_eq!(tablebases.probe_dtz(&board("8/8/8/8/8/2k5/2Q5/6K1 b - - 0 1")), Some(0));
+
+        // Every move but those hanging the queen keeps the win well within the fifty moves
+        let hanging = ["d5d2", "d5d3", "d5d4", "d5b3", "d5c4"];
+        let ranked = tablebases.rank_root_moves(&won, false).unwrap();
+        assert_eq!(ranked.len(), 29);
+        for (r#move, rank) in &ranked {
+            let expected = if hanging.contains(&r#move.to_string().as_str()) { 0 } else { MAX_DTZ };
+            assert_eq!(*rank, expected, "{}", r#move);
+        }
+        assert_eq!(tablebases.root_moves(&won, false).unwrap().len(), 24);
+
+        // Once the game repeated the quickest way to zeroing ranks first, here the mate
+        let board = board("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1");
+        let ranked = tablebases.rank_root_moves(&board, true).unwrap();
+        let rank = |name: &str| ranked.iter().find(|(r#move, _)| r#move.to_string() == name);
+        assert_eq!(rank("b1b8").unwrap().1, MAX_DTZ - 1);
+        assert_eq!(rank("b1c1").unwrap().1, MAX_DTZ - 11);
+        let root_moves = tablebases.root_moves(&board, true).unwrap();
+        assert_eq!(
+            root_moves.iter().map(|r#move| r#move.to_string()).collect::<Vec<_>>(),
+            ["b1b8"]
+        );
+    }
+
+    #[test]
+    fn no_tables() {
+        let tablebases = Tablebases::new("/nonexistent");
+        assert!(tablebases.is_empty());
+
+        let board = Board::from_fen("8/8/8/3Q4/8/2k5/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&board), None);
+    }
+
+    #[test]
+    fn dtz_before_zeroing_values() {
+        assert_eq!(dtz_before_zeroing(WDL_WIN), 1);
+        assert_eq!(dtz_before_zeroing(WDL_CURSED_WIN), 101);
+        assert_eq!(dtz_before_zeroing(WDL_DRAW), 0);
+        assert_eq!(dtz_before_zeroing(WDL_BLESSED_LOSS), -101);
+        assert_eq!(dtz_before_zeroing(WDL_LOSS), -1);
+    }
+}
@@ -0,0 +1,1021 @@
+use std::{fs, path::PathBuf, sync::OnceLock};
+
+use static_init::dynamic;
+
+use crate::board::{
+    movegen::bitmasks::KING_ATTACKS,
+    piece::{Piece, PieceColor, PieceType},
+    Board,
+};
+
+pub const TB_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Flags of a PairsData record
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeState {
+    Fail,
+    Ok,
+    // DTZ tables only store one side to move
+    ChangeStm,
+    // The best move is a capture or pawn move, so the stored value can't be trusted
+    ZeroingBestMove,
+}
+
+// Lookup tables for the position encoding, see `Maps::new`
+pub struct Maps {
+    pawns: [usize; 64],
+    b1h1h7: [usize; 64],
+    a1d1d4: [usize; 64],
+    kk: [[usize; 64]; 10],
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+#[dynamic]
+pub static MAPS: Maps = Maps::new();
+
+fn off_a1h8(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+impl Maps {
+    fn new() -> Self {
+        let mut maps = Maps {
+            pawns: [0; 64],
+            b1h1h7: [0; 64],
+            a1d1d4: [0; 64],
+            kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        // Squares below the a1-h8 diagonal to 0..27
+        let mut code = 0;
+        for square in 0..64 {
+            if off_a1h8(square) < 0 {
+                maps.b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        // The a1-d1-d4 triangle to 0..9, the diagonal squares come last
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in 0..28 {
+            if off_a1h8(square) < 0 && square % 8 <= 3 {
+                maps.a1d1d4[square] = code;
+                code += 1;
+            } else if off_a1h8(square) == 0 && square % 8 <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            maps.a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // The 462 legal placements of two kings with the first one in the a1-d1-d4 triangle, if
+        // the first king is on the diagonal the second can't be above it
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for idx in 0..10 {
+            for s1 in 0..28 {
+                if maps.a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+
+                for s2 in 0..64 {
+                    if (KING_ATTACKS[s1] | 1 << s1) & 1 << s2 != 0
+                        || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0)
+                    {
+                        continue;
+                    }
+
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        maps.kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            maps.kk[idx][s2] = code;
+            code += 1;
+        }
+
+        // binomial[k][n] is the number of ways to choose k of n squares
+        maps.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                maps.binomial[k][n] = if k > 0 { maps.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { maps.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // The leading pawn is the one with the highest value here, nearest to the edge and
+        // with the lowest rank, a2-h7 map to 47..0
+        let mut available = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        maps.pawns[square] = available;
+                        maps.pawns[square ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    maps.lead_pawn_idx[lead_pawns][square] = idx;
+                    idx += maps.binomial[lead_pawns - 1][maps.pawns[square]];
+                }
+                maps.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+
+        maps
+    }
+}
+
+// A material signature like "KRPvKR", white first
+pub fn material_key(board: &Board, color: PieceColor) -> String {
+    let side = |color| {
+        let mut key = String::new();
+        for piece_type in [
+            PieceType::King,
+            PieceType::Queen,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Knight,
+            PieceType::Pawn,
+        ] {
+            let count = board.bit_boards[Piece::new(piece_type, color)].count_ones();
+            for _ in 0..count {
+                key.push(char::from(Piece::new(piece_type, PieceColor::White)));
+            }
+        }
+        key
+    };
+
+    format!("{}v{}", side(color), side(!color))
+}
+
+// Pieces as they are stored in the table files
+fn tb_piece(piece: Piece) -> u8 {
+    (piece.get_type() as u8 + 1) | (piece.get_color() as u8) << 3
+}
+
+#[derive(Debug, Default, Clone)]
+struct PairsData {
+    flags: u8,
+    block_size: usize,
+    // There is a sparse index entry about every span values
+    span: u64,
+    num_blocks: usize,
+    max_sym_len: usize,
+    min_sym_len: usize,
+    // Offsets into the file
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    // Number of values minus one each symbol expands to
+    symlen: Vec<u8>,
+    pieces: [u8; TB_PIECES],
+    group_idx: [u64; TB_PIECES + 1],
+    group_len: [usize; TB_PIECES + 1],
+    map_idx: [u16; 4],
+}
+
+struct TableData {
+    bytes: Vec<u8>,
+    // Indexed by side * 4 + file
+    pairs: Vec<PairsData>,
+    map: usize,
+}
+
+pub struct Table {
+    pub kind: TableKind,
+    path: PathBuf,
+    // The signature with the stronger side as white and as black, equal for symmetric tables
+    pub key: String,
+    pub key2: String,
+    pub piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // Pawns of the leading color and of the other one
+    pawn_count: [usize; 2],
+    // Files are only read on the first probe
+    data: OnceLock<Option<TableData>>,
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> usize {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> usize {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize
+}
+
+// Blocks can end right at the end of the file, read past it as zeros
+fn u32_be(bytes: &[u8], at: usize) -> u64 {
+    let mut buf = [0; 4];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = bytes.get(at + i).copied().unwrap_or(0);
+    }
+    u32::from_be_bytes(buf) as u64
+}
+
+impl Table {
+    // `code` is the file name without the extension, like "KRvK"
+    pub fn new(kind: TableKind, code: &str, path: PathBuf) -> Option<Self> {
+        let (white, black) = code.split_once('v')?;
+        let count = |side: &str, c: char| side.chars().filter(|p| *p == c).count();
+
+        if code.chars().any(|c| !"KQRBNPv".contains(c))
+            || count(white, 'K') != 1
+            || count(black, 'K') != 1
+        {
+            return None;
+        }
+
+        let piece_count = white.len() + black.len();
+        let has_pawns = count(white, 'P') + count(black, 'P') > 0;
+        let has_unique_pieces =
+            "QRBNP".chars().any(|c| count(white, c) == 1 || count(black, c) == 1);
+
+        // The leading color is the one with fewer pawns, but not none
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count =
+            if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] };
+
+        let canonical = |side: &str| {
+            let mut pieces: Vec<char> = side.chars().collect();
+            pieces.sort_by_key(|c| "KQRBNP".find(*c));
+            pieces.into_iter().collect::<String>()
+        };
+        let (white, black) = (canonical(white), canonical(black));
+
+        Some(Self {
+            kind,
+            path,
+            key: format!("{}v{}", white, black),
+            key2: format!("{}v{}", black, white),
+            piece_count,
+            has_pawns,
+            has_unique_pieces,
+            pawn_count,
+            data: OnceLock::new(),
+        })
+    }
+
+    fn sides(&self) -> usize {
+        if self.kind == TableKind::Wdl && self.key != self.key2 {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn pairs_index(&self, stm: usize, file: usize) -> usize {
+        let sides = if self.kind == TableKind::Wdl { 2 } else { 1 };
+        (stm % sides) * 4 + if self.has_pawns { file } else { 0 }
+    }
+
+    fn data(&self) -> Option<&TableData> {
+        self.data
+            .get_or_init(|| {
+                let bytes = fs::read(&self.path).ok()?;
+                let magic = if self.kind == TableKind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+                if bytes.len() < 5 || bytes[..4] != magic {
+                    eprintln!("invalid tablebase file {}", self.path.display());
+                    return None;
+                }
+                Some(self.init(bytes))
+            })
+            .as_ref()
+    }
+
+    // Reads the layout of the file, the data itself stays compressed
+    fn init(&self, bytes: Vec<u8>) -> TableData {
+        let mut pairs = vec![PairsData::default(); 8];
+        let sides = self.sides();
+        let max_file = if self.has_pawns { 3 } else { 0 };
+        let pp = self.has_pawns && self.pawn_count[1] > 0;
+
+        // Skip the magic and the flags
+        let mut at = 5;
+
+        for file in 0..=max_file {
+            let order = [
+                [bytes[at] as usize & 0xF, if pp { bytes[at + 1] as usize & 0xF } else { 0xF }],
+                [bytes[at] as usize >> 4, if pp { bytes[at + 1] as usize >> 4 } else { 0xF }],
+            ];
+            at += 1 + pp as usize;
+
+            for k in 0..self.piece_count {
+                for side in 0..sides {
+                    pairs[self.pairs_index(side, file)].pieces[k] =
+                        if side == 1 { bytes[at] >> 4 } else { bytes[at] & 0xF };
+                }
+                at += 1;
+            }
+
+            for (side, order) in order.iter().enumerate().take(sides) {
+                self.set_groups(&mut pairs[self.pairs_index(side, file)], order, file);
+            }
+        }
+
+        at += at & 1;
+
+        for file in 0..=max_file {
+            for side in 0..sides {
+                at = set_sizes(&mut pairs[self.pairs_index(side, file)], &bytes, at);
+            }
+        }
+
+        let map = at;
+        if self.kind == TableKind::Dtz {
+            for file in 0..=max_file {
+                let d = &mut pairs[self.pairs_index(0, file)];
+                if d.flags & MAPPED == 0 {
+                    continue;
+                }
+
+                if d.flags & WIDE != 0 {
+                    at += at & 1;
+                    for i in 0..4 {
+                        d.map_idx[i] = ((at - map) / 2 + 1) as u16;
+                        at += 2 * u16_le(&bytes, at) + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        d.map_idx[i] = (at - map + 1) as u16;
+                        at += bytes[at] as usize + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for file in 0..=max_file {
+            for side in 0..sides {
+                let d = &mut pairs[self.pairs_index(side, file)];
+                d.sparse_index = at;
+                at += d.sparse_index_size * 6;
+            }
+        }
+
+        for file in 0..=max_file {
+            for side in 0..sides {
+                let d = &mut pairs[self.pairs_index(side, file)];
+                d.block_length = at;
+                at += d.block_length_size * 2;
+            }
+        }
+
+        for file in 0..=max_file {
+            for side in 0..sides {
+                let d = &mut pairs[self.pairs_index(side, file)];
+                at = (at + 0x3F) & !0x3F;
+                d.data = at;
+                at += d.num_blocks * d.block_size;
+            }
+        }
+
+        TableData { bytes, pairs, map }
+    }
+
+    // Pieces that are encoded together form a group: the leading pieces, then pieces of the same
+    // type and color. KRvKN gives KRK + N, KNNvK gives KK + NN and KPPvKP gives P + PP + K + K
+    fn set_groups(&self, d: &mut PairsData, order: &[usize; 2], file: usize) {
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[0] = 1;
+
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        // The order the groups are combined in is stored per table
+        let pp = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if pp { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if pp { d.group_len[1] } else { 0 };
+        let mut idx = 1;
+
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                d.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    MAPS.lead_pawns_size[d.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                d.group_idx[1] = idx;
+                idx *= MAPS.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= MAPS.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+
+        d.group_idx[n] = idx;
+    }
+
+    fn check_dtz_stm(&self, data: &TableData, stm: usize, file: usize) -> bool {
+        self.kind == TableKind::Wdl
+            || (data.pairs[self.pairs_index(stm, file)].flags & STM) as usize == stm
+            || (self.key == self.key2 && !self.has_pawns)
+    }
+
+    fn map_score(&self, data: &TableData, file: usize, mut value: i32, wdl: i32) -> i32 {
+        if self.kind == TableKind::Wdl {
+            return value - 2;
+        }
+
+        // Indexed by wdl + 2
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+
+        let d = &data.pairs[self.pairs_index(0, file)];
+        if d.flags & MAPPED != 0 {
+            let idx = d.map_idx[WDL_MAP[(wdl + 2) as usize]] as usize + value as usize;
+            value = if d.flags & WIDE != 0 {
+                u16_le(&data.bytes, data.map + 2 * idx) as i32
+            } else {
+                data.bytes[data.map + idx] as i32
+            };
+        }
+
+        // Convert moves to plies
+        if (wdl == 2 && d.flags & WIN_PLIES == 0)
+            || (wdl == -2 && d.flags & LOSS_PLIES == 0)
+            || wdl == 1
+            || wdl == -1
+        {
+            value *= 2;
+        }
+
+        value + 1
+    }
+
+    // The WDL value or the DTZ for the position, `wdl` is needed to decode DTZ values
+    pub fn probe(&self, board: &Board, wdl: i32, state: &mut ProbeState) -> i32 {
+        let Some(data) = self.data() else {
+            *state = ProbeState::Fail;
+            return 0;
+        };
+
+        let Some((idx, pairs, file)) = self.encode(board, data) else {
+            *state = ProbeState::ChangeStm;
+            return 0;
+        };
+
+        self.map_score(data, file, decompress_pairs(&data.pairs[pairs], &data.bytes, idx), wdl)
+    }
+
+    // Maps the position to its index in the table, returns the PairsData index and the file of
+    // the leading pawn with it
+    fn encode(&self, board: &Board, data: &TableData) -> Option<(u64, usize, usize)> {
+        let mut squares = [0; TB_PIECES];
+        let mut pieces = [0; TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns_count = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+
+        // Tables only store the position with the stronger side as white, and symmetric ones only
+        // with white to move, otherwise swap the colors and mirror the board
+        let symmetric_black_to_move =
+            self.key == self.key2 && board.current_color == PieceColor::Black;
+        let black_stronger = material_key(board, PieceColor::White) != self.key;
+        let flip = symmetric_black_to_move || black_stronger;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ board.current_color as usize;
+
+        // Tables with pawns are split by the file of the leading pawn
+        if self.has_pawns {
+            let pawn = data.pairs[self.pairs_index(0, 0)].pieces[0] ^ flip_color;
+            let color = if pawn & 8 != 0 { PieceColor::Black } else { PieceColor::White };
+
+            lead_pawns = board.bit_boards[Piece::new(PieceType::Pawn, color)];
+            let mut mask = lead_pawns;
+            while mask != 0 {
+                squares[size] = mask.trailing_zeros() as usize ^ flip_squares;
+                size += 1;
+                mask &= mask - 1;
+            }
+            lead_pawns_count = size;
+
+            let lead =
+                (0..lead_pawns_count).max_by_key(|i| (MAPS.pawns[squares[*i]], usize::MAX - i));
+            squares.swap(0, lead.unwrap());
+
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+
+        if !self.check_dtz_stm(data, stm, file) {
+            return None;
+        }
+
+        let mut mask = board.bit_boards.occupancy() ^ lead_pawns;
+        while mask != 0 {
+            let square = mask.trailing_zeros() as usize;
+            squares[size] = square ^ flip_squares;
+            pieces[size] = tb_piece(board.piece_at(square)) ^ flip_color;
+            size += 1;
+            mask &= mask - 1;
+        }
+
+        let pairs = self.pairs_index(stm, file);
+        let d = &data.pairs[pairs];
+
+        // Put the pieces in the order the table was generated with
+        for i in lead_pawns_count..size - 1 {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // The leading piece goes to the a-d files
+        if squares[0] % 8 > 3 {
+            for square in squares.iter_mut().take(size) {
+                *square ^= 7;
+            }
+        }
+
+        let mut idx;
+        if self.has_pawns {
+            idx = MAPS.lead_pawn_idx[lead_pawns_count][squares[0]];
+
+            squares[1..lead_pawns_count].sort_by_key(|square| MAPS.pawns[*square]);
+            for i in 1..lead_pawns_count {
+                idx += MAPS.binomial[i][MAPS.pawns[squares[i]]];
+            }
+        } else {
+            // Without pawns the leading piece also goes below the fifth rank and below the a1-h8
+            // diagonal
+            if squares[0] / 8 > 3 {
+                for square in squares.iter_mut().take(size) {
+                    *square ^= 56;
+                }
+            }
+
+            for i in 0..d.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+
+                if off_a1h8(squares[i]) > 0 {
+                    for square in squares.iter_mut().take(size).skip(i) {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            idx = if self.has_unique_pieces {
+                self.encode_unique(&squares)
+            } else {
+                MAPS.kk[MAPS.a1d1d4[squares[0]]][squares[1]] as u64
+            };
+        }
+
+        idx *= d.group_idx[0];
+
+        // The remaining groups, with each square counted only among the squares still free
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let group_end = group_start + d.group_len[next];
+            squares[group_start..group_end].sort();
+
+            let mut n = 0;
+            for i in 0..d.group_len[next] {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|s| square > **s).count();
+                n += MAPS.binomial[i + 1][square - adjust - 8 * remaining_pawns as usize];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        Some((idx, pairs, file))
+    }
+
+    // The first three pieces when at least one of them besides the kings is unique
+    fn encode_unique(&self, squares: &[usize; TB_PIECES]) -> u64 {
+        let adjust1 = (squares[1] > squares[0]) as usize;
+        let adjust2 = (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+        let rank = |square: usize| square / 8;
+
+        let idx = if off_a1h8(squares[0]) != 0 {
+            (MAPS.a1d1d4[squares[0]] * 63 + (squares[1] - adjust1)) * 62 + squares[2] - adjust2
+        } else if off_a1h8(squares[1]) != 0 {
+            (6 * 63 + rank(squares[0]) * 28 + MAPS.b1h1h7[squares[1]]) * 62 + squares[2] - adjust2
+        } else if off_a1h8(squares[2]) != 0 {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + rank(squares[0]) * 7 * 28
+                + (rank(squares[1]) - adjust1) * 28
+                + MAPS.b1h1h7[squares[2]]
+        } else {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + 4 * 7 * 28
+                + rank(squares[0]) * 7 * 6
+                + (rank(squares[1]) - adjust1) * 6
+                + (rank(squares[2]) - adjust2)
+        };
+
+        idx as u64
+    }
+}
+
+fn set_symlen(d: &mut PairsData, bytes: &[u8], sym: usize, visited: &mut [bool]) -> u8 {
+    visited[sym] = true;
+
+    let (left, right) = btree(d, bytes, sym);
+    if right == 0xFFF {
+        return 0;
+    }
+
+    if !visited[left] {
+        d.symlen[left] = set_symlen(d, bytes, left, visited);
+    }
+    if !visited[right] {
+        d.symlen[right] = set_symlen(d, bytes, right, visited);
+    }
+
+    d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1)
+}
+
+// The pair of symbols `sym` expands to, for leaves the left one is the value
+fn btree(d: &PairsData, bytes: &[u8], sym: usize) -> (usize, usize) {
+    let lr = &bytes[d.btree + 3 * sym..d.btree + 3 * sym + 3];
+    let left = ((lr[1] as usize & 0xF) << 8) | lr[0] as usize;
+    let right = ((lr[2] as usize) << 4) | (lr[1] as usize >> 4);
+    (left, right)
+}
+
+fn set_sizes(d: &mut PairsData, bytes: &[u8], mut at: usize) -> usize {
+    d.flags = bytes[at];
+    at += 1;
+
+    if d.flags & SINGLE_VALUE != 0 {
+        d.num_blocks = 0;
+        d.span = 0;
+        d.sparse_index_size = 0;
+        // The value every position has
+        d.min_sym_len = bytes[at] as usize;
+        return at + 1;
+    }
+
+    let table_size = d.group_idx[d.group_len.iter().position(|len| *len == 0).unwrap()];
+
+    d.block_size = 1 << bytes[at];
+    d.span = 1 << bytes[at + 1];
+    d.sparse_index_size = table_size.div_ceil(d.span) as usize;
+    let padding = bytes[at + 2] as usize;
+    d.num_blocks = u32_le(bytes, at + 3);
+    d.block_length_size = d.num_blocks + padding;
+    d.max_sym_len = bytes[at + 7] as usize;
+    d.min_sym_len = bytes[at + 8] as usize;
+    d.lowest_sym = at + 9;
+    at += 9;
+
+    // Longer Huffman codes have lower values, base64[l] is the lowest code of length
+    // l + min_sym_len padded to 64 bits
+    let lowest_sym = |i: usize| u16_le(bytes, d.lowest_sym + 2 * i) as u64;
+    d.base64 = vec![0; d.max_sym_len - d.min_sym_len + 1];
+    for i in (0..d.base64.len() - 1).rev() {
+        d.base64[i] =
+            d.base64[i + 1].wrapping_add(lowest_sym(i)).wrapping_sub(lowest_sym(i + 1)) / 2;
+    }
+    for i in 0..d.base64.len() {
+        d.base64[i] <<= 64 - i - d.min_sym_len;
+    }
+
+    at += d.base64.len() * 2;
+    d.symlen = vec![0; u16_le(bytes, at)];
+    at += 2;
+    d.btree = at;
+
+    let mut visited = vec![false; d.symlen.len()];
+    for sym in 0..d.symlen.len() {
+        if !visited[sym] {
+            d.symlen[sym] = set_symlen(d, bytes, sym, &mut visited);
+        }
+    }
+
+    at + d.symlen.len() * 3 + (d.symlen.len() & 1)
+}
+
+// Values are stored in blocks of Huffman coded symbols, each symbol standing for a run of values
+// built by recursive pairing
+fn decompress_pairs(d: &PairsData, bytes: &[u8], idx: u64) -> i32 {
+    if d.flags & SINGLE_VALUE != 0 {
+        return d.min_sym_len as i32;
+    }
+
+    // The sparse index entry k points at the block and offset of value k * span + span / 2
+    let k = (idx / d.span) as usize;
+    let mut block = u32_le(bytes, d.sparse_index + 6 * k);
+    let mut offset = u16_le(bytes, d.sparse_index + 6 * k + 4) as i64;
+    offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+    let block_length = |block: usize| u16_le(bytes, d.block_length + 2 * block) as i64;
+    while offset < 0 {
+        block -= 1;
+        offset += block_length(block) + 1;
+    }
+    while offset > block_length(block) {
+        offset -= block_length(block) + 1;
+        block += 1;
+    }
+
+    let mut at = d.data + block * d.block_size;
+    let mut buf = (u32_be(bytes, at) << 32) | u32_be(bytes, at + 4);
+    let mut buf_size = 64;
+    at += 8;
+
+    let mut sym;
+    loop {
+        let mut len = 0;
+        while buf < d.base64[len] {
+            len += 1;
+        }
+
+        sym = ((buf - d.base64[len]) >> (64 - len - d.min_sym_len)) as usize;
+        sym += u16_le(bytes, d.lowest_sym + 2 * len);
+
+        if offset < d.symlen[sym] as i64 + 1 {
+            break;
+        }
+
+        offset -= d.symlen[sym] as i64 + 1;
+        len += d.min_sym_len;
+        buf <<= len;
+        buf_size -= len;
+
+        if buf_size <= 32 {
+            buf_size += 32;
+            buf |= u32_be(bytes, at) << (64 - buf_size);
+            at += 4;
+        }
+    }
+
+    // Expand the symbol until we reach the leaf holding the value
+    while d.symlen[sym] != 0 {
+        let (left, right) = btree(d, bytes, sym);
+        if offset < d.symlen[left] as i64 + 1 {
+            sym = left;
+        } else {
+            offset -= d.symlen[left] as i64 + 1;
+            sym = right;
+        }
+    }
+
+    btree(d, bytes, sym).0 as i32
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Positions of the KQvK table with white to move are won when their index isn't a multiple
+    // of three, the others drawn, black to move always loses
+    pub fn kqvk_is_win(idx: u64) -> bool {
+        idx % 3 != 0
+    }
+
+    // A synthetic KQvK WDL file, the white to move side is Huffman coded with one bit per value
+    // and the black to move side has a single value
+    pub fn kqvk_wdl() -> Vec<u8> {
+        const TABLE_SIZE: usize = 31332;
+        const BLOCK_VALUES: usize = 256;
+        let num_blocks = TABLE_SIZE.div_ceil(BLOCK_VALUES);
+
+        let mut bytes = WDL_MAGIC.to_vec();
+        bytes.push(1);
+
+        // Piece order, then K, Q and k for both sides
+        bytes.push(0);
+        for piece in [6, 5, 14] {
+            bytes.push(piece | piece << 4);
+        }
+        bytes.push(0);
+
+        // Blocks of 32 bytes, a sparse index entry every 256 values
+        bytes.extend([0, 5, 8, 0]);
+        bytes.extend((num_blocks as u32).to_le_bytes());
+        // Symbol lengths, the lowest symbol and the two symbols standing for a win and a draw
+        bytes.extend([1, 1, 0, 0, 2, 0]);
+        bytes.extend([4, 0xF0, 0xFF, 2, 0xF0, 0xFF]);
+
+        bytes.extend([SINGLE_VALUE, 0]);
+
+        for block in 0..num_blocks {
+            bytes.extend((block as u32).to_le_bytes());
+            bytes.extend((BLOCK_VALUES as u16 / 2).to_le_bytes());
+        }
+        for _ in 0..num_blocks {
+            bytes.extend((BLOCK_VALUES as u16 - 1).to_le_bytes());
+        }
+
+        bytes.resize(bytes.len().next_multiple_of(64), 0);
+        for idx in 0..num_blocks * BLOCK_VALUES {
+            if idx % 8 == 0 {
+                bytes.push(0);
+            }
+            if !kqvk_is_win(idx as u64) {
+                *bytes.last_mut().unwrap() |= 0x80 >> (idx % 8);
+            }
+        }
+
+        bytes
+    }
+
+    // Every KQvK position with white to move that isn't drawn is this many moves from zeroing
+    pub const KQVK_DTZ: u8 = 4;
+
+    // A synthetic KQvK DTZ file, only white to move is stored, as a single value in moves
+    pub fn kqvk_dtz() -> Vec<u8> {
+        let mut bytes = DTZ_MAGIC.to_vec();
+        bytes.push(1);
+
+        bytes.push(0);
+        for piece in [6, 5, 14] {
+            bytes.push(piece);
+        }
+        bytes.push(0);
+
+        bytes.extend([SINGLE_VALUE, KQVK_DTZ]);
+
+        bytes
+    }
+
+    fn board(pieces: [(usize, char); 3], color: char) -> Board {
+        let mut rows = Vec::new();
+        for rank in (0..8).rev() {
+            let mut row = String::new();
+            let mut empty = 0;
+            for file in 0..8 {
+                match pieces.iter().find(|(square, _)| *square == rank * 8 + file) {
+                    Some((_, piece)) => {
+                        if empty > 0 {
+                            row.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        row.push(*piece);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                row.push_str(&empty.to_string());
+            }
+            rows.push(row);
+        }
+
+        Board::from_fen(&format!("{} {} - - 0 1", rows.join("/"), color)).unwrap()
+    }
+
+    #[test]
+    fn maps() {
+        assert_eq!(MAPS.kk.iter().flatten().max(), Some(&461));
+        assert_eq!(MAPS.a1d1d4.iter().max(), Some(&9));
+        assert_eq!(MAPS.b1h1h7.iter().max(), Some(&27));
+        assert_eq!(MAPS.binomial[2][5], 10);
+        assert_eq!(MAPS.binomial[5][63], 7028847);
+        assert_eq!(MAPS.pawns[8], 47);
+        assert_eq!(MAPS.pawns[15], 46);
+
+        // Every placement of the leading pawns on a file gets its own index
+        assert_eq!(MAPS.lead_pawns_size[1].iter().sum::<u64>(), 24);
+        assert_eq!(MAPS.lead_pawns_size[2].iter().sum::<u64>(), 576);
+    }
+
+    #[test]
+    fn material_keys() {
+        let board = Board::from_fen("8/8/4k3/1r6/8/2P5/2K5/3N4 b - - 0 1").unwrap();
+        assert_eq!(material_key(&board, PieceColor::White), "KNPvKR");
+        assert_eq!(material_key(&board, PieceColor::Black), "KRvKNP");
+
+        let table = Table::new(TableKind::Wdl, "KPNvKR", PathBuf::new()).unwrap();
+        assert_eq!(table.key, "KNPvKR");
+        assert_eq!(table.key2, "KRvKNP");
+        assert!(Table::new(TableKind::Wdl, "KQvKK", PathBuf::new()).is_none());
+    }
+
+    #[test]
+    fn encoding() {
+        let table = Table::new(TableKind::Wdl, "KQvK", PathBuf::new()).unwrap();
+        assert!(table.data.set(Some(table.init(kqvk_wdl()))).is_ok());
+        let data = table.data().unwrap();
+
+        let symmetries: [fn(usize) -> usize; 8] = [
+            |square| square,
+            |square| square ^ 7,
+            |square| square ^ 56,
+            |square| square ^ 63,
+            |square| ((square >> 3) | (square << 3)) & 63,
+            |square| (((square >> 3) | (square << 3)) & 63) ^ 7,
+            |square| (((square >> 3) | (square << 3)) & 63) ^ 56,
+            |square| (((square >> 3) | (square << 3)) & 63) ^ 63,
+        ];
+
+        // A sample of positions, each with all its mirror images, the table being small enough
+        // to do all of them would make the test slow in debug builds
+        for king in (0..64).step_by(3) {
+            for queen in (0..64).step_by(5).filter(|queen| *queen != king) {
+                for black_king in (0..64).filter(|square| *square != king && *square != queen) {
+                    let mut indices = symmetries.iter().map(|symmetry| {
+                        let pieces = [
+                            (symmetry(king), 'K'),
+                            (symmetry(queen), 'Q'),
+                            (symmetry(black_king), 'k'),
+                        ];
+                        let (idx, pairs, _) = table.encode(&board(pieces, 'w'), data).unwrap();
+                        assert_eq!(pairs, 0);
+                        idx
+                    });
+
+                    let idx = indices.next().unwrap();
+                    assert!(idx < 31332);
+                    assert!(indices.all(|other| other == idx));
+
+                    let pieces = [(king, 'K'), (queen, 'Q'), (black_king, 'k')];
+                    let mut state = ProbeState::Ok;
+                    let wdl = table.probe(&board(pieces, 'w'), 0, &mut state);
+                    assert_eq!(state, ProbeState::Ok);
+                    assert_eq!(wdl, if kqvk_is_win(idx) { 2 } else { 0 });
+                }
+            }
+        }
+
+        // The colors are swapped when black has the queen
+        let board = Board::from_fen("8/8/8/3q4/8/2k5/8/6K1 b - - 0 1").unwrap();
+        let mut state = ProbeState::Ok;
+        let (_, pairs, _) = table.encode(&board, data).unwrap();
+        assert_eq!(pairs, 0);
+        table.probe(&board, 0, &mut state);
+        assert_eq!(state, ProbeState::Ok);
+
+        let board = Board::from_fen("8/8/8/3Q4/8/2k5/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&board, 0, &mut state), -2);
+    }
+}
@@ -1,12 +1,29 @@
-use std::mem::size_of;
+use std::{
+    mem::size_of,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+};
 
 use crate::board::{
-    piece::PieceType,
+    piece::{Piece, PieceType},
     r#move::{Move, MoveType},
     Board,
 };
 
-use super::MATE_SCORE;
+use super::{MAX_PLY, TB_WIN_SCORE};
+
+// Stored for positions whose static eval wasn't computed, like when in check
+pub const EVAL_NONE: i32 = i16::MIN as i32;
+
+const BUCKET_SIZE: usize = 4;
+const GENERATION_CYCLE: u8 = 32;
+
+// Layout of the data half of an entry
+const MOVE_MASK: u64 = 0xffff;
+const SCORE_SHIFT: u32 = 16;
+const EVAL_SHIFT: u32 = 33;
+const DEPTH_SHIFT: u32 = 49;
+const FLAGS_SHIFT: u32 = 57;
+const GENERATION_SHIFT: u32 = 59;
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HashFlags {
@@ -21,46 +38,72 @@ pub struct THash {
     pub key: u64,
     pub depth: i32,
     pub score: i32,
+    pub eval: i32,
     pub flags: HashFlags,
     pub best_move: Move,
+    generation: u8,
 }
 
 impl THash {
-    pub fn new(key: u64, depth: i32, score: i32, best_move: Move, flags: HashFlags) -> Self {
-        Self { key, depth, score, flags, best_move }
+    pub fn new(
+        key: u64,
+        depth: i32,
+        score: i32,
+        eval: i32,
+        best_move: Move,
+        flags: HashFlags,
+    ) -> Self {
+        Self { key, depth, score, eval, flags, best_move, generation: 0 }
     }
 
+    // The key in the low half, then the move in 16 bits, the score in 17, the static eval in 16,
+    // the depth in 8, the flags in 2 and the generation in 5. The depth is stored one higher, so
+    // an empty slot is told apart from an entry with key zero
     pub fn into_u128(self) -> u128 {
-        let mut val: u128 = 0;
-        // Pack the 64-bit key into bits 0..63.
-        val |= self.key as u128;
-        // Pack the 16-bit depth into bits 64..79.
-        val |= ((self.depth as u128) & 0xffff) << 64;
-        // Pack the 32-bit score into bits 80..111.
-        val |= ((self.score as u128) & 0xffffffff) << 79;
-        // Pack the 2-bit flags into bits 112..113.
-        val |= ((self.flags as u128) & 0b11) << 111;
-        // Pack best_move.from (6 bits) into bits 114..119.
-        val |= ((self.best_move.from() as u128) & 0x3f) << 113;
-        // Pack best_move.to (6 bits) into bits 120..125.
-        val |= ((self.best_move.to() as u128) & 0x3f) << 119;
-        // Pack best_move.move_type (2 bits) into bits 126..127.
-        // (Note: the original code used 0x7, which would be 3 bits.)
-        val |= ((self.best_move.move_type() as u128) & 0x7) << 125;
-        val
+        // Promotions take the codes after the other move types, one for each piece
+        let code = match self.best_move.move_type() {
+            MoveType::Promote => {
+                MoveType::Promote as u64 + self.best_move.piece().get_type() as u64 - 1
+            }
+            move_type => move_type as u64,
+        };
+        let r#move = self.best_move.from() as u64 | (self.best_move.to() as u64) << 6 | code << 12;
+
+        let data = r#move
+            | ((self.score as u64) & 0x1ffff) << SCORE_SHIFT
+            | ((self.eval.clamp(EVAL_NONE, i16::MAX as i32) as u64) & 0xffff) << EVAL_SHIFT
+            | ((self.depth.clamp(0, 0xfe) + 1) as u64) << DEPTH_SHIFT
+            | (self.flags as u64) << FLAGS_SHIFT
+            | (self.generation as u64 % GENERATION_CYCLE as u64) << GENERATION_SHIFT;
+
+        (data as u128) << 64 | self.key as u128
     }
 
     pub fn get_depth(val: u128) -> i32 {
-        ((val >> 64) & 0x7fff) as i32
+        ((val >> (64 + DEPTH_SHIFT)) & 0xff) as i32 - 1
+    }
+
+    fn is_used(val: u128) -> bool {
+        Self::get_depth(val) >= 0
+    }
+
+    fn get_generation(val: u128) -> u8 {
+        (val >> (64 + GENERATION_SHIFT)) as u8
     }
 
     pub fn from_u128(board: &Board, val: u128) -> Self {
-        let from = ((val >> 113) & 0x3f) as u32;
-        let to = ((val >> 119) & 0x3f) as u32;
-        let move_type: MoveType =
-            unsafe { std::mem::transmute::<u8, MoveType>(((val >> 125) & 0x7) as u8) };
+        let data = (val >> 64) as u64;
+
+        let from = (data & 0x3f) as u32;
+        let to = ((data >> 6) & 0x3f) as u32;
+        let code = ((data >> 12) & 0xf) as u8;
+        let move_type = MoveType::from(code.min(MoveType::Promote as u8));
 
-        let piece = board.piece_at(from as usize);
+        let piece = if move_type == MoveType::Promote {
+            Piece::new(PieceType::from(code - MoveType::Promote as u8 + 1), board.current_color)
+        } else {
+            board.piece_at(from as usize)
+        };
         let captured = board.piece_at(to as usize);
         let captured = if captured.get_color() != board.current_color {
             captured.get_type()
@@ -68,76 +111,241 @@ impl THash {
             PieceType::Empty
         };
 
-        Self {
-            key: (val & 0xffffffffffffffff) as u64,   // Bits 0–63
-            depth: ((val >> 64) & 0x7fff) as i32,     // Bits 64–78 (15 bits)
-            score: ((val >> 79) & 0xffffffff) as i32, // Bits 79–110 (32 bits)
-            flags: unsafe { std::mem::transmute::<u8, HashFlags>(((val >> 111) & 0b11) as u8) }, // Bits 111–112 (2 bits)
+        // Sign extend the score and the eval
+        let score = ((data >> SCORE_SHIFT) as i64) << 47 >> 47;
+        let eval = ((data >> EVAL_SHIFT) as u16) as i16;
 
+        Self {
+            key: val as u64,
+            depth: Self::get_depth(val),
+            score: score as i32,
+            eval: eval as i32,
+            flags: unsafe {
+                std::mem::transmute::<u8, HashFlags>(((data >> FLAGS_SHIFT) & 0b11) as u8)
+            },
             best_move: Move::new(from, to, move_type, piece, captured),
+            generation: Self::get_generation(val),
         }
     }
 }
 
+// Counted while `collect_stats` is set, reset for every search
+#[derive(Default)]
+struct Stats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    // Writes that found neither the position nor a free entry, so another position was evicted
+    collisions: AtomicU64,
+    // Collisions that evicted an entry of the current search
+    overwrites: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TTStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub collisions: u64,
+    pub overwrites: u64,
+}
+
+// A cache line of entries, a position can be stored in any entry of its bucket
+#[repr(align(64))]
+#[derive(Default)]
+struct Bucket([(AtomicU64, AtomicU64); BUCKET_SIZE]);
+
+// Shared by all search threads without locking. The key is stored xor'd with the data, so an
+// entry torn by two threads writing at once no longer matches its key and reads as a miss
 pub struct TTable {
-    entries: Box<[u128]>,
+    buckets: Box<[Bucket]>,
+    // Bumped for every search, entries from older searches are replaced first
+    generation: AtomicU8,
+    collect_stats: AtomicBool,
+    stats: Stats,
+}
+
+fn load((stored_key, data): &(AtomicU64, AtomicU64)) -> u128 {
+    let data = data.load(Ordering::Relaxed);
+    let key = stored_key.load(Ordering::Relaxed) ^ data;
+
+    (data as u128) << 64 | key as u128
+}
+
+fn store((stored_key, stored_data): &(AtomicU64, AtomicU64), val: u128) {
+    let (key, data) = (val as u64, (val >> 64) as u64);
+
+    stored_key.store(key ^ data, Ordering::Relaxed);
+    stored_data.store(data, Ordering::Relaxed);
 }
 
 impl TTable {
     pub fn new(size: usize) -> Self {
-        let num_entries = size / size_of::<u128>();
-        let mut entries = Vec::with_capacity(num_entries);
-        entries.resize_with(num_entries, Default::default);
+        let num_buckets = (size / size_of::<Bucket>()).max(1);
+        let buckets = (0..num_buckets).map(|_| Default::default()).collect();
+
+        Self {
+            buckets,
+            generation: AtomicU8::new(0),
+            collect_stats: AtomicBool::new(false),
+            stats: Default::default(),
+        }
+    }
+
+    pub fn clear(&self) {
+        for bucket in self.buckets.iter() {
+            for entry in bucket.0.iter() {
+                store(entry, 0);
+            }
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    pub fn new_search(&self) {
+        let generation = (self.generation() + 1) % GENERATION_CYCLE;
+        self.generation.store(generation, Ordering::Relaxed);
+
+        for counter in
+            [&self.stats.hits, &self.stats.misses, &self.stats.collisions, &self.stats.overwrites]
+        {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // Permille of the entries used by the current search, sampled from the first thousand
+    pub fn hashfull(&self) -> u16 {
+        let mut used = 0;
+        let mut total = 0;
+
+        for bucket in self.buckets.iter().take(1000 / BUCKET_SIZE) {
+            for entry in bucket.0.iter() {
+                let val = load(entry);
+                if THash::is_used(val) && THash::get_generation(val) == self.generation() {
+                    used += 1;
+                }
+                total += 1;
+            }
+        }
 
-        Self { entries: entries.into() }
+        (used * 1000 / total) as u16
     }
 
-    pub fn resize(&mut self, new_size: usize) {
-        let num_entries = new_size / size_of::<u128>();
-        let mut entries = Vec::with_capacity(num_entries);
-        entries.resize(num_entries, Default::default());
+    // The counters cost a shared atomic increment per probe and write, so they are off by default
+    pub fn set_collect_stats(&self, collect_stats: bool) {
+        self.collect_stats.store(collect_stats, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> TTStats {
+        TTStats {
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            collisions: self.stats.collisions.load(Ordering::Relaxed),
+            overwrites: self.stats.overwrites.load(Ordering::Relaxed),
+        }
+    }
 
-        self.entries = entries.into();
+    fn count(&self, counter: &AtomicU64) {
+        if self.collect_stats.load(Ordering::Relaxed) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
-    pub fn clear(&mut self) {
-        self.entries.fill(Default::default());
+    fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
     }
 
-    pub fn write_entry(&mut self, mut entry: THash, ply: u32) {
-        if entry.score > MATE_SCORE {
+    fn bucket(&self, key: u64) -> &Bucket {
+        &self.buckets[(key % self.buckets.len() as u64) as usize]
+    }
+
+    pub fn write_entry(&self, mut entry: THash, ply: u32) {
+        if entry.score > TB_WIN_SCORE - MAX_PLY as i32 {
             entry.score += ply as i32;
-        } else if entry.score < -MATE_SCORE {
+        } else if entry.score < -TB_WIN_SCORE + MAX_PLY as i32 {
             entry.score -= ply as i32;
         }
 
-        if entry.depth
-            >= THash::get_depth(self.entries[(entry.key % self.entries.len() as u64) as usize])
-        {
-            self.entries[(entry.key % self.entries.len() as u64) as usize] = entry.into_u128();
+        entry.generation = self.generation();
+        let mut val = entry.into_u128();
+
+        let bucket = self.bucket(entry.key);
+        let mut replace = 0;
+        let mut replace_value = i32::MAX;
+
+        for (i, stored_entry) in bucket.0.iter().enumerate() {
+            let stored = load(stored_entry);
+
+            if THash::is_used(stored) && stored as u64 == entry.key {
+                // A deeper entry of the same position from this search is only replaced by an
+                // exact score
+                if entry.flags != HashFlags::Exsact
+                    && THash::get_depth(stored) > entry.depth + 2
+                    && THash::get_generation(stored) == entry.generation
+                {
+                    return;
+                }
+
+                if entry.best_move == Move::null() {
+                    val = val & !((MOVE_MASK as u128) << 64) | stored & (MOVE_MASK as u128) << 64;
+                }
+
+                store(stored_entry, val);
+                return;
+            }
+
+            // Otherwise the shallowest entry goes, with every search of age counting as 8 plies
+            let age = (GENERATION_CYCLE + entry.generation - THash::get_generation(stored))
+                % GENERATION_CYCLE;
+            let value = THash::get_depth(stored) - 8 * age as i32;
+            if value < replace_value {
+                replace = i;
+                replace_value = value;
+            }
         }
+
+        let replaced = load(&bucket.0[replace]);
+        if THash::is_used(replaced) {
+            self.count(&self.stats.collisions);
+            if THash::get_generation(replaced) == entry.generation {
+                self.count(&self.stats.overwrites);
+            }
+        }
+
+        store(&bucket.0[replace], val);
     }
 
     pub fn probe_entry(&self, board: &Board, key: u64, ply: u32) -> Option<THash> {
-        let mut hash_entry =
-            THash::from_u128(board, self.entries[(key % self.entries.len() as u64) as usize]);
+        let Some((stored_entry, val)) = self
+            .bucket(key)
+            .0
+            .iter()
+            .map(|entry| (entry, load(entry)))
+            .find(|(_, val)| THash::is_used(*val) && *val as u64 == key)
+        else {
+            self.count(&self.stats.misses);
+            return None;
+        };
+        self.count(&self.stats.hits);
+        let mut hash_entry = THash::from_u128(board, val);
+
+        // Entries still in use are kept from aging
+        if hash_entry.generation != self.generation() {
+            let generation = (self.generation() as u128) << (64 + GENERATION_SHIFT);
+            store(stored_entry, val & !(0x1f << (64 + GENERATION_SHIFT)) | generation);
+        }
 
-        if hash_entry.score > MATE_SCORE {
+        if hash_entry.score > TB_WIN_SCORE - MAX_PLY as i32 {
             hash_entry.score -= ply as i32;
-        } else if hash_entry.score < -MATE_SCORE {
+        } else if hash_entry.score < -TB_WIN_SCORE + MAX_PLY as i32 {
             hash_entry.score += ply as i32;
         }
 
-        if hash_entry.key == key {
-            Some(hash_entry)
-        } else {
-            None
-        }
+        Some(hash_entry)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::mem::transmute;
+
     use rand::Rng;
 
     use crate::board::{movegen::generate_moves, PiecesDelta};
@@ -160,6 +368,8 @@ mod tests {
             Board::from_fen("8/2k1p3/3pP3/3P2K1/8/8/8/8 b - - 0 1").unwrap(),
             Board::from_fen("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1").unwrap(),
             Board::from_fen("8/3k4/3p4/8/3P4/3K4/8/8 b - - 0 1").unwrap(),
+            Board::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap(),
+            Board::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1").unwrap(),
         ]
     }
 
@@ -176,12 +386,14 @@ mod tests {
                 let mut delta = PiecesDelta::new();
                 new_board.make_move(r#move, &mut delta);
 
-                // Create an Entry with a random depth (0-32767) and random score (-10K to 10K)
-                let depth = rng.gen_range(0..=(0x7fff)) as i32;
-                let score = rng.gen_range(-10_000..=10_000) as i32;
-                let flags = unsafe { transmute(rng.gen_range(0..=2) as u8) }; // Random flags (2 bits)
+                // Create an Entry with a random depth (0-254), random score (-50K to 50K) and
+                // random eval (-10K to 10K)
+                let depth = rng.gen_range(0..=0xfe);
+                let score = rng.gen_range(-50_000..=50_000) as i32;
+                let eval = rng.gen_range(-10_000..=10_000);
+                let flags = unsafe { transmute::<u8, HashFlags>(rng.gen_range(0..=2) as u8) }; // Random flags (2 bits)
 
-                let entry = THash { key: rng.gen::<u64>(), depth, score, flags, best_move: r#move };
+                let entry = THash::new(rng.gen::<u64>(), depth, score, eval, r#move, flags);
 
                 let encoded = entry.into_u128();
                 let decoded = THash::from_u128(&board, encoded);
@@ -190,9 +402,130 @@ mod tests {
                 assert_eq!(decoded.key, entry.key, "Key mismatch");
                 assert_eq!(decoded.depth, entry.depth, "Depth mismatch");
                 assert_eq!(decoded.score, entry.score, "Score mismatch");
+                assert_eq!(decoded.eval, entry.eval, "Eval mismatch");
                 assert_eq!(decoded.flags, entry.flags, "Flags mismatch");
                 assert_eq!(decoded.best_move, entry.best_move, "Best move mismatch");
             }
         }
     }
+
+    #[test]
+    fn eval_bounds() {
+        let board = Board::default();
+        let decode = |eval| {
+            let entry = THash::new(1, 5, 0, eval, Move::null(), HashFlags::Exsact);
+            THash::from_u128(&board, entry.into_u128()).eval
+        };
+
+        // Evals past the 16 bits are clamped, a large one must not turn into EVAL_NONE
+        assert_eq!(decode(EVAL_NONE), EVAL_NONE);
+        assert_eq!(decode(i16::MAX as i32), i16::MAX as i32);
+        assert_eq!(decode(-EVAL_NONE), i16::MAX as i32);
+        assert_eq!(decode(100_000), i16::MAX as i32);
+        assert_eq!(decode(-100_000), EVAL_NONE);
+    }
+
+    #[test]
+    fn concurrent_writes() {
+        let tt = TTable::new(64 * size_of::<u128>());
+        let board = Board::default();
+
+        // Every thread writes entries whose score is derived from the key, into few slots so
+        // writes collide, a probe must never mix up two entries
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let tt = &tt;
+                let board = &board;
+                scope.spawn(move || {
+                    let mut rng = rand::thread_rng();
+                    for _ in 0..100000 {
+                        let key = rng.gen_range(1..1000u64).wrapping_mul(0x9E3779B97F4A7C15);
+                        let score = (key % 20000) as i32 - 10000;
+                        tt.write_entry(
+                            THash::new(key, 10, score, EVAL_NONE, Move::null(), HashFlags::Exsact),
+                            0,
+                        );
+
+                        let key = rng.gen_range(1..1000u64).wrapping_mul(0x9E3779B97F4A7C15);
+                        if let Some(entry) = tt.probe_entry(board, key, 0) {
+                            assert_eq!(entry.score, (key % 20000) as i32 - 10000);
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn replacement() {
+        // A single bucket
+        let tt = TTable::new(size_of::<Bucket>());
+        let board = Board::default();
+        let entry =
+            |key, depth| THash::new(key, depth, 0, EVAL_NONE, Move::null(), HashFlags::Beta);
+
+        for key in 1..=4 {
+            tt.write_entry(entry(key, 10 + key as i32), 0);
+        }
+        assert!((1..=4).all(|key| tt.probe_entry(&board, key, 0).is_some()));
+
+        // The shallowest entry is replaced
+        tt.write_entry(entry(5, 1), 0);
+        assert!(tt.probe_entry(&board, 1, 0).is_none());
+        assert!(tt.probe_entry(&board, 5, 0).is_some());
+
+        // A much shallower bound doesn't replace the same position from this search
+        tt.write_entry(entry(4, 2), 0);
+        assert_eq!(tt.probe_entry(&board, 4, 0).unwrap().depth, 14);
+
+        // Entries two searches old go before shallower ones from this search
+        tt.new_search();
+        tt.new_search();
+        tt.write_entry(entry(6, 1), 0);
+        tt.write_entry(entry(7, 1), 0);
+        assert!(tt.probe_entry(&board, 5, 0).is_none());
+        assert!(tt.probe_entry(&board, 2, 0).is_none());
+        assert!(tt.probe_entry(&board, 6, 0).is_some());
+        assert!(tt.probe_entry(&board, 7, 0).is_some());
+
+        // The same position from an older search is always replaced
+        tt.write_entry(entry(4, 2), 0);
+        assert_eq!(tt.probe_entry(&board, 4, 0).unwrap().depth, 2);
+    }
+
+    #[test]
+    fn hashfull_and_stats() {
+        let tt = TTable::new(1000 * size_of::<u128>());
+        let board = Board::default();
+        let entry = |key| THash::new(key, 5, 0, EVAL_NONE, Move::null(), HashFlags::Exsact);
+        assert_eq!(tt.hashfull(), 0);
+
+        // An empty slot doesn't match key zero, an entry does even at depth zero
+        assert!(tt.probe_entry(&board, 0, 0).is_none());
+        tt.write_entry(THash::new(0, 0, 0, EVAL_NONE, Move::null(), HashFlags::Exsact), 0);
+        assert_eq!(tt.probe_entry(&board, 0, 0).unwrap().depth, 0);
+        tt.clear();
+        assert!(tt.probe_entry(&board, 0, 0).is_none());
+
+        tt.set_collect_stats(true);
+        for key in 0..500u64 {
+            tt.write_entry(entry(key.wrapping_mul(0x9E3779B97F4A7C15)), 0);
+        }
+        assert!(tt.hashfull() > 300 && tt.hashfull() <= 500);
+
+        tt.probe_entry(&board, 0x9E3779B97F4A7C15, 0);
+        tt.probe_entry(&board, 12345, 0);
+        let stats = tt.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.collisions, stats.overwrites);
+
+        // Entries of older searches don't count as used and are evicted without overwriting
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+        assert_eq!(tt.stats(), TTStats::default());
+        for key in 500..600u64 {
+            tt.write_entry(entry(key.wrapping_mul(0x9E3779B97F4A7C15)), 0);
+        }
+        assert_eq!(tt.stats().overwrites, 0);
+    }
 }
@@ -1,6 +1,11 @@
 use std::{
     ops::Div,
-    sync::mpsc::{Receiver, Sender},
+    sync::{
+        atomic::Ordering,
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
@@ -10,16 +15,24 @@ use uci::{
 };
 
 use crate::board::{
+    movegen::generate_legal_moves,
     piece::{Piece, PieceColor, PieceType},
     r#move::{Move, MoveType},
     Board, NoDelta,
 };
+use crate::book::Book;
+use crate::params::SearchParams;
+use crate::root::RootMoves;
+use crate::syzygy::Tablebases;
+use crate::transposition::TTable;
 use uci::{Engine, RecivedMessage};
 
 use super::{GrandChessEngine, MATE_SCORE, MATE_VALUE, MAX_PLY, MAX_SCORE, MIN_SCORE};
 
 const VAL_WINDOW: i32 = 50;
 const MEGABYTE: usize = 0x100000;
+const MAX_THREADS: usize = 256;
+const MAX_MULTI_PV: usize = 256;
 
 impl Engine for GrandChessEngine {
     fn go(
@@ -29,52 +42,111 @@ impl Engine for GrandChessEngine {
         time_control: Option<UciTimeControl>,
         search_control: Option<UciSearchControl>,
     ) {
-        self.nnue.refresh_board(&self.board, 0);
+        if let Some(grand) = &mut self.grand {
+            grand.go(reciver, sender, time_control, search_control);
+            return;
+        }
 
-        let mut best_move = UciMove::from_to(UciSquare::from('a', 1), UciSquare::from('a', 1));
+        if let Some(book_move) = self.book_move() {
+            let _ = sender.send(RecivedMessage::BestMove(to_uci_move(book_move, self.chess960)));
+            return;
+        }
 
-        let depth = search_control.and_then(|s| s.depth.map(|d| d as u32)).unwrap_or(1000);
+        self.nnue.refresh_board(&self.board, 0);
+        self.repetition_table[0] = self.board.hash;
+        let search_moves: Vec<_> = search_control
+            .as_ref()
+            .map(|s| s.search_moves.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|uci_move| parse_move(&self.board, uci_move, self.chess960))
+            .collect();
+        self.root_moves = RootMoves::new(&self.board, &search_moves, &self.tablebase_root_moves());
+
+        let depth = search_control.as_ref().and_then(|s| s.depth.map(|d| d as u32)).unwrap_or(1000);
+        self.max_nodes = search_control.as_ref().and_then(|s| s.nodes);
         self.stop = false;
+        self.stop_flag.store(false, Ordering::Relaxed);
+        self.tt.new_search();
+        self.tt.set_collect_stats(self.hash_stats);
+        self.history.decay();
 
-        if let Some(t) = time_control {
-            match t {
-                UciTimeControl::MoveTime(t) => {
-                    self.max_time = Some(Instant::now() - Duration::new(0, 8_000_000) + t);
-                }
-                UciTimeControl::TimeLeft { white_time, black_time, moves_to_go, .. } => {
-                    let moves_to_go = if moves_to_go.unwrap_or(50) == 0 {
-                        1
-                    } else {
-                        moves_to_go.unwrap_or(50) as i32
-                    };
+        self.max_time = search_deadline(time_control, self.board.current_color);
 
-                    self.max_time = match self.board.current_color {
-                        PieceColor::White => {
-                            white_time.map(|t| Instant::now() + t.div(moves_to_go as u32))
-                        }
-                        PieceColor::Black => {
-                            black_time.map(|t| Instant::now() + t.div(moves_to_go as u32))
-                        }
-                    };
-                }
+        let mut d = 1;
 
-                _ => {}
-            }
-        }
+        self.dont_stop = true;
 
-        let mut alpha = MIN_SCORE;
-        let mut beta = MAX_SCORE;
+        // Nodes and time are reported for the whole search rather than per iteration, the node
+        // limit also applies to the whole search
+        self.node_count = 0;
+        let start = Instant::now();
 
-        let mut d = 1;
+        let helper_nodes: Vec<_> = self.helpers.iter().map(|helper| helper.nodes.clone()).collect();
+        let helper_threads = self.start_helpers(depth);
+        let mut result = SearchResult::default();
+
+        let line_count = self.multi_pv.min(self.root_moves.len()).max(1);
 
-        self.dont_stop = true;
         while d <= depth {
-            self.node_count = 0;
+            self.root_moves.new_iteration();
+
+            for pv_index in 0..line_count {
+                self.pv_index = pv_index;
+                let (mut alpha, mut beta) = if d == 1 || self.root_moves.is_empty() {
+                    (MIN_SCORE, MAX_SCORE)
+                } else {
+                    let score = self.root_moves[pv_index].previous_score;
+                    (score - VAL_WINDOW, score + VAL_WINDOW)
+                };
+
+                let score = loop {
+                    let score = self.search_root(
+                        d as i32,
+                        &mut self.board.clone(),
+                        alpha,
+                        beta,
+                        Some(reciver),
+                        Some((sender, start)),
+                    );
+                    if self.stop || (score > alpha && score < beta) {
+                        break score;
+                    }
 
-            let start = Instant::now();
-            let score = self.neg_max(d as i32, 0, &self.board.clone(), alpha, beta, Some(reciver));
+                    // Outside the window the score is only a bound, the line is searched again
+                    // with a full one
+                    let line = SearchResult {
+                        lower_bound: score >= beta,
+                        upper_bound: score <= alpha,
+                        ..self.line(d, pv_index, score)
+                    };
+                    let nodes = self.node_count
+                        + helper_nodes
+                            .iter()
+                            .map(|nodes| nodes.load(Ordering::Relaxed))
+                            .sum::<u64>();
+                    _ = sender.send(RecivedMessage::Info(line.info(
+                        pv_index + 1,
+                        nodes,
+                        start.elapsed(),
+                        self.tt.hashfull(),
+                        self.chess960,
+                    )));
+
+                    alpha = MIN_SCORE;
+                    beta = MAX_SCORE;
+                };
+
+                if self.stop {
+                    break;
+                }
 
-            let time = start.elapsed();
+                // The first line is a search of every move, so it is good to play even if the
+                // iteration doesn't complete
+                if pv_index == 0 {
+                    result = self.line(d, 0, score);
+                }
+            }
 
             self.dont_stop = false;
 
@@ -82,103 +154,84 @@ impl Engine for GrandChessEngine {
                 break;
             };
 
-            if score <= alpha || score >= beta {
-                alpha = MIN_SCORE;
-                beta = MAX_SCORE;
-                continue;
-            }
-
-            let pv: Vec<UciMove> = (0..self.pv_length[0])
-                .map(|i| {
-                    let r#move = self.pv_table[0][i];
-                    let from = UciSquare::from(
-                        (((r#move.from() as u8) % 8) + b'a') as char,
-                        r#move.from() as u8 / 8 + 1,
-                    );
-                    let to = UciSquare::from(
-                        (((r#move.to() as u8) % 8) + b'a') as char,
-                        r#move.to() as u8 / 8 + 1,
-                    );
-
-                    UciMove {
-                        from,
-                        to,
-                        promotion: if r#move.move_type() == MoveType::Promote {
-                            Some(match r#move.piece().get_type() {
-                                PieceType::Queen => UciPiece::Queen,
-                                PieceType::Rook => UciPiece::Rook,
-                                PieceType::Bishop => UciPiece::Bishop,
-                                PieceType::Knight => UciPiece::Knight,
-                                _ => UciPiece::Queen,
-                            })
-                        } else {
-                            None
-                        },
+            // Later lines can come out better than earlier ones, the best is always reported first
+            self.root_moves.finish_iteration(line_count);
+            let lines: Vec<_> = (0..line_count)
+                .map(|pv_index| {
+                    if self.root_moves.is_empty() {
+                        result.clone()
+                    } else {
+                        self.line(d, pv_index, self.root_moves[pv_index].score)
                     }
                 })
                 .collect();
+            result = lines[0].clone();
 
-            best_move = pv[0];
+            let time = start.elapsed();
+            let nodes = self.node_count
+                + helper_nodes.iter().map(|nodes| nodes.load(Ordering::Relaxed)).sum::<u64>();
+            for (pv_index, line) in lines.iter().enumerate() {
+                _ = sender.send(RecivedMessage::Info(line.info(
+                    pv_index + 1,
+                    nodes,
+                    time,
+                    self.tt.hashfull(),
+                    self.chess960,
+                )));
+            }
 
-            if score > MATE_SCORE {
-                _ = sender.send(RecivedMessage::Info(vec![
-                    UciInfoAttribute::Depth(d as u8),
-                    UciInfoAttribute::Score {
-                        cp: None,
-                        mate: Some(((score + MATE_VALUE) / 2 - 1) as i8),
-                        lower_bound: None,
-                        upper_bound: None,
-                    },
-                    UciInfoAttribute::Nodes(self.node_count),
-                    UciInfoAttribute::Nps((self.node_count as f64 / time.as_secs_f64()) as u64),
-                    UciInfoAttribute::Time(time),
-                    UciInfoAttribute::Pv(pv),
-                ]));
+            if lines.len() == 1 && result.score.abs() > MATE_SCORE {
                 break;
-            } else if score < -MATE_SCORE {
-                _ = sender.send(RecivedMessage::Info(vec![
-                    UciInfoAttribute::Depth(d as u8),
-                    UciInfoAttribute::Score {
-                        cp: None,
-                        mate: Some(((MATE_SCORE - score) / 2 + 1) as i8),
-                        lower_bound: None,
-                        upper_bound: None,
-                    },
-                    UciInfoAttribute::Nodes(self.node_count),
-                    UciInfoAttribute::Nps((self.node_count as f64 / time.as_secs_f64()) as u64),
-                    UciInfoAttribute::Time(time),
-                    UciInfoAttribute::Pv(pv),
-                ]));
-                break;
-            } else {
-                _ = sender.send(RecivedMessage::Info(vec![
-                    UciInfoAttribute::Depth(d as u8),
-                    UciInfoAttribute::Score {
-                        cp: Some(score),
-                        mate: None,
-                        lower_bound: None,
-                        upper_bound: None,
-                    },
-                    UciInfoAttribute::Nodes(self.node_count),
-                    UciInfoAttribute::Nps((self.node_count as f64 / time.as_secs_f64()) as u64),
-                    UciInfoAttribute::Time(time),
-                    UciInfoAttribute::Pv(pv),
-                ]));
             }
-            alpha = score - VAL_WINDOW;
-            beta = score + VAL_WINDOW;
+
             d += 1;
         }
 
+        self.pv_index = 0;
+
+        // A helper that got further with a better score is trusted over the main thread
+        self.stop_flag.store(true, Ordering::Relaxed);
+        let mut nodes = self.node_count;
+        let mut helper_is_best = false;
+        for thread in helper_threads {
+            let (helper, helper_result) = thread.join().unwrap();
+            nodes += helper.node_count;
+            self.helpers.push(helper);
+
+            if helper_result.depth > result.depth && helper_result.score > result.score {
+                result = helper_result;
+                helper_is_best = true;
+            }
+        }
+
+        if helper_is_best {
+            _ = sender.send(RecivedMessage::Info(result.info(
+                1,
+                nodes,
+                start.elapsed(),
+                self.tt.hashfull(),
+                self.chess960,
+            )));
+        }
+
+        let best_move = match result.pv.first() {
+            Some(best_move) => to_uci_move(*best_move, self.chess960),
+            None => UciMove::from_to(UciSquare::from('a', 1), UciSquare::from('a', 1)),
+        };
+        if self.hash_stats {
+            let stats = self.tt.stats();
+            _ = sender.send(RecivedMessage::Info(vec![UciInfoAttribute::String(format!(
+                "hash hits {} misses {} collisions {} overwrites {}",
+                stats.hits, stats.misses, stats.collisions, stats.overwrites
+            ))]));
+        }
+
         let _ = sender.send(RecivedMessage::BestMove(best_move));
 
         self.max_time = None;
+        self.max_nodes = None;
 
-        self.pv_table.fill([Move::null(); 128]);
-        self.pv_length.fill(0);
-        self.history_moves.fill([0; 64]);
-        self.killer_moves.fill([Move::null(); MAX_PLY]);
-        self.repetition_table.fill(0);
+        self.clear_search_tables();
     }
 
     fn options() -> Vec<UciOptionConfig> {
@@ -189,45 +242,329 @@ impl Engine for GrandChessEngine {
                 min: None,
                 max: None,
             },
+            UciOptionConfig::Check { name: "HashStats".to_owned(), default: Some(false) },
             UciOptionConfig::Spin {
                 name: "Threads".to_owned(),
                 default: Some(1),
                 min: Some(1),
-                max: Some(1),
+                max: Some(MAX_THREADS as i64),
+            },
+            UciOptionConfig::Spin {
+                name: "MultiPV".to_owned(),
+                default: Some(1),
+                min: Some(1),
+                max: Some(MAX_MULTI_PV as i64),
+            },
+            UciOptionConfig::Check { name: "OwnBook".to_owned(), default: Some(false) },
+            UciOptionConfig::String {
+                name: "BookFile".to_owned(),
+                default: Some("<empty>".to_owned()),
+            },
+            UciOptionConfig::Spin {
+                name: "BookDepth".to_owned(),
+                default: Some(20),
+                min: Some(0),
+                max: Some(1000),
+            },
+            UciOptionConfig::Check { name: "BookBestMove".to_owned(), default: Some(false) },
+            UciOptionConfig::String {
+                name: "SyzygyPath".to_owned(),
+                default: Some("<empty>".to_owned()),
+            },
+            UciOptionConfig::Check { name: "UCI_Chess960".to_owned(), default: Some(false) },
+            UciOptionConfig::Combo {
+                name: "UCI_Variant".to_owned(),
+                default: Some("chess".to_owned()),
+                var: vec!["chess".to_owned(), "grand".to_owned()],
             },
         ]
+        .into_iter()
+        .chain(SearchParams::options())
+        .collect()
     }
 
     fn set_option(&mut self, name: &str, value: Option<&str>) {
         match name {
-            "Hash" => self.tt.resize(match value.unwrap_or("1").parse::<usize>() {
-                Ok(num) => num * MEGABYTE,
-                Err(e) => {
-                    eprintln!("could not parse option due to error: {}", e);
-                    return;
+            "Hash" => match value.unwrap_or("1").parse::<usize>() {
+                Ok(num) => {
+                    self.tt = Arc::new(TTable::new(num * MEGABYTE));
+                    for helper in &mut self.helpers {
+                        helper.tt = self.tt.clone();
+                    }
+                }
+                Err(e) => eprintln!("could not parse option due to error: {}", e),
+            },
+            "HashStats" => self.hash_stats = value == Some("true"),
+            "Threads" => match value.unwrap_or("1").parse::<usize>() {
+                Ok(threads) => {
+                    let threads = threads.clamp(1, MAX_THREADS);
+                    self.helpers.truncate(threads - 1);
+                    while self.helpers.len() < threads - 1 {
+                        self.helpers.push(GrandChessEngine::with_tt(self.tt.clone()));
+                    }
+                }
+                Err(e) => eprintln!("could not parse option due to error: {}", e),
+            },
+            "MultiPV" => match value.unwrap_or("1").parse::<usize>() {
+                Ok(lines) => self.multi_pv = lines.clamp(1, MAX_MULTI_PV),
+                Err(e) => eprintln!("could not parse option due to error: {}", e),
+            },
+            "OwnBook" => self.own_book = value == Some("true"),
+            "BookFile" => {
+                self.book = match value.filter(|path| !path.is_empty() && *path != "<empty>") {
+                    Some(path) => match Book::open(path) {
+                        Ok(book) => Some(book),
+                        Err(e) => {
+                            eprintln!("could not open book {}: {}", path, e);
+                            None
+                        }
+                    },
+                    None => None,
                 }
-            }),
-            "Threads" => (),
-            _ => eprintln!("Invalid option {}", name),
+            }
+            "BookDepth" => match value.unwrap_or("20").parse() {
+                Ok(depth) => self.book_depth = depth,
+                Err(e) => eprintln!("could not parse option due to error: {}", e),
+            },
+            "BookBestMove" => self.book_best_move = value == Some("true"),
+            "SyzygyPath" => {
+                self.tablebases = value
+                    .filter(|path| !path.is_empty() && *path != "<empty>")
+                    .map(|path| Arc::new(Tablebases::new(path)))
+                    .filter(|tablebases| !tablebases.is_empty());
+            }
+            "UCI_Chess960" => self.chess960 = value == Some("true"),
+            "UCI_Variant" => match value {
+                Some("grand") => self.grand = Some(Box::default()),
+                Some("chess") | None => self.grand = None,
+                Some(variant) => eprintln!("unknown variant {}", variant),
+            },
+            _ => {
+                if !self.params.set(name, value) {
+                    eprintln!("Invalid option {}", name)
+                }
+            }
         }
     }
 
     fn set_pos(&mut self, fen: &str, moves: Vec<UciMove>) {
         self.board = Board::from_fen(fen).unwrap();
+        self.game_history.clear();
 
         for uci_move in moves {
-            let parsed_move = parse_move(&self.board, uci_move);
+            let parsed_move = parse_move(&self.board, uci_move, self.chess960);
+            self.game_history.push(self.board.hash);
             self.board.make_move(parsed_move, NoDelta);
-            self.ply_offset += 1;
+
+            // Nothing before an irreversible move can repeat
+            if self.board.halfmove_clock == 0 {
+                self.game_history.clear();
+            }
+        }
+    }
+
+    fn is_variant(&self) -> bool {
+        self.grand.is_some()
+    }
+
+    fn set_variant_pos(&mut self, fen: Option<&str>, moves: Vec<String>) {
+        if let Some(grand) = &mut self.grand {
+            grand.set_pos(fen, &moves);
         }
     }
 
     fn new_game(&mut self) {
         self.tt.clear();
+        self.history.clear();
+        for helper in &mut self.helpers {
+            helper.history.clear();
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+struct SearchResult {
+    depth: u32,
+    score: i32,
+    pv: Vec<Move>,
+    // Set for a score outside the aspiration window
+    lower_bound: bool,
+    upper_bound: bool,
+}
+
+impl SearchResult {
+    fn info(
+        &self,
+        multi_pv: usize,
+        nodes: u64,
+        time: Duration,
+        hashfull: u16,
+        chess960: bool,
+    ) -> Vec<UciInfoAttribute> {
+        let (cp, mate) = if self.score > MATE_SCORE {
+            (None, Some(((self.score + MATE_VALUE) / 2 - 1) as i8))
+        } else if self.score < -MATE_SCORE {
+            (None, Some(((MATE_SCORE - self.score) / 2 + 1) as i8))
+        } else {
+            (Some(self.score), None)
+        };
+
+        vec![
+            UciInfoAttribute::Depth(self.depth as u8),
+            UciInfoAttribute::MultiPv(multi_pv as u16),
+            UciInfoAttribute::Score {
+                cp,
+                mate,
+                lower_bound: self.lower_bound.then_some(true),
+                upper_bound: self.upper_bound.then_some(true),
+            },
+            UciInfoAttribute::Nodes(nodes),
+            UciInfoAttribute::Nps((nodes as f64 / time.as_secs_f64()) as u64),
+            UciInfoAttribute::Time(time),
+            UciInfoAttribute::HashFull(hashfull),
+            UciInfoAttribute::Pv(
+                self.pv.iter().map(|r#move| to_uci_move(*r#move, chess960)).collect(),
+            ),
+        ]
+    }
+}
+
+// Skipped depths of the helper threads, so that they spread over more depths than the main thread
+const SKIP_SIZE: [u32; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [u32; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+impl GrandChessEngine {
+    fn start_helpers(&mut self, depth: u32) -> Vec<JoinHandle<(GrandChessEngine, SearchResult)>> {
+        let mut threads = Vec::new();
+
+        for (i, mut helper) in self.helpers.drain(..).enumerate() {
+            helper.board = self.board.clone();
+            helper.game_history = self.game_history.clone();
+            helper.root_moves = self.root_moves.clone();
+            helper.tablebases = self.tablebases.clone();
+            helper.params = self.params.clone();
+            helper.stop_flag = self.stop_flag.clone();
+
+            threads.push(thread::spawn(move || {
+                let result = helper.helper_search(i, depth);
+                (helper, result)
+            }));
+        }
+
+        threads
+    }
+
+    // Iterative deepening without aspiration windows or output, until the main thread is done
+    fn helper_search(&mut self, helper: usize, max_depth: u32) -> SearchResult {
+        self.nnue.refresh_board(&self.board, 0);
+        self.repetition_table[0] = self.board.hash;
+        self.node_count = 0;
+        self.nodes.store(0, Ordering::Relaxed);
+        self.stop = false;
+        self.dont_stop = false;
+        self.history.decay();
+
+        let mut board = self.board.clone();
+        let skip = helper % SKIP_SIZE.len();
+        let mut result = SearchResult::default();
+
+        for depth in 1..=max_depth {
+            if depth > 1 && ((depth + SKIP_PHASE[skip]) / SKIP_SIZE[skip]) % 2 != 0 {
+                continue;
+            }
+
+            self.root_moves.new_iteration();
+            let score =
+                self.search_root(depth as i32, &mut board, MIN_SCORE, MAX_SCORE, None, None);
+            if self.stop {
+                break;
+            }
+            self.root_moves.finish_iteration(1);
+
+            result = SearchResult {
+                depth,
+                score,
+                pv: self.pv_table[0][..self.pv_length[0]].to_vec(),
+                ..Default::default()
+            };
+        }
+
+        self.clear_search_tables();
+        result
+    }
+
+    // The line of the root move at `pv_index`, empty without root moves
+    fn line(&self, depth: u32, pv_index: usize, score: i32) -> SearchResult {
+        let pv = if self.root_moves.is_empty() {
+            Vec::new()
+        } else {
+            self.root_moves[pv_index].pv.clone()
+        };
+        SearchResult { depth, score, pv, ..Default::default() }
+    }
+
+    fn clear_search_tables(&mut self) {
+        self.pv_table.fill([Move::null(); 128]);
+        self.pv_length.fill(0);
+        self.killer_moves.fill([Move::null(); MAX_PLY]);
+        self.repetition_table.fill(0);
+    }
+
+    fn book_move(&self) -> Option<Move> {
+        let book = self.book.as_ref().filter(|_| self.own_book)?;
+        if self.board.fullmove_number > self.book_depth {
+            return None;
+        }
+
+        if self.book_best_move {
+            book.best_move(&self.board)
+        } else {
+            book.weighted_move(&self.board, &mut rand::thread_rng())
+        }
+    }
+
+    // Restricts the root to the moves that keep the best tablebase result
+    fn tablebase_root_moves(&self) -> Vec<Move> {
+        let Some(tablebases) = self.tablebases.as_ref().filter(|tablebases| {
+            self.board.castle_flags.is_empty()
+                && self.board.bit_boards.occupancy().count_ones() as usize
+                    <= tablebases.cardinality()
+        }) else {
+            return Vec::new();
+        };
+
+        // Once a position repeated the fastest win has to be played to make progress
+        let mut positions = self.game_history.clone();
+        positions.push(self.board.hash);
+        positions.sort();
+        let has_repeated = positions.windows(2).any(|pair| pair[0] == pair[1]);
+
+        tablebases.root_moves(&self.board, has_repeated).unwrap_or_default()
     }
 }
 
-pub fn parse_move(board: &Board, uci_move: UciMove) -> Move {
+// When the search has to stop, shared with the Grand Chess search
+pub(crate) fn search_deadline(
+    time_control: Option<UciTimeControl>,
+    color: PieceColor,
+) -> Option<Instant> {
+    match time_control? {
+        UciTimeControl::MoveTime(t) => Some(Instant::now() - Duration::new(0, 8_000_000) + t),
+        UciTimeControl::TimeLeft { white_time, black_time, moves_to_go, .. } => {
+            let moves_to_go =
+                if moves_to_go.unwrap_or(50) == 0 { 1 } else { moves_to_go.unwrap_or(50) as i32 };
+
+            match color {
+                PieceColor::White => white_time.map(|t| Instant::now() + t.div(moves_to_go as u32)),
+                PieceColor::Black => black_time.map(|t| Instant::now() + t.div(moves_to_go as u32)),
+            }
+        }
+
+        _ => None,
+    }
+}
+
+pub fn parse_move(board: &Board, uci_move: UciMove, chess960: bool) -> Move {
     let from = ((uci_move.from.file as u8 - b'a') + (8 * (uci_move.from.rank - 1))) as usize;
     let to = ((uci_move.to.file as u8 - b'a') + (8 * (uci_move.to.rank - 1))) as usize;
 
@@ -244,55 +581,20 @@ pub fn parse_move(board: &Board, uci_move: UciMove) -> Move {
         );
     }
 
-    // Check if the move is a castling move
-    if from == 4
-        && to == 6
-        && board.piece_at(from).get_type() == PieceType::King
-        && board.current_color == PieceColor::White
+    // Castling is written as the king moving two squares, or as the king taking its own rook in
+    // Chess960
+    if board.piece_at(from) == Piece::new(PieceType::King, board.current_color)
+        && (chess960 || from.abs_diff(to) == 2)
     {
-        return Move::new(
-            from as u32,
-            to as u32,
-            MoveType::KingCastle,
-            Piece::new(PieceType::King, board.current_color),
-            PieceType::Empty,
-        );
-    } else if from == 4
-        && to == 2
-        && board.piece_at(from).get_type() == PieceType::King
-        && board.current_color == PieceColor::White
-    {
-        return Move::new(
-            from as u32,
-            to as u32,
-            MoveType::QueenCastle,
-            Piece::new(PieceType::King, board.current_color),
-            PieceType::Empty,
-        );
-    } else if from == 60
-        && to == 62
-        && board.piece_at(from).get_type() == PieceType::King
-        && board.current_color == PieceColor::Black
-    {
-        return Move::new(
-            from as u32,
-            to as u32,
-            MoveType::KingCastle,
-            Piece::new(PieceType::King, board.current_color),
-            PieceType::Empty,
-        );
-    } else if from == 60
-        && to == 58
-        && board.piece_at(from).get_type() == PieceType::King
-        && board.current_color == PieceColor::Black
-    {
-        return Move::new(
-            from as u32,
-            to as u32,
-            MoveType::QueenCastle,
-            Piece::new(PieceType::King, board.current_color),
-            PieceType::Empty,
-        );
+        let castle = generate_legal_moves(board).into_iter().find(|r#move| {
+            r#move.is_castle()
+                && r#move.from() as usize == from
+                && to as u32 == if chess960 { r#move.to() } else { r#move.castling_squares().0 }
+        });
+
+        if let Some(castle) = castle {
+            return castle;
+        }
     }
 
     // Check if the move is an en passant capture
@@ -351,10 +653,13 @@ pub fn parse_move(board: &Board, uci_move: UciMove) -> Move {
     }
 }
 
-pub fn to_uci_move(r#move: Move) -> UciMove {
+// Castling is the king taking its own rook in Chess960, and the king's move otherwise
+pub fn to_uci_move(r#move: Move, chess960: bool) -> UciMove {
+    let to =
+        if r#move.is_castle() && !chess960 { r#move.castling_squares().0 } else { r#move.to() };
     let from =
         UciSquare::from((((r#move.from() as u8) % 8) + b'a') as char, r#move.from() as u8 / 8 + 1);
-    let to = UciSquare::from((((r#move.to() as u8) % 8) + b'a') as char, r#move.to() as u8 / 8 + 1);
+    let to = UciSquare::from((((to as u8) % 8) + b'a') as char, to as u8 / 8 + 1);
     UciMove {
         from,
         to,
@@ -0,0 +1,150 @@
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+        Mutex,
+    },
+    time::Duration,
+};
+
+use engine::{
+    epd::{parse_epd_line, EpdEntry},
+    uci::parse_move,
+    GrandChessEngine,
+};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use uci::{Engine, RecivedMessage, UciSearchControl, UciTimeControl};
+
+const MEGABYTE: usize = 0x100000;
+
+pub struct EpdOptions {
+    pub time: Option<u64>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub threads: usize,
+    pub hash: usize,
+}
+
+impl Default for EpdOptions {
+    fn default() -> Self {
+        Self { time: None, depth: None, nodes: None, threads: 1, hash: 16 }
+    }
+}
+
+impl EpdOptions {
+    // Parses "--time 1000 --depth 10 --nodes 100000 --threads 4 --hash 16"
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or(format!("missing value for {}", flag))?;
+            let invalid = |_| format!("invalid value for {}: {}", flag, value);
+
+            match flag.as_str() {
+                "--time" => options.time = Some(value.parse().map_err(invalid)?),
+                "--depth" => options.depth = Some(value.parse().map_err(invalid)?),
+                "--nodes" => options.nodes = Some(value.parse().map_err(invalid)?),
+                "--threads" => options.threads = value.parse().map_err(invalid)?,
+                "--hash" => options.hash = value.parse().map_err(invalid)?,
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+
+        // Without any limit every position would be searched forever
+        if options.time.is_none() && options.depth.is_none() && options.nodes.is_none() {
+            options.time = Some(1000);
+        }
+
+        Ok(options)
+    }
+}
+
+fn solve(engine: &mut GrandChessEngine, entry: &EpdEntry, options: &EpdOptions) -> bool {
+    let (_stop_sender, stop_reciver) = channel();
+    let (mut sender, reciver) = channel();
+
+    engine.new_game();
+    engine.set_pos(&entry.board.to_fen(), Vec::new());
+    engine.go(
+        &stop_reciver,
+        &mut sender,
+        options.time.map(|t| UciTimeControl::MoveTime(Duration::from_millis(t))),
+        Some(UciSearchControl {
+            search_moves: Vec::new(),
+            mate: None,
+            depth: options.depth,
+            nodes: options.nodes,
+        }),
+    );
+
+    let best_move = reciver.try_iter().find_map(|message| match message {
+        RecivedMessage::BestMove(best_move) => Some(parse_move(&entry.board, best_move, false)),
+        _ => None,
+    });
+
+    let id = entry.id.as_deref().unwrap_or("?");
+    let expected = |moves: &[engine::board::r#move::Move]| {
+        moves.iter().map(|r#move| r#move.to_san(&entry.board)).collect::<Vec<_>>().join(" ")
+    };
+
+    match best_move {
+        Some(best_move) => {
+            let solved = entry.is_solved_by(best_move);
+            println!(
+                "{}: {} played {} (bm {}; am {})",
+                id,
+                if solved { "solved" } else { "failed" },
+                best_move.to_san(&entry.board),
+                expected(&entry.best_moves),
+                expected(&entry.avoid_moves),
+            );
+            solved
+        }
+        None => {
+            println!("{}: failed, no move returned", id);
+            false
+        }
+    }
+}
+
+pub fn run(path: &str, options: EpdOptions) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("could not read {}: {}", path, e);
+            return;
+        }
+    };
+
+    let mut entries = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_epd_line(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("skipping line {}: {:?}", line_number + 1, e),
+        }
+    }
+
+    let solved = AtomicUsize::new(0);
+
+    // One engine per worker, `solve` resets it between positions
+    let engines: Vec<_> = (0..options.threads)
+        .map(|_| Mutex::new(GrandChessEngine::new(options.hash * MEGABYTE)))
+        .collect();
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(options.threads).build().unwrap();
+    pool.install(|| {
+        entries.par_iter().with_max_len(1).for_each(|entry| {
+            let mut engine = engines[rayon::current_thread_index().unwrap()].lock().unwrap();
+            if solve(&mut engine, entry, &options) {
+                solved.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    });
+
+    println!("solved {} of {} positions", solved.load(Ordering::Relaxed), entries.len());
+}
@@ -7,6 +7,8 @@ use std::{
 
 pub use vampirc_uci::*;
 
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
 pub trait Engine {
     fn id() -> (Option<String>, Option<String>) {
         (None, None)
@@ -27,14 +29,22 @@ pub trait Engine {
     fn new_game(&mut self);
 
     fn set_pos(&mut self, fen: &str, moves: Vec<UciMove>);
+
+    // Variants on other boards get the position as the GUI wrote it, `None` being the variant's
+    // start position, and answer with `RecivedMessage::VariantBestMove`
+    fn is_variant(&self) -> bool {
+        false
+    }
+
+    fn set_variant_pos(&mut self, _fen: Option<&str>, _moves: Vec<String>) {}
 }
 
 pub enum EngineCommand {
     NewGame,
     IsReady,
     SetPos {
-        fen: String,
-        moves: Vec<UciMove>,
+        fen: Option<String>,
+        moves: Vec<String>,
     },
     Go {
         time_control: Option<vampirc_uci::UciTimeControl>,
@@ -48,6 +58,8 @@ pub enum EngineCommand {
 #[allow(clippy::large_enum_variant)]
 pub enum RecivedMessage {
     BestMove(UciMove),
+    VariantBestMove(String),
+    Position { fen: Option<String>, moves: Vec<String> },
     Info(Vec<UciInfoAttribute>),
     ReadyOk,
     Uci(UciMessage),
@@ -83,7 +95,12 @@ impl<W: Write, E: 'static + Engine + Send> UciConnection<W, E> {
                         engine.go(&stop_recv, &mut message_sender, time_control, search_control);
                     }
                     EngineCommand::SetPos { fen, moves } => {
-                        engine.set_pos(&fen, moves);
+                        if engine.is_variant() {
+                            engine.set_variant_pos(fen.as_deref(), moves);
+                        } else {
+                            engine
+                                .set_pos(fen.as_deref().unwrap_or(START_FEN), parse_moves(&moves));
+                        }
                     }
                     EngineCommand::NewGame => {
                         engine.new_game();
@@ -100,7 +117,13 @@ impl<W: Write, E: 'static + Engine + Send> UciConnection<W, E> {
 
         let input_thread = thread::spawn(move || {
             for line in reader.lines() {
-                if input_sender.send(RecivedMessage::Uci(parse_one(&line.unwrap()))).is_err() {
+                let line = line.unwrap();
+                let message = match parse_position(&line) {
+                    Some((fen, moves)) => RecivedMessage::Position { fen, moves },
+                    None => RecivedMessage::Uci(parse_one(&line)),
+                };
+
+                if input_sender.send(message).is_err() {
                     break;
                 }
             }
@@ -138,16 +161,6 @@ impl<W: Write, E: 'static + Engine + Send> UciConnection<W, E> {
                             .engine_sender
                             .send(EngineCommand::Go { time_control, search_control });
                     }
-                    UciMessage::Position { startpos, fen, moves } => {
-                        let fen = if startpos || fen.is_none() {
-                            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
-                        } else {
-                            fen.as_ref().unwrap().0.as_str()
-                        }
-                        .to_string();
-
-                        _ = self.engine_sender.send(EngineCommand::SetPos { fen, moves });
-                    }
                     UciMessage::UciNewGame => {
                         _ = self.engine_sender.send(EngineCommand::NewGame);
                         self.writer.write_fmt(format_args!("{}\n", UciMessage::ReadyOk)).unwrap();
@@ -170,6 +183,12 @@ impl<W: Write, E: 'static + Engine + Send> UciConnection<W, E> {
                     }
                     _ => {}
                 },
+                RecivedMessage::Position { fen, moves } => {
+                    _ = self.engine_sender.send(EngineCommand::SetPos { fen, moves });
+                }
+                RecivedMessage::VariantBestMove(best_move) => {
+                    self.writer.write_fmt(format_args!("bestmove {}\n", best_move)).unwrap();
+                }
                 RecivedMessage::BestMove(uci_move) => {
                     self.writer
                         .write_fmt(format_args!(
@@ -191,3 +210,102 @@ impl<W: Write, E: 'static + Engine + Send> UciConnection<W, E> {
         self.engine_thread.join().unwrap();
     }
 }
+
+// `position` is read here rather than by vampirc, which only knows 8x8 boards and quietly drops
+// the moves it can't read
+fn parse_position(line: &str) -> Option<(Option<String>, Vec<String>)> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.first() != Some(&"position") {
+        return None;
+    }
+
+    let moves_index = words.iter().position(|word| *word == "moves").unwrap_or(words.len());
+    let fen = match words[1..moves_index] {
+        ["startpos"] => None,
+        ["fen", ref fen @ ..] if !fen.is_empty() => Some(fen.join(" ")),
+        _ => return None,
+    };
+
+    Some((fen, words.iter().skip(moves_index + 1).map(|word| word.to_string()).collect()))
+}
+
+fn parse_move(text: &str) -> Option<UciMove> {
+    let bytes = text.as_bytes();
+    if !(4..=5).contains(&bytes.len()) {
+        return None;
+    }
+
+    let square = |file: u8, rank: u8| {
+        ((b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank))
+            .then(|| UciSquare::from(file as char, rank - b'0'))
+    };
+    let promotion = match bytes.get(4) {
+        None => None,
+        Some(b'q') => Some(UciPiece::Queen),
+        Some(b'r') => Some(UciPiece::Rook),
+        Some(b'b') => Some(UciPiece::Bishop),
+        Some(b'n') => Some(UciPiece::Knight),
+        Some(_) => return None,
+    };
+
+    Some(UciMove { from: square(bytes[0], bytes[1])?, to: square(bytes[2], bytes[3])?, promotion })
+}
+
+// The moves up to the first one that can't be read, which is reported like an illegal variant move
+fn parse_moves(moves: &[String]) -> Vec<UciMove> {
+    let mut parsed = Vec::new();
+    for text in moves {
+        match parse_move(text) {
+            Some(r#move) => parsed.push(r#move),
+            None => {
+                eprintln!("invalid move: {}", text);
+                break;
+            }
+        }
+    }
+
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position() {
+        assert_eq!(parse_position("position startpos"), Some((None, Vec::new())));
+        assert_eq!(
+            parse_position("position startpos moves e2e4 e7e5"),
+            Some((None, vec!["e2e4".to_owned(), "e7e5".to_owned()]))
+        );
+
+        let fen = "r8r/1nbqkmcbn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R w - - 0 1";
+        assert_eq!(
+            parse_position(&format!("position fen {} moves j3j4 a8a7", fen)),
+            Some((Some(fen.to_owned()), vec!["j3j4".to_owned(), "a8a7".to_owned()]))
+        );
+
+        assert_eq!(parse_position("position"), None);
+        assert_eq!(parse_position("position fen moves e2e4"), None);
+        assert_eq!(parse_position("go depth 5"), None);
+    }
+
+    #[test]
+    fn chess_moves() {
+        assert_eq!(
+            parse_move("e2e4"),
+            Some(UciMove::from_to(UciSquare::from('e', 2), UciSquare::from('e', 4)))
+        );
+        assert_eq!(parse_move("a7a8n").unwrap().promotion, Some(UciPiece::Knight));
+        assert_eq!(parse_move("j3j4"), None);
+        assert_eq!(parse_move("e9e10"), None);
+        assert_eq!(parse_move("e7e8k"), None);
+
+        let moves = ["e2e4", "e7e5", "e9e10", "g1f3"].map(str::to_owned);
+        assert_eq!(
+            parse_moves(&moves),
+            vec![parse_move("e2e4").unwrap(), parse_move("e7e5").unwrap()]
+        );
+        assert_eq!(parse_moves(&moves[2..]), Vec::new());
+    }
+}